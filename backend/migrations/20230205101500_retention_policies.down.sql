@@ -0,0 +1,7 @@
+-- Add down migration script here
+drop index messages_group_id_sent_at_idx;
+
+drop table group_retention_changes;
+
+alter table groups
+    drop retention_days;
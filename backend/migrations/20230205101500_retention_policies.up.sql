@@ -0,0 +1,16 @@
+-- Add up migration script here
+alter table groups
+    add retention_days int check (retention_days > 0);
+
+create table group_retention_changes (
+    id serial primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    old_retention_days int,
+    new_retention_days int,
+    changed_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id),
+    foreign key (user_id) references users(id)
+);
+
+create index messages_group_id_sent_at_idx on messages (group_id, sent_at);
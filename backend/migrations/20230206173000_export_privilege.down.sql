@@ -0,0 +1,3 @@
+-- Add down migration script here
+alter table roles
+    drop can_export;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+alter table roles
+    add can_export bool not null default false;
+
+update roles
+    set can_export = true
+    from group_roles
+    where group_roles.role_id = roles.id
+    and group_roles.role_type in ('owner', 'admin');
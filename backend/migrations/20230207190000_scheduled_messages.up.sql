@@ -0,0 +1,13 @@
+-- Add up migration script here
+create table scheduled_messages (
+    id serial primary key,
+    user_id uuid not null,
+    group_id uuid not null,
+    content text not null,
+    send_at timestamptz not null,
+    created_at timestamptz not null default now(),
+    foreign key (user_id) references users(id),
+    foreign key (group_id) references groups(id)
+);
+
+create index scheduled_messages_send_at_idx on scheduled_messages (send_at);
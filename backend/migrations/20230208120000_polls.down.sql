@@ -0,0 +1,10 @@
+-- Add down migration script here
+alter table messages
+drop column poll_id,
+drop column kind;
+
+drop type message_kind;
+
+drop table poll_votes;
+drop table poll_options;
+drop table polls;
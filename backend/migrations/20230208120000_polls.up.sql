@@ -0,0 +1,40 @@
+-- Add up migration script here
+create table polls (
+    id serial primary key,
+    user_id uuid not null,
+    group_id uuid not null,
+    question text not null,
+    multiple_choice bool not null default false,
+    anonymous bool not null default false,
+    closes_at timestamptz,
+    closed_at timestamptz,
+    created_at timestamptz not null default now(),
+    foreign key (user_id) references users(id),
+    foreign key (group_id) references groups(id)
+);
+
+create index polls_closes_at_idx on polls (closes_at) where closed_at is null;
+
+create table poll_options (
+    id serial primary key,
+    poll_id int not null,
+    position int not null,
+    content text not null,
+    unique (poll_id, position),
+    foreign key (poll_id) references polls(id) on delete cascade
+);
+
+create table poll_votes (
+    option_id int not null,
+    user_id uuid not null,
+    voted_at timestamptz not null default now(),
+    primary key (option_id, user_id),
+    foreign key (option_id) references poll_options(id) on delete cascade,
+    foreign key (user_id) references users(id)
+);
+
+create type message_kind as enum ('text', 'poll', 'poll_result');
+
+alter table messages
+add kind message_kind not null default 'text',
+add poll_id int references polls(id) on delete set null;
@@ -0,0 +1,17 @@
+-- Add down migration script here
+delete from messages where webhook_id is not null;
+
+alter table messages
+drop constraint messages_author_check,
+drop column webhook_id,
+alter column user_id set not null;
+
+alter type message_kind rename to message_kind_old;
+create type message_kind as enum ('text', 'poll', 'poll_result');
+alter table messages
+alter column kind drop default,
+alter column kind type message_kind using kind::text::message_kind,
+alter column kind set default 'text';
+drop type message_kind_old;
+
+drop table group_webhooks;
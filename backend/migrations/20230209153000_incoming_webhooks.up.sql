@@ -0,0 +1,21 @@
+-- Add up migration script here
+create table group_webhooks (
+    id uuid not null default gen_random_uuid() primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    name text not null,
+    token_hash text not null,
+    created_at timestamptz not null default now(),
+    revoked_at timestamptz,
+    foreign key (group_id) references groups(id),
+    foreign key (user_id) references users(id)
+);
+
+alter type message_kind add value 'webhook';
+
+alter table messages
+alter column user_id drop not null,
+add webhook_id uuid references group_webhooks(id),
+add constraint messages_author_check check (user_id is not null or webhook_id is not null);
+
+create index messages_webhook_id_sent_at_idx on messages (webhook_id, sent_at) where webhook_id is not null;
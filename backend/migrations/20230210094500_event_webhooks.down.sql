@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table event_deliveries;
+drop table event_webhooks;
+drop type delivery_status;
+drop type group_event;
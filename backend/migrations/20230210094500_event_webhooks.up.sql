@@ -0,0 +1,33 @@
+-- Add up migration script here
+create type group_event as enum ('message_created', 'member_joined', 'member_kicked', 'role_changed');
+create type delivery_status as enum ('pending', 'delivered', 'failed');
+
+create table event_webhooks (
+    id uuid not null default gen_random_uuid() primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    url text not null,
+    secret text not null,
+    events group_event[] not null,
+    created_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id),
+    foreign key (user_id) references users(id)
+);
+
+create table event_deliveries (
+    id serial primary key,
+    webhook_id uuid not null,
+    event group_event not null,
+    payload jsonb not null,
+    status delivery_status not null default 'pending',
+    attempts int not null default 0,
+    next_attempt_at timestamptz not null default now(),
+    last_status_code int,
+    last_error text,
+    created_at timestamptz not null default now(),
+    delivered_at timestamptz,
+    foreign key (webhook_id) references event_webhooks(id) on delete cascade
+);
+
+create index event_deliveries_pending_idx on event_deliveries (next_attempt_at) where status = 'pending';
+create index event_deliveries_webhook_id_idx on event_deliveries (webhook_id, id);
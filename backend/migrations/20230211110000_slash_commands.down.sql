@@ -0,0 +1,12 @@
+-- Add down migration script here
+drop table group_mutes;
+
+update messages set kind = 'text' where kind = 'action';
+
+alter type message_kind rename to message_kind_old;
+create type message_kind as enum ('text', 'poll', 'poll_result', 'webhook');
+alter table messages
+alter column kind drop default,
+alter column kind type message_kind using kind::text::message_kind,
+alter column kind set default 'text';
+drop type message_kind_old;
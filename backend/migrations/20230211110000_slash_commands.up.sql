@@ -0,0 +1,16 @@
+-- Add up migration script here
+alter type message_kind add value 'action';
+
+create table group_mutes (
+    id serial primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    muted_by uuid,
+    muted_until timestamptz not null,
+    created_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id) on delete cascade,
+    foreign key (user_id) references users(id) on delete cascade,
+    foreign key (muted_by) references users(id) on delete set null
+);
+
+create index group_mutes_group_id_user_id_idx on group_mutes (group_id, user_id, muted_until);
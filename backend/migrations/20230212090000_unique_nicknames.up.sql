@@ -0,0 +1,2 @@
+-- Add up migration script here
+alter table groups add unique_nicknames boolean not null default false;
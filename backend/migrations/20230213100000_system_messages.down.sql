@@ -0,0 +1,15 @@
+-- Add down migration script here
+delete from messages where system_event is not null;
+
+alter table messages
+drop constraint messages_author_check,
+drop column system_event,
+add constraint messages_author_check check (user_id is not null or webhook_id is not null);
+
+alter type message_kind rename to message_kind_old;
+create type message_kind as enum ('text', 'poll', 'poll_result', 'webhook', 'action');
+alter table messages
+alter column kind drop default,
+alter column kind type message_kind using kind::text::message_kind,
+alter column kind set default 'text';
+drop type message_kind_old;
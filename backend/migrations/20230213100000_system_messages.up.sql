@@ -0,0 +1,7 @@
+-- Add up migration script here
+alter type message_kind add value 'system';
+
+alter table messages
+add system_event jsonb,
+drop constraint messages_author_check,
+add constraint messages_author_check check (user_id is not null or webhook_id is not null or system_event is not null);
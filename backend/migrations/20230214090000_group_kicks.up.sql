@@ -0,0 +1,16 @@
+-- Add up migration script here
+create table group_kicks (
+    id serial primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    nickname text not null,
+    kicked_by uuid,
+    moderator text not null,
+    reason text,
+    created_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id) on delete cascade,
+    foreign key (user_id) references users(id) on delete cascade,
+    foreign key (kicked_by) references users(id) on delete set null
+);
+
+create index group_kicks_group_id_idx on group_kicks (group_id, id);
@@ -0,0 +1,19 @@
+-- Add up migration script here
+create table group_bans (
+    id serial primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    banned_by uuid,
+    reason text,
+    expires_at timestamptz,
+    created_at timestamptz not null default now(),
+    lifted_by uuid,
+    lifted_at timestamptz,
+    foreign key (group_id) references groups(id) on delete cascade,
+    foreign key (user_id) references users(id) on delete cascade,
+    foreign key (banned_by) references users(id) on delete set null,
+    foreign key (lifted_by) references users(id) on delete set null
+);
+
+-- a user has at most one ban that was not lifted by a moderator, expired ones included
+create unique index group_bans_group_id_user_id_idx on group_bans (group_id, user_id) where lifted_at is null;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+drop index group_mutes_expiry_idx;
+
+alter table group_mutes
+    drop column lifted_by,
+    drop column lifted_at,
+    drop column expiry_announced;
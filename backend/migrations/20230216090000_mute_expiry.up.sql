@@ -0,0 +1,10 @@
+-- Add up migration script here
+alter table group_mutes
+    add column lifted_by uuid,
+    add column lifted_at timestamptz,
+    add column expiry_announced boolean not null default false,
+    add foreign key (lifted_by) references users(id) on delete set null;
+
+update group_mutes set expiry_announced = true where muted_until <= now();
+
+create index group_mutes_expiry_idx on group_mutes (muted_until) where not expiry_announced;
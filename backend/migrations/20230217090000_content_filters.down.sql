@@ -0,0 +1,5 @@
+-- Add down migration script here
+drop table message_flags;
+drop table group_content_filters;
+drop type filter_action;
+drop type filter_kind;
@@ -0,0 +1,30 @@
+-- Add up migration script here
+create type filter_kind as enum ('word', 'regex');
+create type filter_action as enum ('block', 'mask', 'flag');
+
+create table group_content_filters (
+    id serial primary key,
+    group_id uuid not null,
+    kind filter_kind not null,
+    pattern text not null,
+    action filter_action not null,
+    created_by uuid,
+    created_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id) on delete cascade,
+    foreign key (created_by) references users(id) on delete set null
+);
+
+create index group_content_filters_group_id_idx on group_content_filters (group_id);
+
+create table message_flags (
+    id serial primary key,
+    message_id int not null,
+    group_id uuid not null,
+    filter_id int,
+    created_at timestamptz not null default now(),
+    foreign key (message_id) references messages(id) on delete cascade,
+    foreign key (group_id) references groups(id) on delete cascade,
+    foreign key (filter_id) references group_content_filters(id) on delete set null
+);
+
+create index message_flags_group_id_idx on message_flags (group_id, id);
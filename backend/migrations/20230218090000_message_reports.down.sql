@@ -0,0 +1,4 @@
+-- Add down migration script here
+drop table message_reports;
+drop type report_resolution;
+drop type report_category;
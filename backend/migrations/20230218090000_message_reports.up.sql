@@ -0,0 +1,28 @@
+-- Add up migration script here
+create type report_category as enum ('spam', 'harassment', 'inappropriate', 'other');
+create type report_resolution as enum ('dismissed', 'message_deleted', 'author_muted', 'author_kicked');
+
+create table message_reports (
+    id serial primary key,
+    group_id uuid not null,
+    message_id int,
+    author_id uuid,
+    author text not null,
+    content text not null,
+    reporter_id uuid,
+    category report_category not null,
+    comment text,
+    created_at timestamptz not null default now(),
+    resolution report_resolution,
+    resolved_by uuid,
+    resolved_at timestamptz,
+    foreign key (group_id) references groups(id) on delete cascade,
+    foreign key (message_id) references messages(id) on delete set null,
+    foreign key (author_id) references users(id) on delete set null,
+    foreign key (reporter_id) references users(id) on delete set null,
+    foreign key (resolved_by) references users(id) on delete set null,
+    check ((resolution is null) = (resolved_at is null))
+);
+
+create index message_reports_group_id_idx on message_reports (group_id, id);
+create unique index message_reports_open_idx on message_reports (message_id, reporter_id) where resolved_at is null;
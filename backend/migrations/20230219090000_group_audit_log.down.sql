@@ -0,0 +1,22 @@
+-- Add down migration script here
+create table group_retention_changes (
+    id serial primary key,
+    group_id uuid not null,
+    user_id uuid not null,
+    old_retention_days int,
+    new_retention_days int,
+    changed_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id),
+    foreign key (user_id) references users(id)
+);
+
+insert into group_retention_changes (group_id, user_id, old_retention_days, new_retention_days, changed_at)
+select group_id, actor_id, (before #>> '{}')::int, (after #>> '{}')::int, created_at
+from group_audit_log
+where action = 'retention_changed' and actor_id is not null
+order by id;
+
+drop trigger group_audit_log_append_only on group_audit_log;
+drop function forbid_audit_log_changes;
+drop table group_audit_log;
+drop type audit_action;
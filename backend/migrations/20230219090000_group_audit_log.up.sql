@@ -0,0 +1,51 @@
+-- Add up migration script here
+create type audit_action as enum (
+    'role_changed',
+    'privilege_changed',
+    'member_kicked',
+    'member_banned',
+    'ban_lifted',
+    'member_muted',
+    'mute_lifted',
+    'invitation_created',
+    'retention_changed',
+    'nickname_policy_changed',
+    'filter_created',
+    'filter_updated',
+    'filter_deleted',
+    'report_resolved'
+);
+
+create table group_audit_log (
+    id serial primary key,
+    group_id uuid not null,
+    actor_id uuid,
+    action audit_action not null,
+    target_user_id uuid,
+    target text,
+    before jsonb,
+    after jsonb,
+    created_at timestamptz not null default now(),
+    foreign key (group_id) references groups(id),
+    foreign key (actor_id) references users(id),
+    foreign key (target_user_id) references users(id)
+);
+
+create index group_audit_log_group_id_idx on group_audit_log (group_id, id);
+
+create function forbid_audit_log_changes() returns trigger as $$
+begin
+    raise exception 'group_audit_log is append-only';
+end;
+$$ language plpgsql;
+
+create trigger group_audit_log_append_only
+    before update or delete on group_audit_log
+    for each row execute function forbid_audit_log_changes();
+
+insert into group_audit_log (group_id, actor_id, action, target, before, after, created_at)
+select group_id, user_id, 'retention_changed', 'retention_days', to_jsonb(old_retention_days), to_jsonb(new_retention_days), changed_at
+from group_retention_changes
+order by id;
+
+drop table group_retention_changes;
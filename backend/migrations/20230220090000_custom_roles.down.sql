@@ -0,0 +1,53 @@
+-- Add down migration script here
+create or replace function add_group_roles(group_id uuid) returns void as $$
+    declare
+        role_type user_role;
+        privs jsonb;
+        privilege_arr default_privileges[] := array[
+            ('owner', '{"can_invite": "Yes", "can_send_messages": {"Yes": 0}}'),
+            ('admin', '{"can_invite": "Yes", "can_send_messages": {"Yes": 0}}'),
+            ('member', '{"can_invite": "Yes", "can_send_messages": {"Yes": 0}}')
+        ];
+    begin
+    for role_type, privs in select role, privileges from unnest(privilege_arr)
+    loop
+        declare new_role_id uuid;
+        begin
+            insert into roles(privileges)
+                values (privs)
+                returning id into new_role_id;
+
+            insert into group_roles(group_id, role_id, role_type)
+            values (group_id, new_role_id, role_type);
+        end;
+    end loop;
+    end;
+$$ language plpgsql;
+
+-- members of custom roles fall back to the member role
+update group_users
+    set role_id = member_roles.role_id
+    from group_roles custom_roles, group_roles member_roles
+    where custom_roles.role_id = group_users.role_id
+    and custom_roles.custom
+    and member_roles.group_id = group_users.group_id
+    and member_roles.role_type = 'member'
+    and not member_roles.custom;
+
+-- enum values can't be dropped and the audit log is append-only, audit records of custom roles stay
+
+create temporary table custom_role_ids as
+    select role_id from group_roles where custom;
+delete from group_roles where custom;
+delete from roles where id in (select role_id from custom_role_ids);
+drop table custom_role_ids;
+
+drop index group_roles_built_in;
+
+alter table group_roles
+    drop custom;
+
+alter table roles
+    drop name,
+    drop color,
+    drop position;
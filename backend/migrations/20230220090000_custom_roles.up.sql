@@ -0,0 +1,71 @@
+-- Add up migration script here
+alter table roles
+    add name text,
+    add color text,
+    add position int;
+
+-- built-in roles keep their privileges and become the bottom, middle and top of the hierarchy
+update roles
+    set name = case group_roles.role_type
+            when 'owner' then 'Owner'
+            when 'admin' then 'Admin'
+            else 'Member'
+        end,
+        color = case group_roles.role_type
+            when 'owner' then '#e67e22'
+            when 'admin' then '#3498db'
+            else '#99aab5'
+        end,
+        position = case group_roles.role_type
+            when 'owner' then 2
+            when 'admin' then 1
+            else 0
+        end
+    from group_roles
+    where group_roles.role_id = roles.id;
+
+delete from roles
+    where name is null
+    and not exists (select 1 from group_users where group_users.role_id = roles.id);
+
+alter table roles
+    alter column name set not null,
+    alter column color set not null,
+    alter column position set not null,
+    add check (char_length(name) between 1 and 32),
+    add check (color ~ '^#[0-9a-f]{6}$');
+
+-- custom roles rank as admins when placed above the admin role and as members otherwise
+alter table group_roles
+    add custom bool not null default false;
+
+create unique index group_roles_built_in on group_roles (group_id, role_type) where not custom;
+
+alter type audit_action add value 'role_created';
+alter type audit_action add value 'role_updated';
+alter type audit_action add value 'role_deleted';
+alter type audit_action add value 'roles_reordered';
+
+create or replace function add_group_roles(group_id uuid) returns void as $$
+    declare
+        new_role_id uuid;
+    begin
+        insert into roles (can_invite, can_send_messages, can_export, name, color, position)
+            values (true, 0, true, 'Owner', '#e67e22', 2)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'owner');
+
+        insert into roles (can_invite, can_send_messages, can_export, name, color, position)
+            values (true, 0, true, 'Admin', '#3498db', 1)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'admin');
+
+        insert into roles (can_invite, can_send_messages, can_export, name, color, position)
+            values (true, 0, false, 'Member', '#99aab5', 0)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'member');
+    end;
+$$ language plpgsql;
@@ -0,0 +1,36 @@
+-- Add down migration script here
+create or replace function add_group_roles(group_id uuid) returns void as $$
+    declare
+        new_role_id uuid;
+    begin
+        insert into roles (can_invite, can_send_messages, can_export, name, color, position)
+            values (true, 0, true, 'Owner', '#e67e22', 2)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'owner');
+
+        insert into roles (can_invite, can_send_messages, can_export, name, color, position)
+            values (true, 0, true, 'Admin', '#3498db', 1)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'admin');
+
+        insert into roles (can_invite, can_send_messages, can_export, name, color, position)
+            values (true, 0, false, 'Member', '#99aab5', 0)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'member');
+    end;
+$$ language plpgsql;
+
+-- enum values can't be dropped and the audit log is append-only, audit records of revoked invitations stay
+
+alter table roles
+    drop can_kick,
+    drop can_ban,
+    drop can_mute,
+    drop can_delete_messages,
+    drop can_pin,
+    drop can_manage_invitations,
+    drop can_edit_settings,
+    drop can_manage_roles;
@@ -0,0 +1,59 @@
+-- Add up migration script here
+alter table roles
+    add can_kick bool not null default false,
+    add can_ban bool not null default false,
+    add can_mute bool not null default false,
+    add can_delete_messages bool not null default false,
+    add can_pin bool not null default false,
+    add can_manage_invitations bool not null default false,
+    add can_edit_settings bool not null default false,
+    add can_manage_roles bool not null default false;
+
+-- admins keep moderating as before, group settings stay with the owner
+update roles
+    set can_kick = true,
+        can_ban = true,
+        can_mute = true,
+        can_delete_messages = true,
+        can_pin = true,
+        can_manage_invitations = true,
+        can_edit_settings = group_roles.role_type = 'owner',
+        can_manage_roles = true
+    from group_roles
+    where group_roles.role_id = roles.id
+    and group_roles.role_type in ('owner', 'admin');
+
+alter type audit_action add value 'invitation_revoked';
+
+create or replace function add_group_roles(group_id uuid) returns void as $$
+    declare
+        new_role_id uuid;
+    begin
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, name, color, position
+        )
+            values (true, 0, true, true, true, true, true, true, true, true, true, 'Owner', '#e67e22', 2)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'owner');
+
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, name, color, position
+        )
+            values (true, 0, true, true, true, true, true, true, true, false, true, 'Admin', '#3498db', 1)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'admin');
+
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, name, color, position
+        )
+            values (true, 0, false, false, false, false, false, false, false, false, false, 'Member', '#99aab5', 0)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'member');
+    end;
+$$ language plpgsql;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+drop trigger group_users_pass_ownership on group_users;
+drop function pass_group_ownership;
+
+-- enum values can't be dropped and the audit log is append-only, audit records of ownership transfers stay
+
+drop table group_ownership_transfers;
+alter table group_users drop joined_at;
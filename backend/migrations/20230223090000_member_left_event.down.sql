@@ -0,0 +1,2 @@
+-- Add down migration script here
+-- enum values can't be dropped, webhooks subscribed to departures keep the subscription
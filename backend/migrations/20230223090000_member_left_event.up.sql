@@ -0,0 +1,2 @@
+-- Add up migration script here
+alter type group_event add value 'member_left';
@@ -0,0 +1,36 @@
+-- Add down migration script here
+create or replace function add_group_roles(group_id uuid) returns void as $$
+    declare
+        new_role_id uuid;
+    begin
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, name, color, position
+        )
+            values (true, 0, true, true, true, true, true, true, true, true, true, 'Owner', '#e67e22', 2)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'owner');
+
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, name, color, position
+        )
+            values (true, 0, true, true, true, true, true, true, true, false, true, 'Admin', '#3498db', 1)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'admin');
+
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, name, color, position
+        )
+            values (true, 0, false, false, false, false, false, false, false, false, false, 'Member', '#99aab5', 0)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'member');
+    end;
+$$ language plpgsql;
+
+alter table roles
+    drop can_manage_nicknames;
@@ -0,0 +1,43 @@
+-- Add up migration script here
+alter table roles
+    add can_manage_nicknames bool not null default false;
+
+-- nicknames are reset by the roles that reset them before, custom roles ranked as admins included
+update roles
+    set can_manage_nicknames = true
+    from group_roles
+    where group_roles.role_id = roles.id
+    and group_roles.role_type in ('owner', 'admin');
+
+create or replace function add_group_roles(group_id uuid) returns void as $$
+    declare
+        new_role_id uuid;
+    begin
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, can_manage_nicknames, name, color, position
+        )
+            values (true, 0, true, true, true, true, true, true, true, true, true, true, 'Owner', '#e67e22', 2)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'owner');
+
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, can_manage_nicknames, name, color, position
+        )
+            values (true, 0, true, true, true, true, true, true, true, false, true, true, 'Admin', '#3498db', 1)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'admin');
+
+        insert into roles (
+            can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+            can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, can_manage_nicknames, name, color, position
+        )
+            values (true, 0, false, false, false, false, false, false, false, false, false, false, 'Member', '#99aab5', 0)
+            returning id into new_role_id;
+        insert into group_roles (group_id, role_id, role_type)
+            values (group_id, new_role_id, 'member');
+    end;
+$$ language plpgsql;
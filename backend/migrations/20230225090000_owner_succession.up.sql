@@ -0,0 +1,33 @@
+-- Add up migration script here
+-- members from before the ownership migration share the time it ran, the earliest in the table,
+-- their first message in the group is the closest record of when they joined
+update group_users
+    set joined_at = first_message.sent_at
+    from (
+        select user_id, group_id, min(sent_at) as sent_at from messages
+            where user_id is not null
+            group by user_id, group_id
+    ) first_message
+    where group_users.user_id = first_message.user_id
+    and group_users.group_id = first_message.group_id
+    and group_users.joined_at = (select min(joined_at) from group_users)
+    and first_message.sent_at < group_users.joined_at;
+-- the ones who never wrote keep the shared time, the successor among them is the lowest user id
+
+-- the successor is picked by the application, which records the change, the database only drops stale offers
+drop trigger group_users_pass_ownership on group_users;
+drop function pass_group_ownership;
+
+create function cancel_ownership_transfers() returns trigger as $$
+    begin
+        delete from group_ownership_transfers
+            where group_id = old.group_id
+            and old.user_id in (from_user_id, to_user_id);
+
+        return null;
+    end;
+$$ language plpgsql;
+
+create trigger group_users_cancel_ownership_transfers
+    after delete on group_users
+    for each row execute function cancel_ownership_transfers();
@@ -0,0 +1,17 @@
+-- Add down migration script here
+drop trigger group_users_pass_ownership on group_users;
+drop function pass_group_ownership;
+
+create function cancel_ownership_transfers() returns trigger as $$
+    begin
+        delete from group_ownership_transfers
+            where group_id = old.group_id
+            and old.user_id in (from_user_id, to_user_id);
+
+        return null;
+    end;
+$$ language plpgsql;
+
+create trigger group_users_cancel_ownership_transfers
+    after delete on group_users
+    for each row execute function cancel_ownership_transfers();
@@ -0,0 +1,46 @@
+-- Add up migration script here
+-- the database picks the successor again, so groups keep an owner when memberships are deleted outside the application,
+-- the application only records the hand-over it finds in the same transaction
+drop trigger group_users_cancel_ownership_transfers on group_users;
+drop function cancel_ownership_transfers;
+
+create function pass_group_ownership() returns trigger as $$
+    declare
+        successor uuid;
+    begin
+        delete from group_ownership_transfers
+            where group_id = old.group_id
+            and old.user_id in (from_user_id, to_user_id);
+
+        if not exists (
+            select 1 from group_roles
+                where role_id = old.role_id
+                and role_type = 'owner'
+                and not custom
+        ) then
+            return null;
+        end if;
+
+        -- members from before join times were recorded share one, the user id breaks the tie
+        select group_users.user_id into successor
+            from group_users
+            join group_roles on group_users.role_id = group_roles.role_id
+            where group_users.group_id = old.group_id
+            order by group_roles.role_type = 'admin' desc, group_users.joined_at, group_users.user_id
+            limit 1
+            for update of group_users;
+
+        if successor is not null then
+            update group_users
+                set role_id = old.role_id
+                where group_id = old.group_id
+                and user_id = successor;
+        end if;
+
+        return null;
+    end;
+$$ language plpgsql;
+
+create trigger group_users_pass_ownership
+    after delete on group_users
+    for each row execute function pass_group_ownership();
@@ -0,0 +1,2 @@
+-- Add down migration script here
+-- revoked webhooks stay revoked, their tokens are gone
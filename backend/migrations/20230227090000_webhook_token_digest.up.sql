@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- webhook tokens are stored as SHA-256 digests now, argon2 hashes of the old tokens can't be converted,
+-- so their webhooks are revoked and have to be created again
+update group_webhooks set revoked_at = now()
+    where revoked_at is null
+    and token_hash like '$argon2%';
@@ -2,7 +2,8 @@ use crate::utils::friends::errors::FriendError;
 use crate::{
     utils::{
         auth::errors::AuthError, chat::errors::ChatError, groups::errors::GroupError,
-        invitations::errors::InvitationError, roles::errors::RoleError
+        invitations::errors::InvitationError, roles::errors::RoleError,
+        webhooks::errors::WebhookError,
     },
 };
 use axum::response::IntoResponse;
@@ -18,6 +19,8 @@ pub enum AppError {
     ChatError(#[from] ChatError),
     #[error(transparent)]
     FriendError(#[from] FriendError),
+    #[error(transparent)]
+    WebhookError(#[from] WebhookError),
 }
 
 // TODO: server error backtrace
@@ -28,6 +31,7 @@ impl IntoResponse for AppError {
             AppError::GroupError(e) => return e.into_response(),
             AppError::ChatError(e) => return e.into_response(),
             AppError::FriendError(e) => return e.into_response(),
+            AppError::WebhookError(e) => return e.into_response(),
         };
     }
 }
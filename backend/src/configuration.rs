@@ -2,15 +2,24 @@ use config::{Config, ConfigError};
 use lettre::{transport::smtp::authentication::Credentials, Address};
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use tracing::info;
 
+use crate::utils::roles::models::Gates;
+
 #[derive(Deserialize, Clone)]
 pub struct Settings {
     pub app: ApplicationSettings,
     pub postgres: PostgresSettings,
     pub redis: RedisSettings,
     pub smtp: SmtpSettings,
+    #[serde(default)]
+    pub chat: ChatSettings,
+    #[serde(default)]
+    pub gates: GatesSettings,
+    #[serde(default)]
+    pub webhooks: WebhookSettings,
 }
 
 #[derive(Deserialize, Clone)]
@@ -41,6 +50,124 @@ impl SmtpSettings {
     }
 }
 
+/// Limits of the actions a websocket client can send
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ChatSettings {
+    /// Actions a single connection can send in a burst
+    pub connection_burst: u32,
+    /// Actions per second a single connection regains
+    pub connection_rate: f64,
+    /// Actions all connections of a user can send in a burst
+    pub user_burst: u32,
+    /// Actions per second all connections of a user regain
+    pub user_rate: f64,
+    /// Identical messages a user can send in a row
+    pub duplicate_limit: u32,
+    /// Seconds after which a repeated message no longer counts as a duplicate
+    pub duplicate_window: u64,
+    /// Refused actions in a row after which the connection is closed
+    pub max_violations: u32,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            connection_burst: 20,
+            connection_rate: 5.0,
+            user_burst: 40,
+            user_rate: 8.0,
+            duplicate_limit: 3,
+            duplicate_window: 30,
+            max_violations: 20,
+        }
+    }
+}
+
+impl ChatSettings {
+    fn from_env() -> Self {
+        let config = Config::builder()
+            .add_source(config::Environment::with_prefix("CHAT").prefix_separator("_"))
+            .build()
+            .unwrap();
+        config.try_deserialize().unwrap()
+    }
+}
+
+/// Targets the event webhooks are allowed to post to
+#[derive(Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct WebhookSettings {
+    /// Lets event webhooks post to loopback, link-local and private addresses, meant for local development
+    pub allow_private_targets: bool,
+}
+
+impl WebhookSettings {
+    fn from_env() -> Self {
+        let config = Config::builder()
+            .add_source(config::Environment::with_prefix("WEBHOOKS").prefix_separator("_"))
+            .build()
+            .unwrap();
+        config.try_deserialize().unwrap()
+    }
+}
+
+/// Role checks of the actions members take against each other, one gate per action
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct GatesSettings {
+    pub kick: GateSettings,
+    pub ban: GateSettings,
+    pub mute: GateSettings,
+    pub manage_roles: GateSettings,
+    pub nicknames: GateSettings,
+}
+
+impl Default for GatesSettings {
+    fn default() -> Self {
+        Self {
+            // no one outranks the owner, members leave the group instead of kicking themselves
+            kick: GateSettings {
+                requirements: HashMap::from([("owner".into(), 1)]),
+                allow_self: false,
+            },
+            ban: GateSettings::default(),
+            mute: GateSettings::default(),
+            // strictly above the role, the owner role itself is never reachable
+            manage_roles: GateSettings::default(),
+            // members with the privilege can reset their own nickname as well
+            nicknames: GateSettings {
+                requirements: HashMap::new(),
+                allow_self: true,
+            },
+        }
+    }
+}
+
+impl GatesSettings {
+    fn from_env() -> Self {
+        let config = Config::builder()
+            .add_source(
+                config::Environment::with_prefix("GATES")
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
+            .build()
+            .unwrap();
+        config.try_deserialize().unwrap()
+    }
+}
+
+/// Gate of a single action, a member passes it when their role is above the role they act on
+#[derive(Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct GateSettings {
+    /// Positions added to built-in roles by name, making them harder to act against
+    pub requirements: HashMap<String, i32>,
+    /// Lets members act on themselves when they are at the same position as the role
+    pub allow_self: bool,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct ApplicationSettings {
     pub host: String,
@@ -215,28 +342,32 @@ pub fn get_config() -> Result<Settings, ConfigError> {
             env.try_into().expect("Failed to parse APP_ENVIRONMENT.")
         });
 
-    match environment {
-        Environment::Local => {
-            let settings = Config::builder()
-                .add_source(config::File::from(config_dir.join("settings.toml")))
-                .add_source(
-                    config::Environment::with_prefix("APP")
-                        .prefix_separator("_")
-                        .separator("__"),
-                );
-            return settings.build()?.try_deserialize();
-        }
+    let settings: Settings = match environment {
+        Environment::Local => Config::builder()
+            .add_source(config::File::from(config_dir.join("settings.toml")))
+            .add_source(
+                config::Environment::with_prefix("APP")
+                    .prefix_separator("_")
+                    .separator("__"),
+            )
+            .build()?
+            .try_deserialize()?,
 
-        Environment::Production => {
-            let settings = Settings {
-                app: ApplicationSettings::from_env(),
-                postgres: PostgresSettings::from_env(),
-                redis: RedisSettings::from_env(),
-                smtp: SmtpSettings::from_env(),
-            };
-            return Ok(settings);
-        }
-    }
+        Environment::Production => Settings {
+            app: ApplicationSettings::from_env(),
+            postgres: PostgresSettings::from_env(),
+            redis: RedisSettings::from_env(),
+            smtp: SmtpSettings::from_env(),
+            chat: ChatSettings::from_env(),
+            gates: GatesSettings::from_env(),
+            webhooks: WebhookSettings::from_env(),
+        },
+    };
+
+    // moderation rules are checked before anything starts, not when the first member is kicked
+    Gates::from_settings(&settings.gates)?;
+
+    Ok(settings)
 }
 
 fn try_get_env(name: &str) -> Option<String> {
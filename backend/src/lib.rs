@@ -23,16 +23,31 @@ use modules::{
 };
 use modules::{external_api::HttpClient, extractors::geolocation::NetworkData};
 use serde_json::json;
-use utils::roles::models::{Role, is_id_the_same, Gate};
+use utils::chat::{polls::run_poll_closer, scheduled::run_message_scheduler, socket::ChatState};
+use utils::groups::mutes::run_mute_expiry_worker;
+use utils::groups::retention::run_retention_worker;
+use utils::roles::models::Gates;
+use utils::webhooks::events::run_event_delivery_worker;
 use std::io;
 use tower_http::cors::CorsLayer;
 use tracing::{debug, error};
 
 pub async fn app(config: Settings, test_pool: Option<PgPool>) -> Router {
-    let pgpool = test_pool.unwrap_or(get_postgres_pool(config.postgres).await);
+    let pgpool = match test_pool {
+        Some(pool) => pool,
+        None => get_postgres_pool(config.postgres).await,
+    };
     let rdpool = get_redis_pool(config.redis).await;
 
+    tokio::spawn(run_retention_worker(pgpool.clone()));
+
     let http_client = HttpClient::new();
+    tokio::spawn(run_event_delivery_worker(pgpool.clone(), http_client.clone()));
+
+    let chat_state = ChatState::with_settings(config.chat.clone());
+    tokio::spawn(run_message_scheduler(pgpool.clone(), chat_state.clone()));
+    tokio::spawn(run_poll_closer(pgpool.clone(), chat_state.clone()));
+    tokio::spawn(run_mute_expiry_worker(pgpool.clone(), chat_state.clone()));
 
     let origin = config
         .app
@@ -45,6 +60,7 @@ pub async fn app(config: Settings, test_pool: Option<PgPool>) -> Router {
         .allow_credentials(true);
 
     let mailer = Mailer::new(config.smtp, config.app.origin);
+    let gates = Gates::from_settings(&config.gates).expect("Invalid gate settings");
 
     let groups = Router::new().nest(
         "/groups",
@@ -59,27 +75,20 @@ pub async fn app(config: Settings, test_pool: Option<PgPool>) -> Router {
         .route("/geo", get(geolocation_info))
         .route("/ua", get(user_agent_info));
 
-    let kick_gate = Gate::build()
-        .role(Role::Owner, 3)
-        .role(Role::Admin, 1)
-        .role(Role::Member, 0)
-        .req(Role::Owner, 4)
-        .req(Role::Admin, 1)
-        .req(Role::Member, 0)
-        .condition(is_id_the_same)
-        .finish();
-
     let api = Router::new()
         .nest("/auth", routes::auth::router())
         .nest("/chat", routes::chat::router())
+        .nest("/hooks", routes::webhooks::router())
         .route("/health", get(health_check))
         .nest("/test", test)
         .merge(groups)
         .layer(Extension(pgpool))
+        .layer(Extension(chat_state))
         .layer(Extension(rdpool))
         .layer(Extension(http_client))
         .layer(Extension(mailer))
-        .layer(Extension(kick_gate))
+        .layer(Extension(gates))
+        .layer(Extension(config.webhooks))
         .layer(Extension(TokenExtractors {
             access: JwtAccessSecret(config.app.access_jwt_secret),
             refresh: JwtRefreshSecret(config.app.refresh_jwt_secret),
@@ -40,6 +40,28 @@ impl HttpClient {
         Ok(user_agent)
     }
 
+    /// Posts a JSON body with extra headers and returns the response status code
+    pub async fn post_json_with_headers(
+        &self,
+        url: &str,
+        headers: &[(&str, String)],
+        body: String,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<u16> {
+        let mut req = self
+            .0
+            .post(url)
+            .header("content-type", "application/json")
+            .timeout(timeout)
+            .body(body);
+        for (name, value) in headers {
+            req = req.header(*name, value);
+        }
+
+        let res = req.send().await?;
+        Ok(res.status().as_u16())
+    }
+
     pub async fn fetch_geolocation(&self, ip: IpAddr) -> anyhow::Result<GeolocationData> {
         let fields = 18596857; // https://ip-api.com/docs/api:json#test
         let url = format!("http://ip-api.com/json/{ip}?fields={fields}");
@@ -1,17 +1,33 @@
 ﻿use crate::utils::auth::models::Claims;
+use crate::utils::chat::commands::{available_commands, parse_command, Command};
+use crate::utils::chat::errors::ChatError;
+use crate::utils::chat::flood::FloodCheck;
 use crate::utils::chat::messages::fetch_last_messages_in_range;
+use crate::utils::chat::nicknames::{find_group_member_by_nickname, set_group_nickname};
 use crate::utils::chat::models::*;
+use crate::utils::chat::polls::{close_poll_as, create_poll, fetch_group_poll, vote_in_poll};
+use crate::utils::chat::scheduled::{cancel_scheduled_message, fetch_scheduled_messages, schedule_message};
 use crate::utils::chat::socket::{
-    ChatState, ClientAction, ServerAction, UserController,
+    ChatState, ClientAction, GroupConnection, ServerAction, UserController,
 };
 use crate::utils::chat::*;
 use crate::utils::groups::*;
-use crate::utils::roles::models::{SocketGroupRolePrivileges, Gate, Role};
-use crate::utils::roles::privileges::{Privilege, CanInvite, CanSendMessages};
-use crate::utils::roles::{get_group_role_privileges, get_user_role, single_set_group_role_privileges, single_set_group_user_role};
+use crate::utils::groups::filters::{load_group_filters, FilterVerdict};
+use crate::utils::groups::kicks::kick_user_from_group;
+use crate::utils::groups::mutes::{get_active_mute, mute_user, unmute_user};
+use crate::utils::invitations::{try_create_group_invitation_with_code, GroupInvitationCreate};
+use crate::utils::roles::models::{SocketGroupRolePrivileges, Gate, Gates};
+use crate::utils::roles::privileges::{Privilege, CanInvite, CanKick, CanSendMessages};
+use crate::utils::roles::{
+    get_group_roles, get_member_role, get_user_privileges, set_group_role_privileges, set_group_user_roles, single_set_group_role_privileges,
+    single_set_group_user_role,
+};
+use crate::utils::webhooks::events::enqueue_group_event;
+use crate::utils::webhooks::models::GroupEvent;
+use anyhow::Context;
 use axum::http::HeaderMap;
 use axum::{
-    extract::ws::{WebSocket, WebSocketUpgrade},
+    extract::ws::{close_code, WebSocket, WebSocketUpgrade},
     response::Response,
     routing::get,
     Extension, Router,
@@ -22,12 +38,12 @@ use std::sync::Arc;
 use tracing::{debug, error, info};
 use uuid::Uuid;
 
-const MAX_MESSAGE_LENGTH: usize = 2000;
+/// Expiration of invitations created with `/invite` (1 day)
+const COMMAND_INVITATION_EXPIRATION_INDEX: i32 = 4;
 
 pub fn router() -> Router {
     Router::new()
         .route("/websocket", get(chat_handler))
-        .layer(Extension(Arc::new(ChatState::new())))
 }
 
 async fn chat_handler(
@@ -37,10 +53,10 @@ async fn chat_handler(
     claims: Claims,
     Extension(state): Extension<Arc<ChatState>>,
     Extension(pool): Extension<PgPool>,
-    Extension(gate): Extension<Gate<Role, (Uuid, Uuid)>>,
+    Extension(gates): Extension<Gates>,
 ) -> Response {
     let connection_id = get_connection_id(headers);
-    ws.on_upgrade(|socket| chat_socket(socket, state, claims, pool, connection_id, gate))
+    ws.on_upgrade(|socket| chat_socket(socket, state, claims, pool, connection_id, gates))
 }
 
 fn get_connection_id(headers: HeaderMap) -> String {
@@ -59,13 +75,25 @@ pub async fn chat_socket(
     claims: Claims,
     pool: PgPool,
     connection_id: String,
-    gate: Gate<Role, (Uuid, Uuid)>
+    gates: Gates
 ) {
     let mut controller = UserController::new(stream, claims.user_id, connection_id);
+    let mut limiter = state.flood.connection_limiter();
 
     loop {
         // Wait for next client action
         let action = controller.user_channel.receiver.next_action().await;
+
+        if !matches!(action, ClientAction::Close) {
+            let check = state.flood.check_action(&mut limiter, claims.user_id);
+            if check != FloodCheck::Allowed {
+                if refuse_flood(&controller, check).await {
+                    continue;
+                }
+                break;
+            }
+        }
+
         match action {
             ClientAction::ChangeGroup { group_id } => {
                 // Security checks
@@ -74,19 +102,23 @@ pub async fn chat_socket(
                 }
 
                 // Fetch role and privileges in order to connect to group
-                let Ok(privileges) = get_group_role_privileges(&pool, group_id).await else {
-                    error!("Cannot fetch group role privileges");
+                let Ok(roles) = get_group_roles(&pool, &group_id).await else {
+                    error!("Cannot fetch group roles");
                     continue
                 };
-                let group_controller = state.groups.get(&group_id, SocketGroupRolePrivileges::from(privileges));
 
-                let Ok(role) = get_user_role(&pool, &claims.user_id, &group_id).await else {
+                let Ok(member) = get_member_role(&pool, &claims.user_id, &group_id).await else {
                     error!("Cannot fetch group user role data");
                     continue
                 };
+                let Some(role) = roles.get(&member.role).cloned() else {
+                    error!("Cannot find the role of the group user");
+                    continue
+                };
+                let group_controller = state.groups.get(&group_id, SocketGroupRolePrivileges::from(roles));
 
                 // Connect user controller to group
-                controller.connect(group_id, group_controller, role).await;
+                controller.connect(group_id, group_controller, &role).await;
 
                 // Load last group messages
                 let Ok(messages) = fetch_last_messages_in_range(&pool,&group_id,10,0).await else {
@@ -100,6 +132,8 @@ pub async fn chat_socket(
                     error!("ws closed: Failed to load fetched messages");
                     continue;
                 }
+
+                send_available_commands(&controller, claims.user_id).await;
             }
             ClientAction::SendMessage { content } => {
                 let Some(conn) = controller.get_group_conn().await else {
@@ -120,19 +154,57 @@ pub async fn chat_socket(
                     continue;
                 }
 
-                // todo: make transaction
+                // Slash commands are run instead of being sent to the group
+                if let Some(command) = parse_command(&content) {
+                    let res = match command {
+                        Ok(command) => run_command(command, &controller, conn, &state, &claims, &pool, &gates).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = res {
+                        debug!("Command of user {} ({}) failed: {e}", &claims.user_id, &claims.login);
+                        send_error(&controller, &e).await;
+                    }
+                    continue;
+                }
+
+                if let Err(check) = state.flood.check_duplicate(&mut limiter, claims.user_id, conn.group_id, &content) {
+                    if refuse_flood(&controller, check).await {
+                        continue;
+                    }
+                    break;
+                }
+
+                if let Err(e) = ensure_not_muted(&pool, &claims.user_id, &conn.group_id).await {
+                    send_error(&controller, &e).await;
+                    continue;
+                }
+
+                let (content, flagged_by) = match filter_content(conn, &pool, &content).await {
+                    Ok(filtered) => filtered,
+                    Err(e) => {
+                        debug!("Message of user {} ({}) was refused: {e}", &claims.user_id, &claims.login);
+                        send_error(&controller, &e).await;
+                        continue;
+                    }
+                };
+
                 // Save message in database
-                let nickname = get_group_nickname(&pool, &claims.user_id, &conn.group_id)
-                    .await
-                    .unwrap_or("unknown_user".into());
+                let nickname = match get_group_nickname(&pool, &claims.user_id, &conn.group_id).await {
+                    Ok(nickname) => nickname,
+                    Err(e) => {
+                        error!("Cannot fetch nickname of user {} ({}): {e:?}", &claims.user_id, &claims.login);
+                        send_error(&controller, &e).await;
+                        continue;
+                    }
+                };
 
-                let Ok(_) = create_message(&pool, &claims.user_id, &conn.group_id, &content).await else {
+                let Ok(message_id) = save_filtered_message(&pool, &claims.user_id, &conn.group_id, &nickname, &content, MessageKind::Text, &flagged_by).await else {
                             error!("Failed to save the message from the user {} ({}) in the database", &claims.user_id, &claims.login);
                             continue;
                         };
 
                 // Send message to the connected group members
-                let action = ServerAction::Message(GroupUserMessage::new(nickname, content));
+                let action = ServerAction::Message(GroupUserMessage::new(nickname, content).with_id(message_id));
                 debug!("Sent: {action:#?}");
                 conn.controller.channel.sender.send(action);
             }
@@ -159,11 +231,81 @@ pub async fn chat_socket(
                     continue;
                 }
             }
-            // todo: send group invites in chat
-            ClientAction::GroupInvite { group_id } => {
-                match controller.verify_with_privilege(claims.user_id, Privilege::CanInvite(CanInvite::Yes)).await {
+            ClientAction::ScheduleMessage { content, send_at } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("Cannot schedule message - group not selected");
+                    continue;
+                };
+
+                if content.len() > MAX_MESSAGE_LENGTH {
+                    debug!(
+                        "Scheduled message too long: the message length is {}, which is greater than {}",
+                        content.len(),
+                        MAX_MESSAGE_LENGTH
+                    );
+                    continue;
+                }
+
+                if let Err(e) = ensure_not_muted(&pool, &claims.user_id, &conn.group_id).await {
+                    send_error(&controller, &e).await;
+                    continue;
+                }
+
+                // the content is filtered again at delivery, refuse what would be blocked right away
+                if let Err(e) = filter_content(conn, &pool, &content).await {
+                    send_error(&controller, &e).await;
+                    continue;
+                }
+
+                let scheduled = match schedule_message(&pool, &claims.user_id, &conn.group_id, &content, send_at).await {
+                    Ok(scheduled) => scheduled,
+                    Err(e) => {
+                        debug!("Failed to schedule message from user {} ({}): {e}", &claims.user_id, &claims.login);
+                        continue;
+                    }
+                };
+
+                let payload = ServerAction::MessageScheduled(scheduled);
+                if controller.user_channel.sender.send(&payload).await.is_err() {
+                    error!("Failed to confirm scheduled message for user {} ({})", &claims.user_id, &claims.login);
+                }
+            }
+            ClientAction::RequestScheduledMessages => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("Cannot fetch scheduled messages - group not selected");
+                    continue;
+                };
+
+                let Ok(scheduled) = fetch_scheduled_messages(&pool, &claims.user_id, &conn.group_id).await else {
+                    error!("Cannot fetch scheduled messages for user {} ({})", &claims.user_id, &claims.login);
+                    continue;
+                };
+
+                let payload = ServerAction::ScheduledMessages(scheduled);
+                if controller.user_channel.sender.send(&payload).await.is_err() {
+                    error!("Failed to load scheduled messages for user {} ({})", &claims.user_id, &claims.login);
+                }
+            }
+            ClientAction::CancelScheduledMessage { id } => {
+                if let Err(e) = cancel_scheduled_message(&pool, &claims.user_id, id).await {
+                    debug!("Failed to cancel scheduled message {id}: {e}");
+                    continue;
+                };
+
+                let payload = ServerAction::ScheduledMessageCancelled { id };
+                if controller.user_channel.sender.send(&payload).await.is_err() {
+                    error!("Failed to confirm scheduled message cancellation for user {} ({})", &claims.user_id, &claims.login);
+                }
+            }
+            ClientAction::CreatePoll { poll } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("Cannot create poll - group not selected");
+                    continue;
+                };
+
+                match controller.verify_with_privilege(claims.user_id, Privilege::CanSendMessages(CanSendMessages::Yes(usize::MAX))).await {
                     Ok(false) => {
-                        info!("User does not have privileges to invite other users");
+                        info!("User does not have privileges to create polls");
                         continue;
                     },
                     Err(e) => {
@@ -173,83 +315,242 @@ pub async fn chat_socket(
                     _ => (),
                 }
 
-                let Ok(_is_member) = check_if_group_member(&pool, &claims.user_id, &group_id).await else {
-                    error!("Failed to check whether a user {} ({}) is a group {} member (during sending a group invite)", &claims.user_id, &claims.login, &group_id);
+                if let Err(e) = ensure_not_muted(&pool, &claims.user_id, &conn.group_id).await {
+                    send_error(&controller, &e).await;
+                    continue;
+                }
+
+                let Ok(nickname) = get_group_nickname(&pool, &claims.user_id, &conn.group_id).await else {
+                    error!("Cannot fetch nickname of user {} ({})", &claims.user_id, &claims.login);
                     continue;
                 };
-            }
-            ClientAction::RemoveUser { user_id, group_id } => {
-                match check_if_group_member(&pool, &user_id, &group_id).await {
-                    Ok(false) => {
-                        debug!(
-                            "Cannot remove user {} from group {} - user is not a group member",
-                            &user_id, &group_id
-                        );
+
+                let (poll, flagged_by) = match filter_poll(conn, &pool, poll).await {
+                    Ok(filtered) => filtered,
+                    Err(e) => {
+                        debug!("Poll of user {} ({}) was refused: {e}", &claims.user_id, &claims.login);
+                        send_error(&controller, &e).await;
                         continue;
                     }
-                    Err(_) => {
-                        error!("ws closed: Failed to check whether a user {} is a group {} member (during user removal)", &user_id, &group_id);
+                };
+
+                let poll = match create_poll(&pool, &claims.user_id, &conn.group_id, poll, &flagged_by).await {
+                    Ok(poll) => poll,
+                    Err(e) => {
+                        debug!("Failed to create poll by user {} ({}): {e}", &claims.user_id, &claims.login);
                         continue;
                     }
-                    _ => (),
                 };
 
-                let Some(user_role) = controller.get_role(claims.user_id).await else {
-                    error!("Failed to get the controller's role");
+                let message = GroupUserMessage::poll(nickname, poll.question.clone(), MessageKind::Poll, poll.id);
+                conn.controller.channel.sender.send(ServerAction::Message(message));
+                conn.controller.channel.sender.send(ServerAction::PollUpdate(poll));
+            }
+            ClientAction::VotePoll { poll_id, options } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("Cannot vote in poll - group not selected");
+                    continue;
+                };
+
+                // Live tally for everyone connected to the group
+                match vote_in_poll(&pool, &claims.user_id, &conn.group_id, poll_id, options).await {
+                    Ok(poll) => conn.controller.channel.sender.send(ServerAction::PollUpdate(poll)),
+                    Err(e) => debug!("Failed to vote in poll {poll_id}: {e}"),
+                }
+            }
+            ClientAction::ClosePoll { poll_id } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("Cannot close poll - group not selected");
                     continue;
                 };
 
-                let Some(target_user_role) = controller.get_role(user_id).await else {
-                    error!("Failed to get the target user's role");
+                let Some(privileges) = controller.get_user_privileges(claims.user_id).await else {
+                    error!("Failed to get the controller's privileges");
                     continue;
                 };
 
-                if !gate.verify(user_role, target_user_role, (claims.user_id, user_id)) {
-                    info!("User does not have privileges to kick another user");
+                match close_poll_as(&pool, &claims.user_id, &privileges, &conn.group_id, poll_id).await {
+                    Ok((poll, message)) => {
+                        conn.controller.channel.sender.send(ServerAction::PollUpdate(poll));
+                        conn.controller.channel.sender.send(ServerAction::Message(message));
+                    }
+                    Err(e) => debug!("Failed to close poll {poll_id}: {e}"),
+                }
+            }
+            ClientAction::RequestCommands => {
+                if controller.get_group_conn().await.is_none() {
+                    debug!("Cannot fetch commands - group not selected");
                     continue;
                 }
 
-                // Remove user from group
-                let Ok(_) = try_remove_user_from_group(&pool, user_id, group_id).await else {
-                    error!("Failed to remove user {} from a group {}", &user_id, &group_id);
+                send_available_commands(&controller, claims.user_id).await;
+            }
+            ClientAction::RequestPoll { poll_id } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("Cannot fetch poll - group not selected");
                     continue;
                 };
 
-                // Stop listening for new group messages on all kicked user connections
-                controller.kick(user_id).await;
+                let Ok(poll) = fetch_group_poll(&pool, &conn.group_id, poll_id).await else {
+                    debug!("Cannot fetch poll {poll_id} for user {} ({})", &claims.user_id, &claims.login);
+                    continue;
+                };
+
+                let payload = ServerAction::PollUpdate(poll);
+                if controller.user_channel.sender.send(&payload).await.is_err() {
+                    error!("Failed to load poll for user {} ({})", &claims.user_id, &claims.login);
+                }
+            }
+            // todo: send group invites in chat
+            ClientAction::GroupInvite { group_id } => {
+                match controller.verify_with_privilege(claims.user_id, Privilege::CanInvite(CanInvite::Yes)).await {
+                    Ok(false) => {
+                        info!("User does not have privileges to invite other users");
+                        continue;
+                    },
+                    Err(e) => {
+                        error!("Failed to verify with privilege: {:?}", e);
+                        continue;
+                    },
+                    _ => (),
+                }
 
-                // todo: disconnect group controllers
+                let Ok(_is_member) = check_if_group_member(&pool, &claims.user_id, &group_id).await else {
+                    error!("Failed to check whether a user {} ({}) is a group {} member (during sending a group invite)", &claims.user_id, &claims.login, &group_id);
+                    continue;
+                };
+            }
+            ClientAction::RemoveUser { user_id, group_id, reason } => {
+                if let Err(e) = kick_member(&state, &claims, &pool, &gates.kick, group_id, user_id, reason.as_deref()).await {
+                    debug!("Cannot remove user {} from group {}: {e}", &user_id, &group_id);
+                    send_error(&controller, &e).await;
+                }
             }
             ClientAction::SingleChangePrivileges { mut data } => {
-                let Some(socket_privileges) = controller.get_group_privileges() else {
+                let Some(conn) = controller.get_group_conn().await else {
                     debug!("User trying to change privileges not in group");
                     continue
                 };
 
-                // there is a concurrency-related edge case which bypasses corrections
-                if data.maintain_hierarchy(socket_privileges).await.is_err() {
-                    error!("Error when maintaining role hierarchy");
+                // privileges of other groups are not held by this connection
+                if conn.group_id != data.group_id {
+                    send_error(&controller, &ChatError::MemberNotFound).await;
                     continue
+                }
+
+                // the hierarchy is corrected in the transaction, memory follows only the committed value
+                let role_changes = conn.controller.lock_role_changes().await;
+                let message = match single_set_group_role_privileges(&pool, &gates.manage_roles, &claims.user_id, &mut data).await {
+                    Ok(message) => message,
+                    Err(e) => {
+                        debug!("Failed to change role privileges: {e:?}");
+                        send_error(&controller, &ChatError::from(e)).await;
+                        continue
+                    }
                 };
 
                 if controller.set_privilege(&data).await.is_err() {
                     error!("Error when changing privilege");
+                };
+                drop(role_changes);
+                broadcast(&state, &data.group_id, ServerAction::Message(message));
+            },
+            ClientAction::SingleChangeUserRole { data } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("User trying to change roles not in group");
                     continue
                 };
 
-                if single_set_group_role_privileges(&pool, &data).await.is_err() {
-                    error!("Error when setting group role privileges");
+                if conn.group_id != data.group_id {
+                    send_error(&controller, &ChatError::MemberNotFound).await;
+                    continue
+                }
+
+                let role_changes = conn.controller.lock_role_changes().await;
+                let message = match single_set_group_user_role(&pool, &gates.manage_roles, &claims.user_id, &data).await {
+                    Ok(message) => message,
+                    Err(e) => {
+                        debug!("Failed to change user role: {e:?}");
+                        send_error(&controller, &ChatError::from(e)).await;
+                        continue
+                    }
                 };
-            },
-            ClientAction::SingleChangeUserRole { data } => {
-                if controller.single_set_role(&data).await.is_err() {
+
+                // the member may not be connected right now
+                let _ = controller.single_set_role(&data).await;
+                drop(role_changes);
+                broadcast(&state, &data.group_id, ServerAction::Message(message));
+
+                let event = GroupEvent::RoleChanged { user_id: data.user_id, role: data.value };
+                if let Err(e) = enqueue_group_event(&pool, &data.group_id, event).await {
+                    error!("Failed to enqueue role changed event: {e:?}");
+                }
+            }
+            ClientAction::ChangePrivileges { data } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("User trying to change privileges not in group");
                     continue
                 };
 
-                let res = single_set_group_user_role(&pool, &data).await;
-                if res.is_err() {
-                    debug!("Failed to change user role: {:#?}", res);
+                if conn.group_id != data.group_id {
+                    send_error(&controller, &ChatError::MemberNotFound).await;
+                    continue
+                }
+
+                let role_changes = conn.controller.lock_role_changes().await;
+                let (privileges, message) = match set_group_role_privileges(&pool, &gates.manage_roles, &claims.user_id, &data).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        debug!("Failed to change role privileges: {e:?}");
+                        send_error(&controller, &ChatError::from(e)).await;
+                        continue
+                    }
+                };
+
+                if controller.set_privileges(&privileges).await.is_err() {
+                    error!("Error when changing privileges");
                 };
+                drop(role_changes);
+                if let Some(message) = message {
+                    broadcast(&state, &data.group_id, ServerAction::Message(message));
+                }
+            }
+            ClientAction::ChangeUserRoles { data } => {
+                let Some(conn) = controller.get_group_conn().await else {
+                    debug!("User trying to change roles not in group");
+                    continue
+                };
+
+                if data.iter().any(|change| change.group_id != conn.group_id) {
+                    send_error(&controller, &ChatError::MemberNotFound).await;
+                    continue
+                }
+
+                let role_changes = conn.controller.lock_role_changes().await;
+                let (changes, message) = match set_group_user_roles(&pool, &gates.manage_roles, &claims.user_id, &data).await {
+                    Ok(res) => res,
+                    Err(e) => {
+                        debug!("Failed to change user roles: {e:?}");
+                        send_error(&controller, &ChatError::from(e)).await;
+                        continue
+                    }
+                };
+
+                for change in changes.iter() {
+                    // the member may not be connected right now
+                    let _ = controller.single_set_role(change).await;
+                }
+                drop(role_changes);
+                if let Some(message) = message {
+                    broadcast(&state, &conn.group_id, ServerAction::Message(message));
+                }
+
+                for change in changes {
+                    let event = GroupEvent::RoleChanged { user_id: change.user_id, role: change.value };
+                    if let Err(e) = enqueue_group_event(&pool, &change.group_id, event).await {
+                        error!("Failed to enqueue role changed event: {e:?}");
+                    }
+                }
             }
             ClientAction::Close => {
                 info!("WebSocket closed explicitly");
@@ -264,6 +565,243 @@ pub async fn chat_socket(
 
     debug!("ws closed: User left the message loop");
     controller.disconnect().await;
+    state.flood.forget_idle_users();
+}
+
+async fn run_command(
+    command: Command,
+    controller: &UserController,
+    conn: &GroupConnection,
+    state: &ChatState,
+    claims: &Claims,
+    pool: &PgPool,
+    gates: &Gates,
+) -> Result<(), ChatError> {
+    match command {
+        Command::Nick { name } => {
+            let nickname = set_group_nickname(pool, &claims.user_id, &conn.group_id, &name).await?;
+            conn.controller.channel.sender.send(ServerAction::NicknameChanged(MemberNickname { user_id: claims.user_id, nickname }));
+        }
+        Command::Me { action } => {
+            ensure_not_muted(pool, &claims.user_id, &conn.group_id).await?;
+
+            let can_send = controller
+                .verify_with_privilege(claims.user_id, Privilege::CanSendMessages(CanSendMessages::Yes(usize::MAX)))
+                .await
+                .context("Failed to verify with privilege")?;
+            if !can_send {
+                return Err(ChatError::InsufficientPrivileges);
+            }
+
+            let (action, flagged_by) = filter_content(conn, pool, &action).await?;
+
+            let nickname = get_group_nickname(pool, &claims.user_id, &conn.group_id).await?;
+            let message_id = save_filtered_message(pool, &claims.user_id, &conn.group_id, &nickname, &action, MessageKind::Action, &flagged_by).await?;
+
+            conn.controller.channel.sender.send(ServerAction::Message(GroupUserMessage::action(nickname, action).with_id(message_id)));
+        }
+        Command::Kick { target, reason } => {
+            let user_id = find_group_member_by_nickname(pool, &conn.group_id, &target).await?;
+            kick_member(state, claims, pool, &gates.kick, conn.group_id, user_id, reason.as_deref()).await?;
+        }
+        Command::Mute { target, duration } => {
+            let user_id = find_group_member_by_nickname(pool, &conn.group_id, &target).await?;
+            let muted_until = mute_user(pool, &gates.mute, &conn.group_id, &user_id, &claims.user_id, duration).await?;
+
+            conn.controller.channel.sender.send(ServerAction::UserMuted { user_id, muted_until: muted_until.unix_timestamp() });
+        }
+        Command::Unmute { target } => {
+            let user_id = find_group_member_by_nickname(pool, &conn.group_id, &target).await?;
+            unmute_user(pool, &gates.mute, &conn.group_id, &claims.user_id, &user_id).await?;
+
+            conn.controller.channel.sender.send(ServerAction::UserUnmuted { user_id });
+        }
+        Command::Invite => {
+            let can_invite = controller
+                .verify_with_privilege(claims.user_id, Privilege::CanInvite(CanInvite::Yes))
+                .await
+                .context("Failed to verify with privilege")?;
+            if !can_invite {
+                return Err(ChatError::InsufficientPrivileges);
+            }
+
+            let invitation = GroupInvitationCreate::new(conn.group_id, Some(COMMAND_INVITATION_EXPIRATION_INDEX), None);
+            let code = try_create_group_invitation_with_code(pool, &claims.user_id, invitation)
+                .await
+                .context("Failed to create group invitation")?;
+
+            let payload = ServerAction::InvitationCreated { code };
+            if controller.user_channel.sender.send(&payload).await.is_err() {
+                error!("Failed to send invitation code to user {} ({})", &claims.user_id, &claims.login);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks whether the user's role grants the privilege and the kick gate lets them act on another group member
+async fn authorize_moderation(
+    claims: &Claims,
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: Uuid,
+    user_id: Uuid,
+    privilege: Privilege,
+) -> Result<(), ChatError> {
+    let is_member = check_if_group_member(pool, &user_id, &group_id)
+        .await
+        .context("Failed to check group membership")?;
+    if !is_member {
+        return Err(ChatError::MemberNotFound);
+    }
+
+    // the moderated group does not have to be the one the socket is connected to
+    let privileges = get_user_privileges(pool, &claims.user_id, &group_id)
+        .await
+        .map_err(|_| ChatError::InsufficientPrivileges)?;
+    if !privileges.satisfies(privilege) {
+        info!("User does not have the privilege to moderate another user");
+        return Err(ChatError::InsufficientPrivileges);
+    }
+
+    let user_role = get_member_role(pool, &claims.user_id, &group_id)
+        .await
+        .map_err(|_| ChatError::InsufficientPrivileges)?;
+
+    let target_user_role = get_member_role(pool, &user_id, &group_id)
+        .await
+        .context("Failed to get the target user's role")?;
+
+    if !gate.verify(user_role, target_user_role, (claims.user_id, user_id)) {
+        info!("User does not have privileges to moderate another user");
+        return Err(ChatError::InsufficientPrivileges);
+    }
+
+    Ok(())
+}
+
+async fn kick_member(
+    state: &ChatState,
+    claims: &Claims,
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: Uuid,
+    user_id: Uuid,
+    reason: Option<&str>,
+) -> Result<(), ChatError> {
+    authorize_moderation(claims, pool, gate, group_id, user_id, Privilege::CanKick(CanKick::Yes)).await?;
+
+    let (message, kick) = kick_user_from_group(pool, &group_id, &user_id, &claims.user_id, reason).await?;
+
+    announce_kick(state, group_id, user_id, message, kick).await;
+
+    Ok(())
+}
+
+/// Detaches every connection of the kicked user from the group and tells the rest of it
+pub(crate) async fn announce_kick(
+    state: &ChatState,
+    group_id: Uuid,
+    user_id: Uuid,
+    message: GroupUserMessage,
+    kick: KickMessage,
+) {
+    if let Some(group_controller) = state.groups.get_loaded(&group_id) {
+        group_controller.kick(user_id, kick).await;
+    }
+
+    broadcast(state, &group_id, ServerAction::Message(message));
+}
+
+/// Runs the message through the group content filter, returns the text to send and the rules that flagged it
+async fn filter_content(conn: &GroupConnection, pool: &PgPool, content: &str) -> Result<(String, Vec<i32>), ChatError> {
+    let filters = conn.controller
+        .content_filters(|| load_group_filters(pool, &conn.group_id))
+        .await?;
+
+    match filters.apply(content) {
+        FilterVerdict::Blocked { filter_id } => {
+            debug!("Message blocked by content filter {filter_id}");
+            Err(ChatError::MessageBlocked)
+        }
+        FilterVerdict::Passed { content, flagged_by } => Ok((content, flagged_by)),
+    }
+}
+
+/// Runs the question and every option through the group content filter, returns the poll to create and the rules that flagged it
+async fn filter_poll(conn: &GroupConnection, pool: &PgPool, poll: NewPoll) -> Result<(NewPoll, Vec<i32>), ChatError> {
+    let (question, mut flagged_by) = filter_content(conn, pool, &poll.question).await?;
+
+    let mut options = Vec::with_capacity(poll.options.len());
+    for option in poll.options.iter() {
+        let (option, flags) = filter_content(conn, pool, option).await?;
+        flagged_by.extend(flags);
+        options.push(option);
+    }
+    flagged_by.sort_unstable();
+    flagged_by.dedup();
+
+    Ok((NewPoll { question, options, ..poll }, flagged_by))
+}
+
+/// A mute overrides the `CanSendMessages` privilege of the member's role
+async fn ensure_not_muted(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Result<(), ChatError> {
+    let mute = get_active_mute(pool, user_id, group_id)
+        .await
+        .context("Failed to check active mutes")?;
+
+    match mute {
+        Some(_) => Err(ChatError::UserMuted),
+        None => Ok(()),
+    }
+}
+
+async fn send_available_commands(controller: &UserController, user_id: Uuid) {
+    let Some(privileges) = controller.get_user_privileges(user_id).await else {
+        error!("No privileges found for user {user_id}");
+        return;
+    };
+
+    let payload = ServerAction::Commands(available_commands(&privileges));
+    if controller.user_channel.sender.send(&payload).await.is_err() {
+        error!("Failed to send available commands");
+    }
+}
+
+/// Sends the action to everyone connected to the group, if anyone is
+fn broadcast(state: &ChatState, group_id: &Uuid, action: ServerAction) {
+    if let Some(group_controller) = state.groups.get_loaded(group_id) {
+        group_controller.channel.sender.send(action);
+    }
+}
+
+/// Reports a failed action back to its sender
+async fn send_error(controller: &UserController, e: &ChatError) {
+    let payload = ServerAction::Error(ActionError::from(e));
+    if controller.user_channel.sender.send(&payload).await.is_err() {
+        error!("Failed to send action error");
+    }
+}
+
+/// Tells a flooding client to slow down, returns `false` when the connection has to be closed
+async fn refuse_flood(controller: &UserController, check: FloodCheck) -> bool {
+    if check != FloodCheck::Exceeded {
+        send_error(controller, &ChatError::RateLimited).await;
+        return true;
+    }
+
+    info!("ws closed: Client kept exceeding the rate limits");
+    if controller
+        .user_channel
+        .sender
+        .close(close_code::POLICY, "Rate limit exceeded")
+        .await
+        .is_err()
+    {
+        error!("Failed to send close frame");
+    }
+    false
 }
 
 /// Checks if group exsists and if users is a group member
@@ -1,16 +1,147 @@
 ﻿use crate::app_errors::AppError;
+use crate::configuration::WebhookSettings;
+use crate::routes::chat::announce_kick;
 use crate::utils::auth::models::Claims;
-use crate::utils::groups::models::NewGroup;
+use crate::utils::chat::export::export_messages;
+use crate::utils::chat::models::{
+    ExportFormat, KickMessage, MemberNickname, NicknameChange, NicknamePolicy, SystemEvent,
+};
+use crate::utils::chat::nicknames::{reset_group_nickname, set_group_nickname, set_unique_nicknames};
+use crate::utils::chat::socket::{ChatState, ServerAction};
+use crate::utils::groups::errors::GroupError;
+use crate::utils::groups::audit::fetch_audit_log;
+use crate::utils::groups::bans::{ban_user, fetch_group_bans, unban_user};
+use crate::utils::groups::filters::{
+    create_group_filter, delete_group_filter, fetch_group_filters, update_group_filter,
+};
+use crate::utils::groups::kicks::fetch_kick_history;
+use crate::utils::groups::mutes::{fetch_group_mutes, mute_user, unmute_user};
+use crate::utils::groups::models::{
+    AuditFilter, AuditRecord, ContentFilter, GroupBan, GroupInfo, GroupMute, KickRecord,
+    MessageReport, NewBan, NewContentFilter, NewGroup, NewMute, NewOwner, NewReport,
+    OwnershipTransfer, ReportAction, ResolutionEffect, RetentionPolicy,
+};
+use crate::utils::groups::ownership::{
+    accept_group_ownership, cancel_ownership_transfer, get_ownership_transfer, offer_group_ownership,
+};
+use crate::utils::groups::reports::{
+    can_moderate_reports, close_report, fetch_group_reports, report_message,
+};
+use crate::utils::groups::retention::set_group_retention;
 use crate::utils::groups::*;
+use crate::utils::invitations::{fetch_group_invitations, revoke_group_invitation, GroupInvitationInfo};
+use crate::utils::roles::custom::{
+    create_custom_role, delete_custom_role, reorder_roles, update_custom_role,
+};
+use crate::utils::roles::models::{
+    CustomRoleUpdate, Gates, GroupPrivilegesChangeData, GroupRolePrivileges, GroupRoles, MemberRoleInfo,
+    NewCustomRole, Role, RoleOrder, RoleUpdate, UserRoleChangeData,
+};
+use crate::utils::roles::privileges::{CanExport, Privilege, Privileges};
+use crate::utils::roles::{
+    get_group_role_privileges, get_user_privileges, set_group_role_privileges,
+    single_set_group_user_role,
+};
+use crate::utils::webhooks::events::{
+    create_event_webhook, enqueue_group_event, delete_event_webhook, fetch_event_deliveries, fetch_event_webhooks,
+};
+use crate::utils::webhooks::models::{
+    CreatedEventWebhook, CreatedWebhook, EventDelivery, EventWebhookInfo, GroupEvent,
+    NewEventWebhook, NewWebhook, WebhookInfo,
+};
+use crate::utils::webhooks::{create_webhook, fetch_group_webhooks, revoke_webhook};
+use axum::body::StreamBody;
+use axum::extract::{Path, Query};
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::response::IntoResponse;
 use axum::Router;
-use axum::{extract::Json, routing::get, Extension};
+use axum::{extract::Json, routing::{delete, get, post, put}, Extension};
+use serde::Deserialize;
 use serde_json::Value;
 use sqlx::PgPool;
-use tracing::debug;
+use std::sync::Arc;
+use tracing::{debug, error};
+use uuid::Uuid;
 
 pub fn router() -> Router {
-    Router::new().route("/", get(get_user_groups).post(post_create_group))
-    // .route("/leave", post(leave_group))
+    Router::new()
+        .route("/", get(get_user_groups).post(post_create_group))
+        .route("/:group_id", get(get_group))
+        .route("/:group_id/retention", put(put_group_retention))
+        .route("/:group_id/export", get(get_group_export))
+        .route("/:group_id/leave", post(post_leave_group))
+        .route("/:group_id/nickname", put(put_own_nickname))
+        .route("/:group_id/nicknames", put(put_nickname_policy))
+        .route("/:group_id/audit", get(get_audit_log))
+        .route(
+            "/:group_id/roles",
+            get(get_group_roles).put(put_group_roles),
+        )
+        .route(
+            "/:group_id/members/:user_id/role",
+            get(get_member_role).put(put_member_role),
+        )
+        .route(
+            "/:group_id/ownership",
+            get(get_group_ownership)
+                .post(post_offer_ownership)
+                .delete(delete_ownership_transfer),
+        )
+        .route("/:group_id/ownership/accept", post(post_accept_ownership))
+        .route(
+            "/:group_id/hierarchy",
+            get(get_role_hierarchy).put(put_role_order),
+        )
+        .route("/:group_id/roles/custom", post(post_create_role))
+        .route(
+            "/:group_id/roles/custom/:role_id",
+            put(put_custom_role).delete(delete_role),
+        )
+        .route("/:group_id/me/privileges", get(get_own_privileges))
+        .route("/:group_id/invitations", get(get_group_invitations))
+        .route("/:group_id/invitations/:code", delete(delete_group_invitation))
+        .route("/:group_id/kicks", get(get_kick_history))
+        .route("/:group_id/bans", get(get_group_bans).post(post_ban_user))
+        .route("/:group_id/bans/:user_id", delete(delete_user_ban))
+        .route("/:group_id/mutes", get(get_group_mutes).post(post_mute_user))
+        .route("/:group_id/mutes/:user_id", delete(delete_user_mute))
+        .route(
+            "/:group_id/reports",
+            get(get_group_reports).post(post_report_message),
+        )
+        .route(
+            "/:group_id/reports/:report_id/resolve",
+            post(post_resolve_report),
+        )
+        .route(
+            "/:group_id/filters",
+            get(get_group_filters).post(post_create_filter),
+        )
+        .route(
+            "/:group_id/filters/:filter_id",
+            put(put_group_filter).delete(delete_filter),
+        )
+        .route(
+            "/:group_id/members/:user_id/nickname",
+            delete(delete_member_nickname),
+        )
+        .route(
+            "/:group_id/webhooks",
+            get(get_group_webhooks).post(post_create_webhook),
+        )
+        .route("/:group_id/webhooks/:webhook_id", delete(delete_webhook))
+        .route(
+            "/:group_id/event-webhooks",
+            get(get_event_webhooks).post(post_create_event_webhook),
+        )
+        .route(
+            "/:group_id/event-webhooks/:webhook_id",
+            delete(delete_group_event_webhook),
+        )
+        .route(
+            "/:group_id/event-webhooks/:webhook_id/deliveries",
+            get(get_event_deliveries),
+        )
 }
 
 async fn get_user_groups(
@@ -40,11 +171,904 @@ async fn post_create_group(
     Ok(res)
 }
 
-// async fn leave_group(
-//     claims: Claims,
-//     Extension(pool): Extension<PgPool>,
-//     Json(group_id): Json<Uuid>,
-//  ) -> Result<(), AppError> {
-//     tracing::trace!("JWT: {:#?}", claims);
-//     Ok(try_remove_user_from_group(&pool, claims.user_id, group_id).await?)
-// }
+async fn get_group(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<GroupInfo>, AppError> {
+    if !check_if_group_member(&pool, &claims.user_id, &group_id).await? {
+        return Err(GroupError::UserNotInGroup)?;
+    }
+
+    let info = get_group_info(&pool, &group_id).await?;
+    Ok(Json(info))
+}
+
+async fn put_group_retention(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Json(policy): Json<RetentionPolicy>,
+) -> Result<(), AppError> {
+    set_group_retention(&pool, &claims.user_id, &group_id, policy).await?;
+
+    debug!(
+        "User {} ({}) changed group {} retention to {:?} days",
+        &claims.user_id, &claims.login, group_id, policy.days
+    );
+    Ok(())
+}
+
+async fn put_own_nickname(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path(group_id): Path<Uuid>,
+    Json(change): Json<NicknameChange>,
+) -> Result<Json<MemberNickname>, AppError> {
+    let nickname = set_group_nickname(&pool, &claims.user_id, &group_id, &change.nickname).await?;
+    let member = MemberNickname {
+        user_id: claims.user_id,
+        nickname,
+    };
+
+    debug!(
+        "User {} ({}) changed their nickname in group {} to {}",
+        &claims.user_id, &claims.login, group_id, member.nickname
+    );
+
+    broadcast_nickname(&state, &group_id, &member);
+    Ok(Json(member))
+}
+
+async fn delete_member_nickname(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<MemberNickname>, AppError> {
+    let nickname = reset_group_nickname(&pool, &gates.nicknames, &claims.user_id, &group_id, &user_id).await?;
+    let member = MemberNickname { user_id, nickname };
+
+    debug!(
+        "User {} ({}) reset the nickname of user {} in group {}",
+        &claims.user_id, &claims.login, user_id, group_id
+    );
+
+    broadcast_nickname(&state, &group_id, &member);
+    Ok(Json(member))
+}
+
+async fn put_nickname_policy(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Json(policy): Json<NicknamePolicy>,
+) -> Result<(), AppError> {
+    set_unique_nicknames(&pool, &claims.user_id, &group_id, policy.unique).await?;
+
+    debug!(
+        "User {} ({}) set unique nicknames in group {} to {}",
+        &claims.user_id, &claims.login, group_id, policy.unique
+    );
+    Ok(())
+}
+
+/// Lets connected clients update their member lists
+fn broadcast_nickname(state: &ChatState, group_id: &Uuid, member: &MemberNickname) {
+    if let Some(group_controller) = state.groups.get_loaded(group_id) {
+        group_controller
+            .channel
+            .sender
+            .send(ServerAction::NicknameChanged(member.clone()));
+    }
+}
+
+async fn get_group_roles(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<GroupRolePrivileges>, AppError> {
+    if !check_if_group_member(&pool, &claims.user_id, &group_id).await? {
+        return Err(GroupError::UserNotInGroup)?;
+    }
+
+    let privileges = get_group_role_privileges(&pool, group_id).await?;
+    Ok(Json(privileges))
+}
+
+async fn put_group_roles(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path(group_id): Path<Uuid>,
+    Json(privileges): Json<GroupRolePrivileges>,
+) -> Result<Json<GroupRolePrivileges>, AppError> {
+    let data = GroupPrivilegesChangeData {
+        group_id,
+        privileges,
+    };
+
+    // connected members get the change in the order it was committed
+    let group_controller = state.groups.get_loaded(&group_id);
+    let role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let (privileges, message) =
+        set_group_role_privileges(&pool, &gates.manage_roles, &claims.user_id, &data).await?;
+
+    debug!(
+        "User {} ({}) changed role privileges in group {}",
+        &claims.user_id, &claims.login, group_id
+    );
+
+    if let Some(group_controller) = &group_controller {
+        if let Err(e) = group_controller.set_privileges(&privileges).await {
+            error!("Failed to apply privileges to group {group_id}: {e:?}");
+        }
+        drop(role_changes);
+        if let Some(message) = message {
+            group_controller.channel.sender.send(ServerAction::Message(message));
+        }
+    }
+
+    Ok(Json(privileges))
+}
+
+async fn get_member_role(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<MemberRoleInfo>, AppError> {
+    if !check_if_group_member(&pool, &claims.user_id, &group_id).await? {
+        return Err(GroupError::UserNotInGroup)?;
+    }
+
+    let role = crate::utils::roles::get_member_role(&pool, &user_id, &group_id).await?;
+    Ok(Json(MemberRoleInfo { user_id, role: role.role }))
+}
+
+async fn put_member_role(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+    Json(update): Json<RoleUpdate>,
+) -> Result<Json<MemberRoleInfo>, AppError> {
+    let data = UserRoleChangeData::new(group_id, user_id, update.role);
+
+    let group_controller = state.groups.get_loaded(&group_id);
+    let role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let message = single_set_group_user_role(&pool, &gates.manage_roles, &claims.user_id, &data).await?;
+
+    debug!(
+        "User {} ({}) changed the role of user {} in group {} to {}",
+        &claims.user_id, &claims.login, user_id, group_id, update.role
+    );
+
+    if let Some(group_controller) = &group_controller {
+        // the member may not be connected right now
+        let _ = group_controller.set_role(&data).await;
+        drop(role_changes);
+        group_controller.channel.sender.send(ServerAction::Message(message));
+    }
+
+    let event = GroupEvent::RoleChanged { user_id, role: update.role };
+    if let Err(e) = enqueue_group_event(&pool, &group_id, event).await {
+        error!("Failed to enqueue role changed event: {e:?}");
+    }
+
+    Ok(Json(MemberRoleInfo { user_id, role: update.role }))
+}
+
+async fn get_group_ownership(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Option<OwnershipTransfer>>, AppError> {
+    let transfer = get_ownership_transfer(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(transfer))
+}
+
+async fn post_offer_ownership(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Json(owner): Json<NewOwner>,
+) -> Result<Json<OwnershipTransfer>, AppError> {
+    let transfer = offer_group_ownership(&pool, &claims.user_id, &group_id, &owner.user_id).await?;
+    Ok(Json(transfer))
+}
+
+async fn delete_ownership_transfer(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<(), AppError> {
+    cancel_ownership_transfer(&pool, &claims.user_id, &group_id).await?;
+    Ok(())
+}
+
+async fn post_accept_ownership(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path(group_id): Path<Uuid>,
+) -> Result<(), AppError> {
+    let group_controller = state.groups.get_loaded(&group_id);
+    let role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let (transfer, message) = accept_group_ownership(&pool, &claims.user_id, &group_id).await?;
+
+    debug!(
+        "User {} ({}) took over group {} from user {}",
+        &claims.user_id, &claims.login, group_id, transfer.from_user_id
+    );
+
+    let changes = [
+        UserRoleChangeData::new(group_id, transfer.from_user_id, Role::Admin),
+        UserRoleChangeData::new(group_id, transfer.to_user_id, Role::Owner),
+    ];
+
+    if let Some(group_controller) = &group_controller {
+        for change in changes.iter() {
+            // either of them may not be connected right now
+            let _ = group_controller.set_role(change).await;
+        }
+        drop(role_changes);
+        group_controller.channel.sender.send(ServerAction::Message(message));
+    }
+
+    for change in changes {
+        let event = GroupEvent::RoleChanged { user_id: change.user_id, role: change.value };
+        if let Err(e) = enqueue_group_event(&pool, &group_id, event).await {
+            error!("Failed to enqueue role changed event: {e:?}");
+        }
+    }
+
+    Ok(())
+}
+
+async fn get_role_hierarchy(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<GroupRoles>, AppError> {
+    if !check_if_group_member(&pool, &claims.user_id, &group_id).await? {
+        return Err(GroupError::UserNotInGroup)?;
+    }
+
+    let roles = crate::utils::roles::get_group_roles(&pool, &group_id).await?;
+    Ok(Json(roles))
+}
+
+async fn put_role_order(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path(group_id): Path<Uuid>,
+    Json(order): Json<RoleOrder>,
+) -> Result<Json<GroupRoles>, AppError> {
+    let group_controller = state.groups.get_loaded(&group_id);
+    let _role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let roles = reorder_roles(&pool, &gates.manage_roles, &claims.user_id, &group_id, &order).await?;
+
+    debug!(
+        "User {} ({}) reordered roles in group {}",
+        &claims.user_id, &claims.login, group_id
+    );
+
+    if let Some(group_controller) = &group_controller {
+        group_controller.set_roles(roles.clone()).await;
+    }
+
+    Ok(Json(roles))
+}
+
+async fn post_create_role(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path(group_id): Path<Uuid>,
+    Json(role): Json<NewCustomRole>,
+) -> Result<Json<GroupRoles>, AppError> {
+    let group_controller = state.groups.get_loaded(&group_id);
+    let _role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let roles = create_custom_role(&pool, &gates.manage_roles, &claims.user_id, &group_id, &role).await?;
+
+    if let Some(group_controller) = &group_controller {
+        group_controller.set_roles(roles.clone()).await;
+    }
+
+    Ok(Json(roles))
+}
+
+async fn put_custom_role(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, role_id)): Path<(Uuid, Uuid)>,
+    Json(update): Json<CustomRoleUpdate>,
+) -> Result<Json<GroupRoles>, AppError> {
+    let group_controller = state.groups.get_loaded(&group_id);
+    let _role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let roles = update_custom_role(&pool, &gates.manage_roles, &claims.user_id, &group_id, &role_id, &update).await?;
+
+    if let Some(group_controller) = &group_controller {
+        group_controller.set_roles(roles.clone()).await;
+    }
+
+    Ok(Json(roles))
+}
+
+async fn delete_role(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, role_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<GroupRoles>, AppError> {
+    let group_controller = state.groups.get_loaded(&group_id);
+    let _role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let roles = delete_custom_role(&pool, &gates.manage_roles, &claims.user_id, &group_id, &role_id).await?;
+
+    debug!(
+        "User {} ({}) deleted role {} in group {}",
+        &claims.user_id, &claims.login, role_id, group_id
+    );
+
+    // members of the deleted role are moved to the member role
+    if let Some(group_controller) = &group_controller {
+        group_controller.set_roles(roles.clone()).await;
+    }
+
+    Ok(Json(roles))
+}
+
+async fn get_own_privileges(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Privileges>, AppError> {
+    if !check_if_group_member(&pool, &claims.user_id, &group_id).await? {
+        return Err(GroupError::UserNotInGroup)?;
+    }
+
+    let privileges = get_user_privileges(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(privileges))
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    format: ExportFormat,
+}
+
+async fn get_group_export(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, AppError> {
+    if !check_if_group_member(&pool, &claims.user_id, &group_id).await? {
+        return Err(GroupError::UserNotInGroup)?;
+    }
+
+    let privileges = get_user_privileges(&pool, &claims.user_id, &group_id).await?;
+    if !privileges.satisfies(Privilege::CanExport(CanExport::Yes)) {
+        return Err(GroupError::InsufficientPrivileges)?;
+    }
+
+    let info = get_group_info(&pool, &group_id).await?;
+
+    debug!(
+        "User {} ({}) exports group {} history as {:?}",
+        &claims.user_id, &claims.login, group_id, params.format
+    );
+
+    let headers = [
+        (CONTENT_TYPE, params.format.content_type().to_string()),
+        (
+            CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"{group_id}.{}\"",
+                params.format.extension()
+            ),
+        ),
+    ];
+    let body = StreamBody::new(export_messages(pool, group_id, info.name, params.format));
+
+    Ok((headers, body))
+}
+
+async fn get_group_webhooks(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<WebhookInfo>>, AppError> {
+    let webhooks = fetch_group_webhooks(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(webhooks))
+}
+
+async fn post_create_webhook(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Json(webhook): Json<NewWebhook>,
+) -> Result<Json<CreatedWebhook>, AppError> {
+    let webhook = create_webhook(&pool, &claims.user_id, &group_id, &webhook.name).await?;
+
+    debug!(
+        "User {} ({}) created webhook {} in group {}",
+        &claims.user_id, &claims.login, webhook.id, group_id
+    );
+    Ok(Json(webhook))
+}
+
+async fn delete_webhook(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path((group_id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<(), AppError> {
+    revoke_webhook(&pool, &claims.user_id, &group_id, &webhook_id).await?;
+
+    debug!(
+        "User {} ({}) revoked webhook {} in group {}",
+        &claims.user_id, &claims.login, webhook_id, group_id
+    );
+    Ok(())
+}
+
+async fn get_event_webhooks(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<EventWebhookInfo>>, AppError> {
+    let webhooks = fetch_event_webhooks(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(webhooks))
+}
+
+async fn post_create_event_webhook(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(settings): Extension<WebhookSettings>,
+    Path(group_id): Path<Uuid>,
+    Json(webhook): Json<NewEventWebhook>,
+) -> Result<Json<CreatedEventWebhook>, AppError> {
+    let webhook =
+        create_event_webhook(&pool, &settings, &claims.user_id, &group_id, &webhook.url, webhook.events)
+            .await?;
+
+    debug!(
+        "User {} ({}) registered event webhook {} in group {}",
+        &claims.user_id, &claims.login, webhook.id, group_id
+    );
+    Ok(Json(webhook))
+}
+
+async fn delete_group_event_webhook(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path((group_id, webhook_id)): Path<(Uuid, Uuid)>,
+) -> Result<(), AppError> {
+    delete_event_webhook(&pool, &claims.user_id, &group_id, &webhook_id).await?;
+
+    debug!(
+        "User {} ({}) deleted event webhook {} in group {}",
+        &claims.user_id, &claims.login, webhook_id, group_id
+    );
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct DeliveryLogParams {
+    before: Option<i32>,
+}
+
+async fn get_event_deliveries(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path((group_id, webhook_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<DeliveryLogParams>,
+) -> Result<Json<Vec<EventDelivery>>, AppError> {
+    let deliveries = fetch_event_deliveries(
+        &pool,
+        &claims.user_id,
+        &group_id,
+        &webhook_id,
+        params.before,
+    )
+    .await?;
+    Ok(Json(deliveries))
+}
+
+async fn get_audit_log(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Query(filter): Query<AuditFilter>,
+) -> Result<Json<Vec<AuditRecord>>, AppError> {
+    let records = fetch_audit_log(&pool, &claims.user_id, &group_id, filter).await?;
+    Ok(Json(records))
+}
+
+async fn get_group_invitations(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<GroupInvitationInfo>>, AppError> {
+    let invitations = fetch_group_invitations(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(invitations))
+}
+
+async fn delete_group_invitation(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path((group_id, code)): Path<(Uuid, String)>,
+) -> Result<(), AppError> {
+    revoke_group_invitation(&pool, &claims.user_id, &group_id, &code).await?;
+
+    debug!(
+        "User {} ({}) revoked invitation {} of group {}",
+        &claims.user_id, &claims.login, code, group_id
+    );
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct KickHistoryParams {
+    before: Option<i32>,
+}
+
+async fn get_kick_history(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Query(params): Query<KickHistoryParams>,
+) -> Result<Json<Vec<KickRecord>>, AppError> {
+    let kicks = fetch_kick_history(&pool, &claims.user_id, &group_id, params.before).await?;
+    Ok(Json(kicks))
+}
+
+async fn get_group_bans(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<GroupBan>>, AppError> {
+    let bans = fetch_group_bans(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(bans))
+}
+
+async fn post_ban_user(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path(group_id): Path<Uuid>,
+    Json(new_ban): Json<NewBan>,
+) -> Result<Json<GroupBan>, AppError> {
+    let (ban, message) = ban_user(&pool, &gates.ban, &group_id, &claims.user_id, new_ban).await?;
+
+    debug!(
+        "User {} ({}) banned user {} from group {}",
+        &claims.user_id, &claims.login, ban.user_id, group_id
+    );
+
+    // A banned member is removed from the group like a kicked one
+    if let (Some(message), Some(group_controller)) = (message, state.groups.get_loaded(&group_id)) {
+        if let Some(SystemEvent::MemberBanned { moderator, reason, .. }) = message.event.clone() {
+            let kick = KickMessage {
+                from: moderator,
+                reason,
+            };
+            group_controller.kick(ban.user_id, kick).await;
+        }
+        group_controller.channel.sender.send(ServerAction::Message(message));
+    }
+
+    Ok(Json(ban))
+}
+
+async fn delete_user_ban(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<(), AppError> {
+    unban_user(&pool, &gates.ban, &group_id, &claims.user_id, &user_id).await?;
+
+    debug!(
+        "User {} ({}) lifted the ban of user {} in group {}",
+        &claims.user_id, &claims.login, user_id, group_id
+    );
+    Ok(())
+}
+
+async fn get_group_mutes(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<GroupMute>>, AppError> {
+    let mutes = fetch_group_mutes(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(mutes))
+}
+
+async fn post_mute_user(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path(group_id): Path<Uuid>,
+    Json(mute): Json<NewMute>,
+) -> Result<(), AppError> {
+    let duration = time::Duration::seconds(mute.duration);
+    let muted_until = mute_user(&pool, &gates.mute, &group_id, &mute.user_id, &claims.user_id, duration).await?;
+
+    debug!(
+        "User {} ({}) muted user {} in group {}",
+        &claims.user_id, &claims.login, mute.user_id, group_id
+    );
+
+    if let Some(group_controller) = state.groups.get_loaded(&group_id) {
+        group_controller.channel.sender.send(ServerAction::UserMuted {
+            user_id: mute.user_id,
+            muted_until: muted_until.unix_timestamp(),
+        });
+    }
+    Ok(())
+}
+
+async fn delete_user_mute(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<(), AppError> {
+    unmute_user(&pool, &gates.mute, &group_id, &claims.user_id, &user_id).await?;
+
+    debug!(
+        "User {} ({}) unmuted user {} in group {}",
+        &claims.user_id, &claims.login, user_id, group_id
+    );
+
+    if let Some(group_controller) = state.groups.get_loaded(&group_id) {
+        group_controller
+            .channel
+            .sender
+            .send(ServerAction::UserUnmuted { user_id });
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ReportQueueParams {
+    #[serde(default)]
+    resolved: bool,
+    before: Option<i32>,
+}
+
+async fn get_group_reports(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+    Query(params): Query<ReportQueueParams>,
+) -> Result<Json<Vec<MessageReport>>, AppError> {
+    let reports =
+        fetch_group_reports(&pool, &claims.user_id, &group_id, params.resolved, params.before)
+            .await?;
+    Ok(Json(reports))
+}
+
+async fn post_report_message(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path(group_id): Path<Uuid>,
+    Json(report): Json<NewReport>,
+) -> Result<Json<MessageReport>, AppError> {
+    let report = report_message(&pool, &claims.user_id, &group_id, report).await?;
+
+    debug!(
+        "User {} ({}) filed report {} in group {}",
+        &claims.user_id, &claims.login, report.id, group_id
+    );
+
+    if let Some(group_controller) = state.groups.get_loaded(&group_id) {
+        let action = ServerAction::ReportCreated(report.clone());
+        group_controller.send_to(&action, |_, privileges| can_moderate_reports(privileges)).await;
+    }
+    Ok(Json(report))
+}
+
+async fn post_resolve_report(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Extension(gates): Extension<Gates>,
+    Path((group_id, report_id)): Path<(Uuid, i32)>,
+    Json(action): Json<ReportAction>,
+) -> Result<(), AppError> {
+    let resolution = action.resolution();
+    let (closed, effect) = close_report(&pool, &gates, &claims.user_id, &group_id, report_id, action).await?;
+
+    debug!(
+        "User {} ({}) resolved report {} in group {} with {:?}",
+        &claims.user_id, &claims.login, report_id, group_id, resolution
+    );
+
+    let group_controller = state.groups.get_loaded(&group_id);
+    match (effect, group_controller.as_ref()) {
+        (ResolutionEffect::AuthorKicked { user_id, message, kick }, _) => {
+            announce_kick(&state, group_id, user_id, *message, kick).await;
+        }
+        (ResolutionEffect::MessageDeleted { message_id }, Some(group_controller)) => {
+            group_controller
+                .channel
+                .sender
+                .send(ServerAction::MessageDeleted { id: message_id });
+        }
+        (ResolutionEffect::AuthorMuted { user_id, muted_until }, Some(group_controller)) => {
+            group_controller.channel.sender.send(ServerAction::UserMuted {
+                user_id,
+                muted_until: muted_until.unix_timestamp(),
+            });
+        }
+        _ => (),
+    }
+
+    let Some(group_controller) = group_controller else {
+        return Ok(());
+    };
+
+    // moderators keep their queue in sync, reporters learn the outcome
+    for report in closed {
+        let action = ServerAction::ReportResolved {
+            id: report.id,
+            resolution,
+        };
+        group_controller
+            .send_to(&action, |user_id, privileges| {
+                can_moderate_reports(privileges) || Some(user_id) == report.reporter_id
+            })
+            .await;
+    }
+    Ok(())
+}
+
+async fn get_group_filters(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<Vec<ContentFilter>>, AppError> {
+    let filters = fetch_group_filters(&pool, &claims.user_id, &group_id).await?;
+    Ok(Json(filters))
+}
+
+async fn post_create_filter(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path(group_id): Path<Uuid>,
+    Json(filter): Json<NewContentFilter>,
+) -> Result<Json<ContentFilter>, AppError> {
+    let filter = create_group_filter(&pool, &claims.user_id, &group_id, filter).await?;
+
+    debug!(
+        "User {} ({}) added content filter {} in group {}",
+        &claims.user_id, &claims.login, filter.id, group_id
+    );
+
+    invalidate_filters(&state, &group_id).await;
+    Ok(Json(filter))
+}
+
+async fn put_group_filter(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path((group_id, filter_id)): Path<(Uuid, i32)>,
+    Json(filter): Json<NewContentFilter>,
+) -> Result<Json<ContentFilter>, AppError> {
+    let filter = update_group_filter(&pool, &claims.user_id, &group_id, filter_id, filter).await?;
+
+    debug!(
+        "User {} ({}) edited content filter {} in group {}",
+        &claims.user_id, &claims.login, filter_id, group_id
+    );
+
+    invalidate_filters(&state, &group_id).await;
+    Ok(Json(filter))
+}
+
+async fn delete_filter(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path((group_id, filter_id)): Path<(Uuid, i32)>,
+) -> Result<(), AppError> {
+    delete_group_filter(&pool, &claims.user_id, &group_id, filter_id).await?;
+
+    debug!(
+        "User {} ({}) deleted content filter {} in group {}",
+        &claims.user_id, &claims.login, filter_id, group_id
+    );
+
+    invalidate_filters(&state, &group_id).await;
+    Ok(())
+}
+
+/// Makes connected members use the edited rules with their next message
+async fn invalidate_filters(state: &ChatState, group_id: &Uuid) {
+    if let Some(group_controller) = state.groups.get_loaded(group_id) {
+        group_controller.invalidate_filters().await;
+    }
+}
+
+async fn post_leave_group(
+    claims: Claims,
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path(group_id): Path<Uuid>,
+) -> Result<(), AppError> {
+    let group_controller = state.groups.get_loaded(&group_id);
+    let role_changes = match &group_controller {
+        Some(group_controller) => Some(group_controller.lock_role_changes().await),
+        None => None,
+    };
+
+    let (message, succession) = try_remove_user_from_group(&pool, claims.user_id, group_id).await?;
+
+    debug!(
+        "User {} ({}) left group {}",
+        &claims.user_id, &claims.login, group_id
+    );
+
+    let Some(group_controller) = &group_controller else {
+        return Ok(());
+    };
+
+    group_controller.leave(claims.user_id).await;
+    group_controller.channel.sender.send(ServerAction::Message(message));
+
+    if let Some(message) = succession {
+        if let Some(SystemEvent::OwnershipTransferred { user_id, .. }) = message.event {
+            // the new owner may not be connected right now
+            let _ = group_controller.set_role(&UserRoleChangeData::new(group_id, user_id, Role::Owner)).await;
+        }
+        group_controller.channel.sender.send(ServerAction::Message(message));
+    }
+    drop(role_changes);
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use crate::app_errors::AppError;
 use crate::utils::auth::models::*;
+use crate::utils::chat::socket::{ChatState, ServerAction};
 use crate::utils::groups::models::GroupInfo;
 use crate::utils::invitations::{
     fetch_group_info_by_code, try_create_group_invitation_with_code, try_join_group_by_code,
@@ -10,6 +11,7 @@ use axum::{extract::Json, routing::post, Extension};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use sqlx::PgPool;
+use std::sync::Arc;
 use tracing::debug;
 
 pub fn router() -> Router {
@@ -53,9 +55,14 @@ async fn post_fetch_group_info_by_code(
 async fn post_join_group_by_code(
     claims: Claims,
     Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
     Json(payload): Json<JoinGroupCode>,
 ) -> Result<(), AppError> {
-    try_join_group_by_code(&pool, &claims.user_id, &payload.code).await?;
+    let (group_id, message) = try_join_group_by_code(&pool, &claims.user_id, &payload.code).await?;
+
+    if let Some(group_controller) = state.groups.get_loaded(&group_id) {
+        group_controller.channel.sender.send(ServerAction::Message(message));
+    }
 
     debug!(
         "User {} ({}) joined a group successfully",
@@ -2,4 +2,5 @@
 pub mod chat;
 pub mod groups;
 pub mod invitations;
-pub mod friends;
\ No newline at end of file
+pub mod friends;
+pub mod webhooks;
\ No newline at end of file
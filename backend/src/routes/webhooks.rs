@@ -0,0 +1,36 @@
+use crate::app_errors::AppError;
+use crate::utils::chat::socket::{ChatState, ServerAction};
+use crate::utils::webhooks::models::WebhookPayload;
+use crate::utils::webhooks::post_webhook_message;
+use axum::extract::Path;
+use axum::Router;
+use axum::{extract::Json, routing::post, Extension};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::debug;
+use uuid::Uuid;
+
+pub fn router() -> Router {
+    Router::new().route("/:webhook_id/:token", post(post_webhook))
+}
+
+// Unauthenticated on purpose - the token in the url is the credential
+async fn post_webhook(
+    Extension(pool): Extension<PgPool>,
+    Extension(state): Extension<Arc<ChatState>>,
+    Path((webhook_id, token)): Path<(Uuid, String)>,
+    Json(payload): Json<WebhookPayload>,
+) -> Result<(), AppError> {
+    let (group_id, message) =
+        post_webhook_message(&pool, &webhook_id, &token, &payload.content).await?;
+
+    if let Some(group_controller) = state.groups.get_loaded(&group_id) {
+        group_controller
+            .channel
+            .sender
+            .send(ServerAction::Message(message));
+    }
+
+    debug!("Webhook {webhook_id} posted a message in group {group_id}");
+    Ok(())
+}
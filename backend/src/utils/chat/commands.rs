@@ -0,0 +1,192 @@
+use time::Duration;
+
+use super::errors::ChatError;
+use super::models::CommandInfo;
+use crate::utils::groups::mutes::MAX_MUTE_DURATION;
+use crate::utils::roles::privileges::{CanInvite, CanKick, CanMute, CanSendMessages, Privilege, Privileges};
+
+pub const COMMAND_PREFIX: char = '/';
+pub const MENTION_PREFIX: char = '@';
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Nick { name: String },
+    Me { action: String },
+    Kick { target: String, reason: Option<String> },
+    Mute { target: String, duration: Duration },
+    Unmute { target: String },
+    Invite,
+}
+
+struct CommandSpec {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    /// Privilege needed to run the command
+    privilege: Option<Privilege>,
+}
+
+const COMMANDS: [CommandSpec; 6] = [
+    CommandSpec {
+        name: "nick",
+        usage: "/nick <name>",
+        description: "Change your nickname in this group",
+        privilege: None,
+    },
+    CommandSpec {
+        name: "me",
+        usage: "/me <action>",
+        description: "Describe what you are doing",
+        privilege: Some(Privilege::CanSendMessages(CanSendMessages::Yes(usize::MAX))),
+    },
+    CommandSpec {
+        name: "kick",
+        usage: "/kick @user [reason]",
+        description: "Remove a member from the group",
+        privilege: Some(Privilege::CanKick(CanKick::Yes)),
+    },
+    CommandSpec {
+        name: "mute",
+        usage: "/mute @user <duration>",
+        description: "Stop a member from sending messages, e.g. 30m, 2h or 1d",
+        privilege: Some(Privilege::CanMute(CanMute::Yes)),
+    },
+    CommandSpec {
+        name: "unmute",
+        usage: "/unmute @user",
+        description: "Let a muted member send messages again",
+        privilege: Some(Privilege::CanMute(CanMute::Yes)),
+    },
+    CommandSpec {
+        name: "invite",
+        usage: "/invite",
+        description: "Create an invitation code valid for a day",
+        privilege: Some(Privilege::CanInvite(CanInvite::Yes)),
+    },
+];
+
+/// Commands suggested to a member with the given privileges
+pub fn available_commands(privileges: &Privileges) -> Vec<CommandInfo> {
+    COMMANDS
+        .iter()
+        .filter(|spec| spec.privilege.map_or(true, |min_val| privileges.satisfies(min_val)))
+        .map(|spec| CommandInfo {
+            name: spec.name.into(),
+            usage: spec.usage.into(),
+            description: spec.description.into(),
+        })
+        .collect()
+}
+
+/// Returns `None` if the content is a plain text message
+pub fn parse_command(content: &str) -> Option<Result<Command, ChatError>> {
+    let content = content.trim_start().strip_prefix(COMMAND_PREFIX)?;
+
+    let (name, args) = match content.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (content, ""),
+    };
+
+    let Some(spec) = COMMANDS.iter().find(|spec| spec.name == name) else {
+        return Some(Err(ChatError::UnknownCommand(name.to_string())));
+    };
+    let invalid_usage = || ChatError::InvalidCommandUsage(spec.usage.to_string());
+
+    let command = match spec.name {
+        "nick" if !args.is_empty() => Ok(Command::Nick { name: args.into() }),
+        "me" if !args.is_empty() => Ok(Command::Me { action: args.into() }),
+        "kick" => {
+            let (target, reason) = match args.split_once(char::is_whitespace) {
+                Some((target, reason)) => (target, Some(reason.trim().to_string())),
+                None => (args, None),
+            };
+            parse_mention(target)
+                .map(|target| Command::Kick { target, reason })
+                .ok_or_else(invalid_usage)
+        }
+        "mute" => {
+            let mut args = args.split_whitespace();
+            let target = args.next().and_then(parse_mention);
+            let duration = args.next().and_then(parse_duration);
+            match (target, duration, args.next()) {
+                (Some(target), Some(duration), None) => Ok(Command::Mute { target, duration }),
+                _ => Err(invalid_usage()),
+            }
+        }
+        "unmute" => parse_mention(args)
+            .map(|target| Command::Unmute { target })
+            .ok_or_else(invalid_usage),
+        "invite" if args.is_empty() => Ok(Command::Invite),
+        _ => Err(invalid_usage()),
+    };
+
+    Some(command)
+}
+
+fn parse_mention(arg: &str) -> Option<String> {
+    let nickname = arg.strip_prefix(MENTION_PREFIX)?;
+    (!nickname.is_empty()).then(|| nickname.to_string())
+}
+
+/// Parses durations like `90s`, `30m`, `2h` or `7d`
+pub fn parse_duration(arg: &str) -> Option<Duration> {
+    let unit_at = arg.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = arg.split_at(unit_at);
+    let amount: i64 = amount.parse().ok()?;
+
+    let duration = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        _ => return None,
+    };
+
+    (duration.is_positive() && duration <= MAX_MUTE_DURATION).then_some(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_not_a_command() {
+        assert!(parse_command("hello /nick").is_none());
+    }
+
+    #[test]
+    fn parse_kick_with_reason() {
+        let command = parse_command("/kick @Marco spamming links").unwrap().unwrap();
+        assert_eq!(
+            command,
+            Command::Kick {
+                target: "Marco".into(),
+                reason: Some("spamming links".into())
+            }
+        );
+    }
+
+    #[test]
+    fn parse_mute() {
+        let command = parse_command("/mute @Polo 30m").unwrap().unwrap();
+        assert_eq!(
+            command,
+            Command::Mute {
+                target: "Polo".into(),
+                duration: Duration::minutes(30)
+            }
+        );
+    }
+
+    #[test]
+    fn parse_mute_without_duration() {
+        let res = parse_command("/mute @Polo").unwrap();
+        assert!(matches!(res, Err(ChatError::InvalidCommandUsage(_))));
+    }
+
+    #[test]
+    fn parse_unknown_command() {
+        let res = parse_command("/shrug").unwrap();
+        assert!(matches!(res, Err(ChatError::UnknownCommand(name)) if name == "shrug"));
+    }
+}
@@ -2,10 +2,65 @@ use axum::{http::StatusCode, response::IntoResponse, Json};
 use serde_json::json;
 use thiserror::Error;
 
+use crate::utils::groups::errors::GroupError;
+use crate::utils::roles::errors::RoleError;
+
 #[derive(Error, Debug)]
 pub enum ChatError {
     #[error("Empty message")]
     EmptyMessage,
+    #[error("Invalid scheduled message time")]
+    InvalidScheduleTime,
+    #[error("Too many scheduled messages")]
+    TooManyScheduledMessages,
+    #[error("Scheduled message not found")]
+    ScheduledMessageNotFound,
+    #[error("Invalid poll")]
+    InvalidPoll,
+    #[error("Poll not found")]
+    PollNotFound,
+    #[error("Poll is already closed")]
+    PollClosed,
+    #[error("Invalid vote")]
+    InvalidVote,
+    #[error("Insufficient privileges")]
+    InsufficientPrivileges,
+    #[error("Unknown command /{0}")]
+    UnknownCommand(String),
+    #[error("Usage: {0}")]
+    InvalidCommandUsage(String),
+    #[error("Group member not found")]
+    MemberNotFound,
+    #[error("More than one group member uses this nickname")]
+    AmbiguousMember,
+    #[error("Invalid nickname")]
+    InvalidNickname,
+    #[error("Nickname is already taken in this group")]
+    NicknameTaken,
+    #[error("Some group members share a nickname")]
+    NicknamesNotUnique,
+    #[error("You are muted in this group")]
+    UserMuted,
+    #[error("Reason is too long")]
+    ReasonTooLong,
+    #[error("Cannot kick yourself")]
+    CannotKickSelf,
+    #[error("Cannot mute yourself")]
+    CannotMuteSelf,
+    #[error("User is not muted")]
+    MuteNotFound,
+    #[error("Message blocked by the group content filter")]
+    MessageBlocked,
+    #[error("Too many actions, slow down")]
+    RateLimited,
+    #[error("The owner role can only be handed over by an ownership transfer")]
+    OwnershipTransferRequired,
+    #[error("Role change rejected")]
+    RoleChangeRejected,
+    #[error("A role can't have higher privileges than the role above it")]
+    HierarchyViolation,
+    #[error("Role not found in the group")]
+    RoleNotFound,
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
 }
@@ -14,17 +69,111 @@ impl IntoResponse for ChatError {
     fn into_response(self) -> axum::response::Response {
         let status_code = match &self {
             ChatError::EmptyMessage => StatusCode::BAD_REQUEST,
+            ChatError::InvalidScheduleTime => StatusCode::BAD_REQUEST,
+            ChatError::TooManyScheduledMessages => StatusCode::BAD_REQUEST,
+            ChatError::ScheduledMessageNotFound => StatusCode::NOT_FOUND,
+            ChatError::InvalidPoll => StatusCode::BAD_REQUEST,
+            ChatError::PollNotFound => StatusCode::NOT_FOUND,
+            ChatError::PollClosed => StatusCode::BAD_REQUEST,
+            ChatError::InvalidVote => StatusCode::BAD_REQUEST,
+            ChatError::InsufficientPrivileges => StatusCode::FORBIDDEN,
+            ChatError::UnknownCommand(_) => StatusCode::BAD_REQUEST,
+            ChatError::InvalidCommandUsage(_) => StatusCode::BAD_REQUEST,
+            ChatError::MemberNotFound => StatusCode::NOT_FOUND,
+            ChatError::AmbiguousMember => StatusCode::BAD_REQUEST,
+            ChatError::InvalidNickname => StatusCode::BAD_REQUEST,
+            ChatError::NicknameTaken => StatusCode::CONFLICT,
+            ChatError::NicknamesNotUnique => StatusCode::CONFLICT,
+            ChatError::UserMuted => StatusCode::FORBIDDEN,
+            ChatError::ReasonTooLong => StatusCode::BAD_REQUEST,
+            ChatError::CannotKickSelf => StatusCode::BAD_REQUEST,
+            ChatError::CannotMuteSelf => StatusCode::BAD_REQUEST,
+            ChatError::MuteNotFound => StatusCode::NOT_FOUND,
+            ChatError::MessageBlocked => StatusCode::BAD_REQUEST,
+            ChatError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ChatError::OwnershipTransferRequired => StatusCode::BAD_REQUEST,
+            ChatError::RoleChangeRejected => StatusCode::BAD_REQUEST,
+            ChatError::HierarchyViolation => StatusCode::BAD_REQUEST,
+            ChatError::RoleNotFound => StatusCode::NOT_FOUND,
             ChatError::Unexpected(e) => {
                 tracing::error!("Internal server error: {e:?}");
                 StatusCode::INTERNAL_SERVER_ERROR
             }
         };
 
-        let info = match self {
+        (status_code, Json(json!({ "error_info": self.info() }))).into_response()
+    }
+}
+
+impl ChatError {
+    /// Machine readable name of the error sent to websocket clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChatError::EmptyMessage => "empty_message",
+            ChatError::InvalidScheduleTime => "invalid_schedule_time",
+            ChatError::TooManyScheduledMessages => "too_many_scheduled_messages",
+            ChatError::ScheduledMessageNotFound => "scheduled_message_not_found",
+            ChatError::InvalidPoll => "invalid_poll",
+            ChatError::PollNotFound => "poll_not_found",
+            ChatError::PollClosed => "poll_closed",
+            ChatError::InvalidVote => "invalid_vote",
+            ChatError::InsufficientPrivileges => "insufficient_privileges",
+            ChatError::UnknownCommand(_) => "unknown_command",
+            ChatError::InvalidCommandUsage(_) => "invalid_command_usage",
+            ChatError::MemberNotFound => "member_not_found",
+            ChatError::AmbiguousMember => "ambiguous_member",
+            ChatError::InvalidNickname => "invalid_nickname",
+            ChatError::NicknameTaken => "nickname_taken",
+            ChatError::NicknamesNotUnique => "nicknames_not_unique",
+            ChatError::UserMuted => "user_muted",
+            ChatError::ReasonTooLong => "reason_too_long",
+            ChatError::CannotKickSelf => "cannot_kick_self",
+            ChatError::CannotMuteSelf => "cannot_mute_self",
+            ChatError::MuteNotFound => "mute_not_found",
+            ChatError::MessageBlocked => "message_blocked",
+            ChatError::RateLimited => "rate_limited",
+            ChatError::OwnershipTransferRequired => "ownership_transfer_required",
+            ChatError::RoleChangeRejected => "role_change_rejected",
+            ChatError::HierarchyViolation => "hierarchy_violation",
+            ChatError::RoleNotFound => "role_not_found",
+            ChatError::Unexpected(_) => "unexpected",
+        }
+    }
+
+    /// Error description safe to show to the user
+    pub fn info(&self) -> String {
+        match self {
             ChatError::Unexpected(_) => "Unexpected server error".into(),
             _ => self.to_string(),
-        };
+        }
+    }
+}
+
+impl From<GroupError> for ChatError {
+    fn from(e: GroupError) -> Self {
+        match e {
+            GroupError::UserNotInGroup => ChatError::MemberNotFound,
+            GroupError::InsufficientPrivileges => ChatError::InsufficientPrivileges,
+            GroupError::ReasonTooLong => ChatError::ReasonTooLong,
+            GroupError::CannotKickSelf => ChatError::CannotKickSelf,
+            GroupError::CannotMuteSelf => ChatError::CannotMuteSelf,
+            GroupError::MuteNotFound => ChatError::MuteNotFound,
+            GroupError::RoleError(e) => ChatError::from(e),
+            e => ChatError::Unexpected(anyhow::Error::from(e)),
+        }
+    }
+}
 
-        (status_code, Json(json!({ "error_info": info }))).into_response()
+impl From<RoleError> for ChatError {
+    fn from(e: RoleError) -> Self {
+        match e {
+            RoleError::UserNotFound => ChatError::MemberNotFound,
+            RoleError::InsufficientPrivileges => ChatError::InsufficientPrivileges,
+            RoleError::OwnershipTransferRequired => ChatError::OwnershipTransferRequired,
+            RoleError::RoleChangeRejection => ChatError::RoleChangeRejected,
+            RoleError::HierarchyViolation => ChatError::HierarchyViolation,
+            RoleError::RoleNotFound => ChatError::RoleNotFound,
+            e => ChatError::Unexpected(anyhow::Error::from(e)),
+        }
     }
 }
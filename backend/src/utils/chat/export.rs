@@ -0,0 +1,213 @@
+use anyhow::Context;
+use futures::{stream, Stream};
+use maud::{html, PreEscaped, DOCTYPE};
+use sqlx::{query_as, PgPool};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use super::errors::ChatError;
+use super::models::{ExportFormat, ExportedMessage, ExportedMessageModel};
+
+/// Amount of messages fetched from the database for a single exported chunk
+const EXPORT_PAGE_SIZE: i64 = 500;
+
+const EXPORT_STYLE: &str = r#"
+    body { font-family: sans-serif; max-width: 60rem; margin: 2rem auto; color: #222; }
+    .message { border-bottom: 1px solid #ddd; padding: 0.5rem 0; }
+    .author { font-weight: bold; }
+    time { color: #777; font-size: 0.8rem; }
+    .content { margin: 0.25rem 0 0; white-space: pre-wrap; }
+"#;
+
+pub async fn fetch_messages_page(
+    pool: &PgPool,
+    group_id: &Uuid,
+    after_id: i32,
+    limit: i64,
+) -> Result<Vec<ExportedMessage>, ChatError> {
+    let messages = query_as!(
+        ExportedMessageModel,
+        r#"
+            select m.id, coalesce(gu.nickname, w.name, u.username, 'System') as "author!", m.content, m.sent_at from messages as m
+            left join users u on u.id = m.user_id
+            left join group_users gu on gu.group_id = m.group_id and gu.user_id = m.user_id
+            left join group_webhooks w on w.id = m.webhook_id
+            where m.group_id = $1
+            and m.id > $2
+            order by m.id
+            limit $3
+        "#,
+        group_id,
+        after_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch messages for export")?;
+
+    messages
+        .into_iter()
+        .map(|msg| {
+            Ok(ExportedMessage {
+                id: msg.id,
+                author: msg.author,
+                sent_at: format_timestamp(msg.sent_at)?,
+                content: msg.content,
+            })
+        })
+        .collect()
+}
+
+enum ExportStage {
+    Header,
+    Page { after_id: i32, is_first: bool },
+    Footer,
+    Done,
+}
+
+/// Streams the whole group history page by page, so it never has to be held in memory
+pub fn export_messages(
+    pool: PgPool,
+    group_id: Uuid,
+    group_name: String,
+    format: ExportFormat,
+) -> impl Stream<Item = Result<String, ChatError>> {
+    stream::unfold(ExportStage::Header, move |stage| {
+        let pool = pool.clone();
+        let group_name = group_name.clone();
+        async move {
+            match stage {
+                ExportStage::Header => Some((
+                    export_header(format, &group_name),
+                    ExportStage::Page {
+                        after_id: 0,
+                        is_first: true,
+                    },
+                )),
+                ExportStage::Page { after_id, is_first } => {
+                    let messages =
+                        match fetch_messages_page(&pool, &group_id, after_id, EXPORT_PAGE_SIZE).await {
+                            Ok(messages) => messages,
+                            Err(e) => return Some((Err(e), ExportStage::Done)),
+                        };
+
+                    let Some(last) = messages.last() else {
+                        return Some((Ok(export_footer(format)), ExportStage::Done));
+                    };
+
+                    let next = if (messages.len() as i64) < EXPORT_PAGE_SIZE {
+                        ExportStage::Footer
+                    } else {
+                        ExportStage::Page {
+                            after_id: last.id,
+                            is_first: false,
+                        }
+                    };
+
+                    Some((export_page(format, &messages, is_first), next))
+                }
+                ExportStage::Footer => Some((Ok(export_footer(format)), ExportStage::Done)),
+                ExportStage::Done => None,
+            }
+        }
+    })
+}
+
+fn export_header(format: ExportFormat, group_name: &str) -> Result<String, ChatError> {
+    let exported_at = format_timestamp(OffsetDateTime::now_utc())?;
+
+    let header = match format {
+        ExportFormat::Json => format!(
+            r#"{{"group":{},"exported_at":"{}","messages":["#,
+            serde_json::to_string(group_name).context("Failed to serialize group name")?,
+            exported_at
+        ),
+        ExportFormat::Csv => String::from("id,author,sent_at,content\n"),
+        ExportFormat::Html => html! {
+            (DOCTYPE)
+            head {
+                meta charset="utf-8";
+                title { (group_name) " - Chad chat archive" }
+                style { (PreEscaped(EXPORT_STYLE)) }
+            }
+            header {
+                h1 { (group_name) }
+                p { "Exported at " time datetime=(exported_at) { (exported_at) } }
+            }
+        }
+        .into_string(),
+    };
+
+    Ok(header)
+}
+
+fn export_page(
+    format: ExportFormat,
+    messages: &[ExportedMessage],
+    is_first: bool,
+) -> Result<String, ChatError> {
+    let page = match format {
+        ExportFormat::Json => {
+            let mut page = messages
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to serialize exported messages")?
+                .join(",");
+            if !is_first {
+                page.insert(0, ',');
+            }
+            page
+        }
+        ExportFormat::Csv => messages
+            .iter()
+            .map(|msg| {
+                format!(
+                    "{},{},{},{}\n",
+                    msg.id,
+                    escape_csv(&msg.author),
+                    msg.sent_at,
+                    escape_csv(&msg.content)
+                )
+            })
+            .collect(),
+        ExportFormat::Html => html! {
+            @for msg in messages {
+                article.message {
+                    span.author { (msg.author) }
+                    " "
+                    time datetime=(msg.sent_at) { (msg.sent_at) }
+                    p.content { (msg.content) }
+                }
+            }
+        }
+        .into_string(),
+    };
+
+    Ok(page)
+}
+
+fn export_footer(format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Json => String::from("]}"),
+        ExportFormat::Csv => String::new(),
+        ExportFormat::Html => html! {
+            footer { p { "End of the group history" } }
+        }
+        .into_string(),
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn format_timestamp(timestamp: OffsetDateTime) -> Result<String, ChatError> {
+    Ok(timestamp
+        .format(&Rfc3339)
+        .context("Failed to format message timestamp")?)
+}
@@ -0,0 +1,251 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::configuration::ChatSettings;
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, rate: f64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            rate,
+            tokens: capacity as f64,
+            updated_at: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated_at = now;
+    }
+
+    fn has_token(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= 1.0
+    }
+
+    /// Takes a token if there is one left
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        if !self.has_token(now) {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    /// A full bucket holds no information and can be dropped
+    fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+}
+
+/// Limits of a single websocket connection
+pub struct ConnectionLimiter {
+    bucket: TokenBucket,
+    violations: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FloodCheck {
+    Allowed,
+    Limited,
+    /// The client ignored too many refusals and should be disconnected
+    Exceeded,
+}
+
+struct UserLimits {
+    bucket: TokenBucket,
+    last_message: Option<RecentMessage>,
+}
+
+struct RecentMessage {
+    hash: u64,
+    sent_at: Instant,
+    repeats: u32,
+}
+
+/// Rate limits shared by every connection of a user
+pub struct FloodGuard {
+    settings: ChatSettings,
+    users: DashMap<Uuid, UserLimits>,
+}
+
+impl FloodGuard {
+    pub fn new(settings: ChatSettings) -> Self {
+        Self {
+            settings,
+            users: DashMap::new(),
+        }
+    }
+
+    pub fn connection_limiter(&self) -> ConnectionLimiter {
+        ConnectionLimiter {
+            bucket: TokenBucket::new(self.settings.connection_burst, self.settings.connection_rate),
+            violations: 0,
+        }
+    }
+
+    /// Counts an incoming action against the connection and user limits
+    pub fn check_action(&self, limiter: &mut ConnectionLimiter, user_id: Uuid) -> FloodCheck {
+        let now = Instant::now();
+
+        // a refused action does not cost the connection a token
+        let allowed =
+            limiter.bucket.has_token(now) && self.user_limits(user_id).bucket.try_take(now);
+
+        if allowed {
+            limiter.bucket.try_take(now);
+            limiter.violations = 0;
+            return FloodCheck::Allowed;
+        }
+
+        self.record_violation(limiter)
+    }
+
+    /// Refuses the same message sent over and over to a group
+    pub fn check_duplicate(
+        &self,
+        limiter: &mut ConnectionLimiter,
+        user_id: Uuid,
+        group_id: Uuid,
+        content: &str,
+    ) -> Result<(), FloodCheck> {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.settings.duplicate_window);
+
+        let mut hasher = DefaultHasher::new();
+        (group_id, content.trim()).hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut user = self.user_limits(user_id);
+        let repeats = match &user.last_message {
+            Some(last) if last.hash == hash && now.saturating_duration_since(last.sent_at) < window => {
+                last.repeats + 1
+            }
+            _ => 1,
+        };
+        user.last_message = Some(RecentMessage {
+            hash,
+            sent_at: now,
+            repeats,
+        });
+        drop(user);
+
+        if repeats > self.settings.duplicate_limit {
+            return Err(self.record_violation(limiter));
+        }
+        Ok(())
+    }
+
+    /// Drops the state of users who have been quiet long enough for it not to matter
+    pub fn forget_idle_users(&self) {
+        let now = Instant::now();
+        let window = Duration::from_secs(self.settings.duplicate_window);
+        self.users.retain(|_, user| {
+            let message_expired = user
+                .last_message
+                .as_ref()
+                .map_or(true, |last| now.saturating_duration_since(last.sent_at) >= window);
+            !(user.bucket.is_full(now) && message_expired)
+        });
+    }
+
+    fn user_limits(&self, user_id: Uuid) -> dashmap::mapref::one::RefMut<'_, Uuid, UserLimits> {
+        self.users.entry(user_id).or_insert_with(|| UserLimits {
+            bucket: TokenBucket::new(self.settings.user_burst, self.settings.user_rate),
+            last_message: None,
+        })
+    }
+
+    fn record_violation(&self, limiter: &mut ConnectionLimiter) -> FloodCheck {
+        limiter.violations += 1;
+        if limiter.violations >= self.settings.max_violations {
+            return FloodCheck::Exceeded;
+        }
+        FloodCheck::Limited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ChatSettings {
+        ChatSettings {
+            connection_burst: 3,
+            connection_rate: 1.0,
+            user_burst: 5,
+            user_rate: 1.0,
+            duplicate_limit: 2,
+            duplicate_window: 30,
+            max_violations: 2,
+        }
+    }
+
+    #[test]
+    fn token_bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2, 1.0);
+        assert!(bucket.try_take(start));
+        assert!(bucket.try_take(start));
+        assert!(!bucket.try_take(start));
+        assert!(bucket.try_take(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn connection_is_closed_after_repeated_violations() {
+        let guard = FloodGuard::new(settings());
+        let mut limiter = guard.connection_limiter();
+        let user_id = Uuid::new_v4();
+
+        for _ in 0..3 {
+            assert_eq!(guard.check_action(&mut limiter, user_id), FloodCheck::Allowed);
+        }
+        assert_eq!(guard.check_action(&mut limiter, user_id), FloodCheck::Limited);
+        assert_eq!(guard.check_action(&mut limiter, user_id), FloodCheck::Exceeded);
+    }
+
+    #[test]
+    fn user_limit_is_shared_by_connections() {
+        let guard = FloodGuard::new(settings());
+        let user_id = Uuid::new_v4();
+        let mut first = guard.connection_limiter();
+        let mut second = guard.connection_limiter();
+
+        for _ in 0..3 {
+            assert_eq!(guard.check_action(&mut first, user_id), FloodCheck::Allowed);
+        }
+        for _ in 0..2 {
+            assert_eq!(guard.check_action(&mut second, user_id), FloodCheck::Allowed);
+        }
+        assert_eq!(guard.check_action(&mut second, user_id), FloodCheck::Limited);
+    }
+
+    #[test]
+    fn duplicate_messages_are_refused() {
+        let guard = FloodGuard::new(settings());
+        let mut limiter = guard.connection_limiter();
+        let (user_id, group_id) = (Uuid::new_v4(), Uuid::new_v4());
+
+        assert!(guard.check_duplicate(&mut limiter, user_id, group_id, "spam").is_ok());
+        assert!(guard.check_duplicate(&mut limiter, user_id, group_id, "spam").is_ok());
+        assert_eq!(
+            guard.check_duplicate(&mut limiter, user_id, group_id, "spam"),
+            Err(FloodCheck::Limited)
+        );
+        assert!(guard.check_duplicate(&mut limiter, user_id, group_id, "not spam").is_ok());
+    }
+}
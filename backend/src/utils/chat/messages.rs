@@ -1,8 +1,8 @@
 use anyhow::Context;
-use sqlx::{query_as, PgPool};
+use sqlx::{query_as, types::Json, PgPool};
 use uuid::Uuid;
 
-use super::models::{GroupUserMessage, GroupUserMessageModel};
+use super::models::{GroupUserMessage, GroupUserMessageModel, MessageKind, SystemEvent};
 
 use super::errors::ChatError;
 
@@ -15,10 +15,13 @@ pub async fn fetch_last_messages_in_range(
     let messages = query_as!(
         GroupUserMessageModel,
         r#"
-            select gu.nickname, m.content, m.sent_at from messages as m
-            join group_users gu on m.group_id = gu.group_id
+            select m.id, coalesce(gu.nickname, w.name, '') as "nickname!", m.content, m.sent_at, m.kind as "kind: MessageKind", m.poll_id,
+            m.system_event as "system_event: Json<SystemEvent>" from messages as m
+            left join group_users gu on m.group_id = gu.group_id and m.user_id = gu.user_id
+            left join group_webhooks w on m.webhook_id = w.id
             where m.group_id = $1
-            order by id desc
+            and (gu.nickname is not null or w.name is not null or m.system_event is not null)
+            order by m.id desc
             limit $2 offset $3
         "#,
         group_id,
@@ -32,11 +35,7 @@ pub async fn fetch_last_messages_in_range(
     let messages = messages
         .into_iter()
         .rev()
-        .map(|msg| GroupUserMessage {
-            content: msg.content,
-            nickname: msg.nickname,
-            sat: msg.sent_at.unix_timestamp(),
-        })
+        .map(GroupUserMessage::from)
         .rev()
         .collect();
 
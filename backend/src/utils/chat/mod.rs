@@ -1,15 +1,27 @@
+pub mod commands;
 pub mod errors;
+pub mod export;
+pub mod flood;
 pub mod messages;
 pub mod models;
+pub mod nicknames;
+pub mod polls;
+pub mod scheduled;
 pub mod socket;
 
 use anyhow::Context;
 use errors::*;
-use sqlx::{query, PgPool};
+use models::{GroupUserMessage, MessageKind, SystemEvent};
+use sqlx::{query, Executor, PgPool, Postgres};
 use uuid::Uuid;
 
-pub async fn get_group_nickname(
-    pool: &PgPool,
+use crate::utils::groups::filters::record_message_flags;
+use crate::utils::webhooks::{events::enqueue_group_event, models::GroupEvent};
+
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+pub async fn get_group_nickname<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
     user_id: &Uuid,
     group_id: &Uuid,
 ) -> Result<String, ChatError> {
@@ -21,11 +33,11 @@ pub async fn get_group_nickname(
         user_id,
         group_id
     )
-    .fetch_one(pool)
+    .fetch_optional(exe)
     .await
     .context("Cannot fetch user nickname from database")?;
 
-    Ok(res.nickname)
+    res.map(|member| member.nickname).ok_or(ChatError::MemberNotFound)
 }
 
 pub async fn get_user_email_by_id(pool: &PgPool, user_id: &Uuid) -> Result<String, ChatError> {
@@ -42,29 +54,104 @@ pub async fn get_user_email_by_id(pool: &PgPool, user_id: &Uuid) -> Result<Strin
     Ok(res.email)
 }
 
-pub async fn create_message(
+pub async fn create_message<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    content: &str,
+) -> Result<i32, ChatError> {
+    create_message_with_kind(exe, user_id, group_id, content, MessageKind::Text).await
+}
+
+/// Saves a message that passed the content filter together with the rules that flagged it
+/// and queues it for the event webhooks, returns its id
+pub async fn save_filtered_message(
     pool: &PgPool,
     user_id: &Uuid,
     group_id: &Uuid,
+    nickname: &str,
     content: &str,
-) -> Result<(), ChatError> {
+    kind: MessageKind,
+    flagged_by: &[i32],
+) -> Result<i32, ChatError> {
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    let message_id = create_message_with_kind(&mut transaction, user_id, group_id, content, kind).await?;
+    if !flagged_by.is_empty() {
+        record_message_flags(&mut transaction, group_id, message_id, flagged_by).await?;
+    }
+
+    let event = GroupEvent::MessageCreated {
+        user_id: Some(*user_id),
+        webhook_id: None,
+        nickname: nickname.to_string(),
+        content: content.to_string(),
+    };
+    enqueue_group_event(&mut transaction, group_id, event)
+        .await
+        .context("Failed to enqueue message created event")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(message_id)
+}
+
+pub async fn create_message_with_kind<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    content: &str,
+    kind: MessageKind,
+) -> Result<i32, ChatError> {
     if content.trim().is_empty() {
         return Err(ChatError::EmptyMessage);
     }
 
-    query!(
+    let res = query!(
         r#"
-            insert into messages (content, user_id, group_id)
-            values ($1, $2, $3)
+            insert into messages (content, user_id, group_id, kind)
+            values ($1, $2, $3, $4)
+            returning id
         "#,
         content,
         user_id,
-        group_id
+        group_id,
+        kind as MessageKind
     )
-    .execute(pool)
+    .fetch_one(exe)
     .await
     .context("Failed to add message")?;
-    Ok(())
+    Ok(res.id)
+}
+
+/// Records a membership or role event in the group history and returns it ready to broadcast
+pub async fn create_system_message<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    group_id: &Uuid,
+    event: SystemEvent,
+) -> Result<GroupUserMessage, ChatError> {
+    let payload = serde_json::to_value(&event).context("Failed to serialize system event")?;
+    let message = GroupUserMessage::system(event);
+
+    let res = query!(
+        r#"
+            insert into messages (content, group_id, kind, system_event)
+            values ($1, $2, $3, $4)
+            returning id
+        "#,
+        message.content,
+        group_id,
+        MessageKind::System as MessageKind,
+        payload
+    )
+    .fetch_one(exe)
+    .await
+    .context("Failed to add system message")?;
+
+    Ok(message.with_id(res.id))
 }
 
 #[cfg(test)]
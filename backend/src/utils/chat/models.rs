@@ -1,7 +1,15 @@
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
+use super::errors::ChatError;
+use crate::utils::roles::models::{Role, RoleRef};
+use crate::utils::roles::privileges::{
+    CanBan, CanDeleteMessages, CanEditSettings, CanExport, CanInvite, CanKick, CanManageInvitations, CanManageNicknames, CanManageRoles, CanMute,
+    CanPin, CanSendMessages, Privilege,
+};
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AddresedMessage {
     pub content: String,
@@ -11,30 +19,536 @@ pub struct AddresedMessage {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GroupUserMessageModel {
+    pub id: i32,
     pub nickname: String,
     pub content: String,
     pub sent_at: OffsetDateTime,
+    pub kind: MessageKind,
+    pub poll_id: Option<i32>,
+    pub system_event: Option<Json<SystemEvent>>,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[sqlx(type_name = "message_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum MessageKind {
+    #[default]
+    Text,
+    Poll,
+    PollResult,
+    Webhook,
+    /// Sent with `/me`, `content` describes what the author is doing
+    Action,
+    /// Membership or role event recorded by the server
+    System,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GroupUserMessage {
+    /// Database id, missing in messages that were not stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
     pub nickname: String,
     pub content: String,
     pub sat: i64,
+    #[serde(default)]
+    pub kind: MessageKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll_id: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<SystemEvent>,
 }
 
 impl GroupUserMessage {
     pub fn new(nickname: String, content: String) -> Self {
         Self {
+            id: None,
             nickname,
             content,
             sat: OffsetDateTime::now_utc().unix_timestamp(),
+            kind: MessageKind::Text,
+            poll_id: None,
+            event: None,
+        }
+    }
+
+    /// Message referencing a poll, `content` holds its plain text summary
+    pub fn poll(nickname: String, content: String, kind: MessageKind, poll_id: i32) -> Self {
+        Self {
+            id: None,
+            nickname,
+            content,
+            sat: OffsetDateTime::now_utc().unix_timestamp(),
+            kind,
+            poll_id: Some(poll_id),
+            event: None,
+        }
+    }
+
+    /// Attaches the id clients use to refer to the stored message
+    pub fn with_id(mut self, id: i32) -> Self {
+        self.id = Some(id);
+        self
+    }
+}
+
+impl GroupUserMessage {
+    /// Message posted through an incoming webhook under its bot name
+    pub fn webhook(name: String, content: String) -> Self {
+        Self {
+            id: None,
+            nickname: name,
+            content,
+            sat: OffsetDateTime::now_utc().unix_timestamp(),
+            kind: MessageKind::Webhook,
+            poll_id: None,
+            event: None,
+        }
+    }
+}
+
+impl GroupUserMessage {
+    pub fn action(nickname: String, action: String) -> Self {
+        Self {
+            id: None,
+            nickname,
+            content: action,
+            sat: OffsetDateTime::now_utc().unix_timestamp(),
+            kind: MessageKind::Action,
+            poll_id: None,
+            event: None,
+        }
+    }
+}
+
+impl GroupUserMessage {
+    /// Server message without an author, `content` holds the summary of the event
+    pub fn system(event: SystemEvent) -> Self {
+        Self {
+            id: None,
+            nickname: String::new(),
+            content: event.summary(),
+            sat: OffsetDateTime::now_utc().unix_timestamp(),
+            kind: MessageKind::System,
+            poll_id: None,
+            event: Some(event),
+        }
+    }
+}
+
+/// Structured part of a system message
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SystemEvent {
+    MemberJoined {
+        user_id: Uuid,
+        nickname: String,
+    },
+    MemberLeft {
+        user_id: Uuid,
+        nickname: String,
+    },
+    MemberKicked {
+        user_id: Uuid,
+        nickname: String,
+        kicked_by: Uuid,
+        moderator: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+    MemberBanned {
+        user_id: Uuid,
+        nickname: String,
+        banned_by: Uuid,
+        moderator: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+        /// Unix timestamp, `None` for a permanent ban
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<i64>,
+    },
+    RoleChanged {
+        user_id: Uuid,
+        nickname: String,
+        role: RoleRef,
+        /// Name of a custom role
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role_name: Option<String>,
+        changed_by: Uuid,
+        moderator: String,
+    },
+    PrivilegeChanged {
+        role: RoleRef,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        role_name: Option<String>,
+        privilege: Privilege,
+        changed_by: Uuid,
+        moderator: String,
+    },
+    PrivilegesChanged {
+        changes: Vec<RolePrivilege>,
+        changed_by: Uuid,
+        moderator: String,
+    },
+    RolesChanged {
+        members: Vec<MemberRole>,
+        changed_by: Uuid,
+        moderator: String,
+    },
+    OwnershipTransferred {
+        user_id: Uuid,
+        nickname: String,
+        previous_owner_id: Uuid,
+        previous_owner: String,
+    },
+}
+
+/// Privilege of a role set by a batch change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RolePrivilege {
+    pub role: RoleRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_name: Option<String>,
+    pub privilege: Privilege,
+}
+
+/// Role of a member set by a batch change
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MemberRole {
+    pub user_id: Uuid,
+    pub nickname: String,
+    pub role: RoleRef,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role_name: Option<String>,
+}
+
+impl SystemEvent {
+    /// Plain text description shown by clients and exports
+    pub fn summary(&self) -> String {
+        match self {
+            SystemEvent::MemberJoined { nickname, .. } => format!("{nickname} joined the group"),
+            SystemEvent::MemberLeft { nickname, .. } => format!("{nickname} left the group"),
+            SystemEvent::MemberKicked {
+                nickname,
+                moderator,
+                reason: None,
+                ..
+            } => format!("{moderator} removed {nickname} from the group"),
+            SystemEvent::MemberKicked {
+                nickname,
+                moderator,
+                reason: Some(reason),
+                ..
+            } => format!("{moderator} removed {nickname} from the group: {reason}"),
+            SystemEvent::MemberBanned {
+                nickname,
+                moderator,
+                reason: None,
+                ..
+            } => format!("{moderator} banned {nickname} from the group"),
+            SystemEvent::MemberBanned {
+                nickname,
+                moderator,
+                reason: Some(reason),
+                ..
+            } => format!("{moderator} banned {nickname} from the group: {reason}"),
+            SystemEvent::RoleChanged {
+                nickname,
+                role,
+                role_name,
+                moderator,
+                ..
+            } => format!("{moderator} changed the role of {nickname} to {}", role_label(role, role_name)),
+            SystemEvent::PrivilegeChanged {
+                role,
+                role_name,
+                privilege,
+                moderator,
+                ..
+            } => format!(
+                "{moderator} changed {} privileges: {}",
+                role_label(role, role_name),
+                privilege_description(privilege)
+            ),
+            SystemEvent::PrivilegesChanged { changes, moderator, .. } => format!(
+                "{moderator} changed privileges: {}",
+                changes
+                    .iter()
+                    .map(|change| format!("{} {}", role_label(&change.role, &change.role_name), privilege_description(&change.privilege)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SystemEvent::RolesChanged { members, moderator, .. } => format!(
+                "{moderator} changed roles: {}",
+                members
+                    .iter()
+                    .map(|member| format!("{} to {}", member.nickname, role_label(&member.role, &member.role_name)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SystemEvent::OwnershipTransferred {
+                nickname,
+                previous_owner,
+                ..
+            } => format!("{nickname} took over the group from {previous_owner}"),
+        }
+    }
+}
+
+fn role_label(role: &RoleRef, name: &Option<String>) -> String {
+    match (role, name) {
+        (_, Some(name)) => name.clone(),
+        (RoleRef::Kind(Role::Member), None) => "member".into(),
+        (RoleRef::Kind(Role::Admin), None) => "admin".into(),
+        (RoleRef::Kind(Role::Owner), None) => "owner".into(),
+        (RoleRef::Id(_), None) => "a custom role".into(),
+    }
+}
+
+fn privilege_description(privilege: &Privilege) -> String {
+    match privilege {
+        Privilege::CanInvite(CanInvite::Yes) => "can invite".into(),
+        Privilege::CanInvite(CanInvite::No) => "cannot invite".into(),
+        Privilege::CanSendMessages(CanSendMessages::Yes(0)) => "can send messages".into(),
+        Privilege::CanSendMessages(CanSendMessages::Yes(x)) => {
+            format!("can send messages every {x} seconds")
+        }
+        Privilege::CanSendMessages(CanSendMessages::No) => "cannot send messages".into(),
+        Privilege::CanExport(CanExport::Yes) => "can export history".into(),
+        Privilege::CanExport(CanExport::No) => "cannot export history".into(),
+        Privilege::CanKick(CanKick::Yes) => "can kick members".into(),
+        Privilege::CanKick(CanKick::No) => "cannot kick members".into(),
+        Privilege::CanBan(CanBan::Yes) => "can ban users".into(),
+        Privilege::CanBan(CanBan::No) => "cannot ban users".into(),
+        Privilege::CanMute(CanMute::Yes) => "can mute members".into(),
+        Privilege::CanMute(CanMute::No) => "cannot mute members".into(),
+        Privilege::CanDeleteMessages(CanDeleteMessages::Yes) => "can delete messages of others".into(),
+        Privilege::CanDeleteMessages(CanDeleteMessages::No) => "cannot delete messages of others".into(),
+        Privilege::CanPin(CanPin::Yes) => "can pin messages".into(),
+        Privilege::CanPin(CanPin::No) => "cannot pin messages".into(),
+        Privilege::CanManageInvitations(CanManageInvitations::Yes) => "can manage invitations".into(),
+        Privilege::CanManageInvitations(CanManageInvitations::No) => "cannot manage invitations".into(),
+        Privilege::CanEditSettings(CanEditSettings::Yes) => "can edit group settings".into(),
+        Privilege::CanEditSettings(CanEditSettings::No) => "cannot edit group settings".into(),
+        Privilege::CanManageRoles(CanManageRoles::Yes) => "can manage roles".into(),
+        Privilege::CanManageRoles(CanManageRoles::No) => "cannot manage roles".into(),
+        Privilege::CanManageNicknames(CanManageNicknames::Yes) => "can reset nicknames".into(),
+        Privilege::CanManageNicknames(CanManageNicknames::No) => "cannot reset nicknames".into(),
+    }
+}
+
+impl From<GroupUserMessageModel> for GroupUserMessage {
+    fn from(val: GroupUserMessageModel) -> Self {
+        Self {
+            id: Some(val.id),
+            nickname: val.nickname,
+            content: val.content,
+            sat: val.sent_at.unix_timestamp(),
+            kind: val.kind,
+            poll_id: val.poll_id,
+            event: val.system_event.map(|Json(event)| event),
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct KickMessage {
+    /// Nickname of the moderator
     pub from: String,
-    pub reason: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NicknameChange {
+    pub nickname: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MemberNickname {
+    pub user_id: Uuid,
+    pub nickname: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct NicknamePolicy {
+    pub unique: bool,
+}
+
+/// Slash command suggested to the client for autocompletion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommandInfo {
+    pub name: String,
+    pub usage: String,
+    pub description: String,
+}
+
+/// Failed client action reported back to its sender
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ActionError {
+    pub error: String,
+    pub error_info: String,
+}
+
+impl From<&ChatError> for ActionError {
+    fn from(val: &ChatError) -> Self {
+        Self {
+            error: val.code().into(),
+            error_info: val.info(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ExportedMessageModel {
+    pub id: i32,
+    pub author: String,
+    pub content: String,
+    pub sent_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExportedMessage {
+    pub id: i32,
+    pub author: String,
+    pub sent_at: String,
+    pub content: String,
+}
+
+#[derive(Debug)]
+pub struct ScheduledMessageModel {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub group_id: Uuid,
+    pub content: String,
+    pub send_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScheduledMessage {
+    pub id: i32,
+    pub group_id: Uuid,
+    pub content: String,
+    pub send_at: i64,
+}
+
+impl From<ScheduledMessageModel> for ScheduledMessage {
+    fn from(val: ScheduledMessageModel) -> Self {
+        Self {
+            id: val.id,
+            group_id: val.group_id,
+            content: val.content,
+            send_at: val.send_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewPoll {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub multiple_choice: bool,
+    #[serde(default)]
+    pub anonymous: bool,
+    pub closes_at: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct PollModel {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub group_id: Uuid,
+    pub question: String,
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<OffsetDateTime>,
+    pub closed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug)]
+pub struct PollOptionModel {
+    pub id: i32,
+    pub content: String,
+    pub votes: i64,
+    pub voters: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PollOption {
+    pub id: i32,
+    pub content: String,
+    pub votes: i64,
+    /// Nicknames of the voters, omitted for anonymous polls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voters: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Poll {
+    pub id: i32,
+    pub question: String,
+    pub multiple_choice: bool,
+    pub anonymous: bool,
+    pub closes_at: Option<i64>,
+    pub closed: bool,
+    pub options: Vec<PollOption>,
+}
+
+impl Poll {
+    pub fn new(poll: PollModel, options: Vec<PollOptionModel>) -> Self {
+        Self {
+            id: poll.id,
+            question: poll.question,
+            multiple_choice: poll.multiple_choice,
+            anonymous: poll.anonymous,
+            closes_at: poll.closes_at.map(|t| t.unix_timestamp()),
+            closed: poll.closed_at.is_some(),
+            options: options
+                .into_iter()
+                .map(|option| PollOption {
+                    id: option.id,
+                    content: option.content,
+                    votes: option.votes,
+                    voters: (!poll.anonymous).then_some(option.voters),
+                })
+                .collect(),
+        }
+    }
+
+    /// Plain text summary posted as the final result of the poll
+    pub fn result_summary(&self) -> String {
+        let results = self
+            .options
+            .iter()
+            .map(|option| format!("{} ({})", option.content, option.votes))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Poll \"{}\" closed: {}", self.question, results)
+    }
 }
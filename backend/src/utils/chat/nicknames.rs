@@ -0,0 +1,290 @@
+use anyhow::Context;
+use sqlx::{query, PgConnection, PgPool};
+use tracing::debug;
+use uuid::Uuid;
+
+use super::errors::ChatError;
+use crate::utils::groups::audit::{record_audit_entry, AuditEntry};
+use crate::utils::groups::models::AuditAction;
+use crate::utils::roles::models::Gate;
+use crate::utils::roles::privileges::{CanEditSettings, CanManageNicknames, Privilege};
+use crate::utils::roles::{member_group_role, member_role};
+
+pub const MAX_NICKNAME_LENGTH: usize = 32;
+
+const MANAGE_NICKNAMES: Privilege = Privilege::CanManageNicknames(CanManageNicknames::Yes);
+
+/// Trims the nickname and checks its length and characters.
+///
+/// Whitespace is not allowed so that every nickname can be mentioned with `@nickname`.
+pub fn validate_nickname(nickname: &str) -> Result<&str, ChatError> {
+    let nickname = nickname.trim();
+    let is_allowed = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+
+    if nickname.is_empty()
+        || nickname.chars().count() > MAX_NICKNAME_LENGTH
+        || !nickname.chars().all(is_allowed)
+    {
+        return Err(ChatError::InvalidNickname);
+    }
+
+    Ok(nickname)
+}
+
+/// Changes the user's own nickname in the group and returns it
+pub async fn set_group_nickname(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    nickname: &str,
+) -> Result<String, ChatError> {
+    let nickname = validate_nickname(nickname)?;
+
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    // A different suggestion means the nickname is taken in a group requiring unique ones
+    if available_nickname(&mut transaction, group_id, user_id, nickname)
+        .await
+        .context("Failed to check nickname availability")?
+        != nickname
+    {
+        return Err(ChatError::NicknameTaken);
+    }
+
+    let res = query!(
+        r#"
+            update group_users set nickname = $3
+            where user_id = $1 and group_id = $2
+        "#,
+        user_id,
+        group_id,
+        nickname
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update user nickname")?;
+
+    if res.rows_affected() == 0 {
+        return Err(ChatError::MemberNotFound);
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit nickname change")?;
+
+    Ok(nickname.to_string())
+}
+
+/// Restores the member's username as their nickname and returns it.
+///
+/// The moderator's role has to grant managing nicknames and pass the gate against the role of the member.
+pub async fn reset_group_nickname(
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    moderator_id: &Uuid,
+    group_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<String, ChatError> {
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    let moderator = match member_group_role(&mut transaction, moderator_id, group_id).await? {
+        Some(role) if role.privileges.satisfies(MANAGE_NICKNAMES) => role,
+        _ => return Err(ChatError::InsufficientPrivileges),
+    };
+    let Some(target) = member_role(&mut transaction, user_id, group_id).await? else {
+        return Err(ChatError::MemberNotFound);
+    };
+
+    if !gate.verify(moderator.place(), target, (*moderator_id, *user_id)) {
+        return Err(ChatError::InsufficientPrivileges);
+    }
+
+    let username = query!(
+        r#"
+            select username from users
+            where id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to fetch username")?
+    .username;
+
+    let nickname = available_nickname(&mut transaction, group_id, user_id, &username)
+        .await
+        .context("Failed to check nickname availability")?;
+
+    query!(
+        r#"
+            update group_users set nickname = $3
+            where user_id = $1 and group_id = $2
+        "#,
+        user_id,
+        group_id,
+        nickname
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to reset user nickname")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit nickname reset")?;
+
+    debug!("User {moderator_id} reset the nickname of user {user_id} in group {group_id}");
+
+    Ok(nickname)
+}
+
+/// Requires nicknames to be unique within the group, changing it is up to members who can edit group settings
+pub async fn set_unique_nicknames(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    enabled: bool,
+) -> Result<(), ChatError> {
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    match member_group_role(&mut transaction, user_id, group_id).await? {
+        Some(role) if role.privileges.satisfies(Privilege::CanEditSettings(CanEditSettings::Yes)) => {}
+        _ => return Err(ChatError::InsufficientPrivileges),
+    }
+
+    // Lock the group so that no nickname changes in the meantime
+    let group = query!(
+        r#"
+            select unique_nicknames from groups
+            where id = $1
+            for update
+        "#,
+        group_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to lock group")?;
+
+    if enabled {
+        let duplicates = query!(
+            r#"
+                select count(*) as "count!" from (
+                    select lower(nickname) from group_users
+                    where group_id = $1
+                    group by lower(nickname)
+                    having count(*) > 1
+                ) as duplicates
+            "#,
+            group_id
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .context("Failed to count duplicated nicknames")?;
+
+        if duplicates.count > 0 {
+            return Err(ChatError::NicknamesNotUnique);
+        }
+    }
+
+    query!(
+        r#"
+            update groups set unique_nicknames = $2
+            where id = $1
+        "#,
+        group_id,
+        enabled
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update nickname policy")?;
+
+    let entry = AuditEntry::new(AuditAction::NicknamePolicyChanged)
+        .target("unique_nicknames")
+        .before(group.unique_nicknames)
+        .after(enabled);
+    record_audit_entry(&mut transaction, group_id, user_id, entry)
+        .await
+        .context("Failed to record nickname policy change")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit nickname policy")?;
+
+    Ok(())
+}
+
+/// Returns `nickname` or, if the group requires unique nicknames and it is taken,
+/// the first free `nickname_<n>` variant.
+///
+/// Locks the group row until the end of the transaction so that concurrent changes can't collide.
+pub async fn available_nickname(
+    conn: &mut PgConnection,
+    group_id: &Uuid,
+    user_id: &Uuid,
+    nickname: &str,
+) -> Result<String, sqlx::Error> {
+    let group = query!(
+        r#"
+            select unique_nicknames from groups
+            where id = $1
+            for update
+        "#,
+        group_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    if !group.unique_nicknames {
+        return Ok(nickname.to_string());
+    }
+
+    let taken = query!(
+        r#"
+            select lower(nickname) as "nickname!" from group_users
+            where group_id = $1 and user_id <> $2
+        "#,
+        group_id,
+        user_id
+    )
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|member| member.nickname)
+    .collect::<Vec<_>>();
+
+    let mut candidate = nickname.to_string();
+    let mut suffix = 2;
+    while taken.contains(&candidate.to_lowercase()) {
+        candidate = format!("{nickname}_{suffix}");
+        suffix += 1;
+    }
+
+    Ok(candidate)
+}
+
+/// Finds the group member mentioned by their nickname
+pub async fn find_group_member_by_nickname(
+    pool: &PgPool,
+    group_id: &Uuid,
+    nickname: &str,
+) -> Result<Uuid, ChatError> {
+    let members = query!(
+        r#"
+            select user_id from group_users
+            where group_id = $1 and nickname = $2
+        "#,
+        group_id,
+        nickname
+    )
+    .fetch_all(pool)
+    .await
+    .context("Cannot fetch group members by nickname")?;
+
+    match members.as_slice() {
+        [member] => Ok(member.user_id),
+        [] => Err(ChatError::MemberNotFound),
+        _ => Err(ChatError::AmbiguousMember),
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::{query, query_as, PgConnection, PgPool};
+use time::OffsetDateTime;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::errors::ChatError;
+use super::models::{
+    GroupUserMessage, MessageKind, NewPoll, Poll, PollModel, PollOptionModel,
+};
+use super::scheduled::MAX_SCHEDULE_AHEAD;
+use super::socket::{ChatState, ServerAction};
+use crate::utils::groups::filters::record_message_flags;
+use crate::utils::roles::privileges::{CanDeleteMessages, Privilege, Privileges};
+
+pub const MAX_POLL_QUESTION_LENGTH: usize = 300;
+pub const MAX_POLL_OPTION_LENGTH: usize = 100;
+pub const MIN_POLL_OPTIONS: usize = 2;
+pub const MAX_POLL_OPTIONS: usize = 10;
+/// Time between two checks for polls past their deadline
+pub const POLL_CLOSER_INTERVAL: Duration = Duration::from_secs(10);
+
+fn validate_poll(poll: &NewPoll) -> Result<(), ChatError> {
+    let question = poll.question.trim();
+    if question.is_empty() || question.len() > MAX_POLL_QUESTION_LENGTH {
+        return Err(ChatError::InvalidPoll);
+    }
+
+    if poll.options.len() < MIN_POLL_OPTIONS || poll.options.len() > MAX_POLL_OPTIONS {
+        return Err(ChatError::InvalidPoll);
+    }
+
+    let mut unique = HashSet::new();
+    for option in poll.options.iter() {
+        let option = option.trim();
+        if option.is_empty() || option.len() > MAX_POLL_OPTION_LENGTH || !unique.insert(option) {
+            return Err(ChatError::InvalidPoll);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates the poll together with the group message announcing it
+pub async fn create_poll(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    poll: NewPoll,
+    flagged_by: &[i32],
+) -> Result<Poll, ChatError> {
+    validate_poll(&poll)?;
+
+    let closes_at = match poll.closes_at {
+        Some(closes_at) => {
+            let closes_at = OffsetDateTime::from_unix_timestamp(closes_at)
+                .map_err(|_| ChatError::InvalidPoll)?;
+            let now = OffsetDateTime::now_utc();
+            if closes_at <= now || closes_at > now + MAX_SCHEDULE_AHEAD {
+                return Err(ChatError::InvalidPoll);
+            }
+            Some(closes_at)
+        }
+        None => None,
+    };
+
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    let res = query!(
+        r#"
+            insert into polls (user_id, group_id, question, multiple_choice, anonymous, closes_at)
+            values ($1, $2, $3, $4, $5, $6)
+            returning id
+        "#,
+        user_id,
+        group_id,
+        poll.question.trim(),
+        poll.multiple_choice,
+        poll.anonymous,
+        closes_at
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to create poll")?;
+
+    for (position, option) in poll.options.iter().enumerate() {
+        query!(
+            r#"
+                insert into poll_options (poll_id, position, content)
+                values ($1, $2, $3)
+            "#,
+            res.id,
+            position as i32,
+            option.trim()
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to add poll option")?;
+    }
+
+    let message_id = create_poll_message(
+        &mut transaction,
+        user_id,
+        group_id,
+        poll.question.trim(),
+        MessageKind::Poll,
+        res.id,
+    )
+    .await?;
+    if !flagged_by.is_empty() {
+        record_message_flags(&mut transaction, group_id, message_id, flagged_by).await?;
+    }
+
+    let created = fetch_poll(&mut transaction, group_id, res.id).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit poll creation")?;
+
+    Ok(created)
+}
+
+pub async fn fetch_poll(
+    conn: &mut PgConnection,
+    group_id: &Uuid,
+    poll_id: i32,
+) -> Result<Poll, ChatError> {
+    let poll = query_as!(
+        PollModel,
+        r#"
+            select id, user_id, group_id, question, multiple_choice, anonymous, closes_at, closed_at from polls
+            where id = $1 and group_id = $2
+        "#,
+        poll_id,
+        group_id
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .context("Failed to fetch poll")?
+    .ok_or(ChatError::PollNotFound)?;
+
+    let options = query_as!(
+        PollOptionModel,
+        r#"
+            select o.id, o.content, count(v.user_id) as "votes!",
+            coalesce(array_agg(gu.nickname order by v.voted_at) filter (where gu.nickname is not null), '{}') as "voters!"
+            from poll_options o
+            join polls p on p.id = o.poll_id
+            left join poll_votes v on v.option_id = o.id
+            left join group_users gu on gu.user_id = v.user_id and gu.group_id = p.group_id
+            where o.poll_id = $1
+            group by o.id
+            order by o.position
+        "#,
+        poll_id
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .context("Failed to fetch poll options")?;
+
+    Ok(Poll::new(poll, options))
+}
+
+pub async fn fetch_group_poll(pool: &PgPool, group_id: &Uuid, poll_id: i32) -> Result<Poll, ChatError> {
+    let mut conn = pool.acquire().await.context("Failed to acquire connection")?;
+    fetch_poll(&mut conn, group_id, poll_id).await
+}
+
+/// Replaces user's previous votes in the poll with the selected options.
+///
+/// An empty selection withdraws the vote.
+pub async fn vote_in_poll(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    poll_id: i32,
+    options: Vec<i32>,
+) -> Result<Poll, ChatError> {
+    let options: Vec<i32> = options
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    // Lock the poll so it cannot be closed in the middle of voting
+    let poll = query!(
+        r#"
+            select multiple_choice, closed_at, closes_at from polls
+            where id = $1 and group_id = $2
+            for update
+        "#,
+        poll_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to fetch poll")?
+    .ok_or(ChatError::PollNotFound)?;
+
+    let is_expired = poll
+        .closes_at
+        .map_or(false, |closes_at| closes_at <= OffsetDateTime::now_utc());
+    if poll.closed_at.is_some() || is_expired {
+        return Err(ChatError::PollClosed);
+    }
+
+    if !poll.multiple_choice && options.len() > 1 {
+        return Err(ChatError::InvalidVote);
+    }
+
+    let matching = query!(
+        r#"
+            select count(*) as "count!" from poll_options
+            where poll_id = $1 and id = any($2)
+        "#,
+        poll_id,
+        &options
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to check poll options")?;
+
+    if matching.count != options.len() as i64 {
+        return Err(ChatError::InvalidVote);
+    }
+
+    query!(
+        r#"
+            delete from poll_votes
+            where user_id = $1
+            and option_id in (select id from poll_options where poll_id = $2)
+        "#,
+        user_id,
+        poll_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to withdraw previous votes")?;
+
+    query!(
+        r#"
+            insert into poll_votes (option_id, user_id)
+            select unnest($1::int[]), $2
+        "#,
+        &options,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to save votes")?;
+
+    let updated = fetch_poll(&mut transaction, group_id, poll_id).await?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit votes")?;
+
+    Ok(updated)
+}
+
+/// Closes the poll on request of a group member - only the author and members who can delete messages can do it
+pub async fn close_poll_as(
+    pool: &PgPool,
+    user_id: &Uuid,
+    privileges: &Privileges,
+    group_id: &Uuid,
+    poll_id: i32,
+) -> Result<(Poll, GroupUserMessage), ChatError> {
+    let author = query!(
+        r#"
+            select user_id from polls
+            where id = $1 and group_id = $2
+        "#,
+        poll_id,
+        group_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch poll author")?
+    .ok_or(ChatError::PollNotFound)?;
+
+    if author.user_id != *user_id && !privileges.satisfies(Privilege::CanDeleteMessages(CanDeleteMessages::Yes)) {
+        return Err(ChatError::InsufficientPrivileges);
+    }
+
+    close_poll(pool, group_id, poll_id).await
+}
+
+/// Closes the poll and posts its final result in the group
+pub async fn close_poll(
+    pool: &PgPool,
+    group_id: &Uuid,
+    poll_id: i32,
+) -> Result<(Poll, GroupUserMessage), ChatError> {
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    let closed = query!(
+        r#"
+            update polls set closed_at = now()
+            where id = $1 and group_id = $2 and closed_at is null
+            returning user_id
+        "#,
+        poll_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to close poll")?
+    .ok_or(ChatError::PollClosed)?;
+
+    let poll = fetch_poll(&mut transaction, group_id, poll_id).await?;
+    let summary = poll.result_summary();
+
+    create_poll_message(
+        &mut transaction,
+        &closed.user_id,
+        group_id,
+        &summary,
+        MessageKind::PollResult,
+        poll_id,
+    )
+    .await?;
+
+    // The author might have already left the group
+    let author = query!(
+        r#"
+            select coalesce(gu.nickname, u.username) as "name!" from users u
+            left join group_users gu on gu.user_id = u.id and gu.group_id = $2
+            where u.id = $1
+        "#,
+        closed.user_id,
+        group_id
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to fetch poll author name")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit poll closing")?;
+
+    let message = GroupUserMessage::poll(author.name, summary, MessageKind::PollResult, poll_id);
+    Ok((poll, message))
+}
+
+async fn create_poll_message(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    content: &str,
+    kind: MessageKind,
+    poll_id: i32,
+) -> Result<i32, ChatError> {
+    let res = query!(
+        r#"
+            insert into messages (content, user_id, group_id, kind, poll_id)
+            values ($1, $2, $3, $4, $5)
+            returning id
+        "#,
+        content,
+        user_id,
+        group_id,
+        kind as MessageKind,
+        poll_id
+    )
+    .fetch_one(conn)
+    .await
+    .context("Failed to add poll message")?;
+    Ok(res.id)
+}
+
+/// Closes every poll past its deadline and broadcasts the results to the loaded groups
+pub async fn close_expired_polls(pool: &PgPool, state: &ChatState) -> Result<u64, ChatError> {
+    let expired = query!(
+        r#"
+            select id, group_id from polls
+            where closed_at is null and closes_at <= now()
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch expired polls")?;
+
+    let mut closed = 0;
+    for poll in expired {
+        let (result, message) = match close_poll(pool, &poll.group_id, poll.id).await {
+            Ok(res) => res,
+            Err(ChatError::PollClosed) => {
+                debug!("Poll {} was closed in the meantime", poll.id);
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        closed += 1;
+
+        if let Some(group_controller) = state.groups.get_loaded(&poll.group_id) {
+            group_controller.channel.sender.send(ServerAction::PollUpdate(result));
+            group_controller.channel.sender.send(ServerAction::Message(message));
+        }
+    }
+
+    Ok(closed)
+}
+
+pub async fn run_poll_closer(pool: PgPool, state: Arc<ChatState>) {
+    let mut interval = tokio::time::interval(POLL_CLOSER_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match close_expired_polls(&pool, &state).await {
+            Ok(0) => (),
+            Ok(n) => info!("Poll closer closed {n} expired polls"),
+            Err(e) => error!("Poll closer failed to close expired polls: {e:?}"),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::{query, query_as, PgPool};
+use time::OffsetDateTime;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::errors::ChatError;
+use super::models::{GroupUserMessage, ScheduledMessage, ScheduledMessageModel};
+use super::socket::{ChatState, ServerAction};
+use super::{create_message, get_group_nickname};
+use crate::utils::groups::check_if_group_member;
+use crate::utils::groups::filters::{load_group_filters, record_message_flags, CompiledFilters, FilterVerdict};
+use crate::utils::groups::mutes::get_active_mute;
+use crate::utils::roles::get_user_privileges;
+use crate::utils::roles::privileges::{CanSendMessages, Privilege};
+use crate::utils::webhooks::{events::enqueue_group_event, models::GroupEvent};
+
+/// Furthest point in the future a message can be scheduled for (1 year)
+pub const MAX_SCHEDULE_AHEAD: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+/// Amount of pending messages a user can have in a single group
+pub const MAX_PENDING_PER_GROUP: i64 = 25;
+/// Amount of due messages delivered in a single scheduler pass
+pub const SCHEDULER_BATCH_SIZE: i64 = 100;
+/// Time between two scheduler passes
+pub const SCHEDULER_INTERVAL: Duration = Duration::from_secs(5);
+
+pub async fn schedule_message(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    content: &str,
+    send_at: i64,
+) -> Result<ScheduledMessage, ChatError> {
+    if content.trim().is_empty() {
+        return Err(ChatError::EmptyMessage);
+    }
+
+    let send_at =
+        OffsetDateTime::from_unix_timestamp(send_at).map_err(|_| ChatError::InvalidScheduleTime)?;
+    let now = OffsetDateTime::now_utc();
+    if send_at <= now || send_at > now + MAX_SCHEDULE_AHEAD {
+        return Err(ChatError::InvalidScheduleTime);
+    }
+
+    let pending = query!(
+        r#"
+            select count(*) as "count!" from scheduled_messages
+            where user_id = $1 and group_id = $2
+        "#,
+        user_id,
+        group_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count pending scheduled messages")?;
+
+    if pending.count >= MAX_PENDING_PER_GROUP {
+        return Err(ChatError::TooManyScheduledMessages);
+    }
+
+    let scheduled = query_as!(
+        ScheduledMessageModel,
+        r#"
+            insert into scheduled_messages (user_id, group_id, content, send_at)
+            values ($1, $2, $3, $4)
+            returning id, user_id, group_id, content, send_at
+        "#,
+        user_id,
+        group_id,
+        content,
+        send_at
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to schedule message")?;
+
+    Ok(scheduled.into())
+}
+
+pub async fn fetch_scheduled_messages(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<ScheduledMessage>, ChatError> {
+    let scheduled = query_as!(
+        ScheduledMessageModel,
+        r#"
+            select id, user_id, group_id, content, send_at from scheduled_messages
+            where user_id = $1 and group_id = $2
+            order by send_at
+        "#,
+        user_id,
+        group_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch scheduled messages")?;
+
+    Ok(scheduled.into_iter().map(ScheduledMessage::from).collect())
+}
+
+pub async fn cancel_scheduled_message(
+    pool: &PgPool,
+    user_id: &Uuid,
+    scheduled_id: i32,
+) -> Result<(), ChatError> {
+    let res = query!(
+        r#"
+            delete from scheduled_messages
+            where id = $1 and user_id = $2
+        "#,
+        scheduled_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to cancel scheduled message")?;
+
+    if res.rows_affected() == 0 {
+        return Err(ChatError::ScheduledMessageNotFound);
+    }
+
+    Ok(())
+}
+
+/// Sends every due message the same way as a live one and returns the amount of delivered messages.
+///
+/// Messages whose author left the group or can no longer send messages are dropped, as well as the ones
+/// a content filter blocks. The ones of a muted author wait until the mute ends.
+pub async fn deliver_due_messages(
+    pool: &PgPool,
+    state: &ChatState,
+    batch_size: i64,
+) -> Result<u64, ChatError> {
+    let mut transaction = pool.begin().await.context("Failed to begin transaction")?;
+
+    let due = query_as!(
+        ScheduledMessageModel,
+        r#"
+            select id, user_id, group_id, content, send_at from scheduled_messages
+            where send_at <= now()
+            order by send_at
+            limit $1
+            for update skip locked
+        "#,
+        batch_size
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to fetch due scheduled messages")?;
+
+    let mut delivered = Vec::new();
+    for msg in due {
+        if let Some(muted_until) = get_active_mute(pool, &msg.user_id, &msg.group_id).await? {
+            query!(
+                r#"
+                    update scheduled_messages set send_at = $2
+                    where id = $1
+                "#,
+                msg.id,
+                muted_until
+            )
+            .execute(&mut transaction)
+            .await
+            .context("Failed to defer scheduled message")?;
+
+            debug!(
+                "Deferred scheduled message {} - user {} is muted in group {} until {}",
+                msg.id, msg.user_id, msg.group_id, muted_until
+            );
+            continue;
+        }
+
+        query!(
+            r#"
+                delete from scheduled_messages
+                where id = $1
+            "#,
+            msg.id
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to remove scheduled message")?;
+
+        if !can_still_send(pool, &msg.user_id, &msg.group_id).await? {
+            debug!(
+                "Dropped scheduled message {} - user {} cannot send messages in group {}",
+                msg.id, msg.user_id, msg.group_id
+            );
+            continue;
+        }
+
+        // the rules may have changed since the message was scheduled
+        let filters = group_filters(pool, state, &msg.group_id).await?;
+        let (content, flagged_by) = match filters.apply(&msg.content) {
+            FilterVerdict::Blocked { filter_id } => {
+                debug!("Dropped scheduled message {} - blocked by content filter {filter_id}", msg.id);
+                continue;
+            }
+            FilterVerdict::Passed { content, flagged_by } => (content, flagged_by),
+        };
+
+        let nickname = get_group_nickname(pool, &msg.user_id, &msg.group_id).await?;
+        let message_id = create_message(&mut transaction, &msg.user_id, &msg.group_id, &content).await?;
+        if !flagged_by.is_empty() {
+            record_message_flags(&mut transaction, &msg.group_id, message_id, &flagged_by).await?;
+        }
+
+        let event = GroupEvent::MessageCreated {
+            user_id: Some(msg.user_id),
+            webhook_id: None,
+            nickname: nickname.clone(),
+            content: content.clone(),
+        };
+        enqueue_group_event(&mut transaction, &msg.group_id, event)
+            .await
+            .context("Failed to enqueue message created event")?;
+
+        delivered.push((msg.group_id, nickname, content, message_id));
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit scheduled messages delivery")?;
+
+    // Broadcast only once the messages are persisted
+    for (group_id, nickname, content, message_id) in delivered.iter() {
+        let Some(group_controller) = state.groups.get_loaded(group_id) else {
+            continue;
+        };
+        let action = ServerAction::Message(GroupUserMessage::new(nickname.clone(), content.clone()).with_id(*message_id));
+        group_controller.channel.sender.send(action);
+    }
+
+    Ok(delivered.len() as u64)
+}
+
+/// Content filters of the group, cached by its controller when the group is loaded
+async fn group_filters(pool: &PgPool, state: &ChatState, group_id: &Uuid) -> Result<Arc<CompiledFilters>, ChatError> {
+    let load = || load_group_filters(pool, group_id);
+    let filters = match state.groups.get_loaded(group_id) {
+        Some(group_controller) => group_controller.content_filters(load).await?,
+        None => Arc::new(load().await?),
+    };
+    Ok(filters)
+}
+
+async fn can_still_send(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Result<bool, ChatError> {
+    let is_member = check_if_group_member(pool, user_id, group_id)
+        .await
+        .context("Failed to check group membership")?;
+    if !is_member {
+        return Ok(false);
+    }
+
+    let privileges = get_user_privileges(pool, user_id, group_id)
+        .await
+        .context("Failed to fetch user privileges")?;
+
+    // Any slow mode value is enough - the schedule itself decides the timing
+    Ok(privileges.satisfies(Privilege::CanSendMessages(CanSendMessages::Yes(usize::MAX))))
+}
+
+pub async fn run_message_scheduler(pool: PgPool, state: Arc<ChatState>) {
+    let mut interval = tokio::time::interval(SCHEDULER_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match deliver_due_messages(&pool, &state, SCHEDULER_BATCH_SIZE).await {
+            Ok(0) => (),
+            Ok(n) => info!("Message scheduler delivered {n} scheduled messages"),
+            Err(e) => error!("Message scheduler failed to deliver scheduled messages: {e:?}"),
+        }
+    }
+}
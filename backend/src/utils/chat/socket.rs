@@ -1,32 +1,49 @@
+use crate::configuration::ChatSettings;
+use crate::utils::groups::filters::CompiledFilters;
+use crate::utils::groups::models::{MessageReport, ReportResolution};
 use crate::utils::roles::errors::RoleError;
-use crate::utils::roles::models::{Role, SocketGroupRolePrivileges, PrivilegeChangeData, UserRoleChangeData};
+use crate::utils::roles::models::{
+    GroupPrivilegesChangeData, GroupRole, GroupRolePrivileges, GroupRoles, PrivilegeChangeData, Role, RoleRef,
+    SocketGroupRolePrivileges, UserRoleChangeData,
+};
 use crate::utils::roles::privileges::{Privileges, Privilege};
 
-use super::models::{GroupUserMessage, KickMessage};
+use super::models::{
+    ActionError, CommandInfo, GroupUserMessage, KickMessage, MemberNickname, NewPoll, Poll,
+    ScheduledMessage,
+};
+use super::flood::FloodGuard;
 use anyhow::anyhow;
-use axum::extract::ws::{Message, WebSocket};
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
 use dashmap::DashMap;
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 use tokio::select;
 use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast;
-use tokio::sync::{Mutex, Notify, RwLock};
+use tokio::sync::{Mutex, MutexGuard, Notify, RwLock};
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, trace};
 use uuid::Uuid;
 
 pub struct ChatState {
     pub groups: Groups,
+    pub flood: FloodGuard,
 }
 
 impl ChatState {
     pub fn new() -> Arc<Self> {
+        Self::with_settings(ChatSettings::default())
+    }
+
+    pub fn with_settings(settings: ChatSettings) -> Arc<Self> {
         Arc::new(Self {
             groups: Groups::new(),
+            flood: FloodGuard::new(settings),
         })
     }
 }
@@ -45,6 +62,12 @@ impl Groups {
             .value()
             .clone()
     }
+
+    /// Get group controller only if it was already created by a connected user
+    pub fn get_loaded(&self, group_id: &Uuid) -> Option<GroupController> {
+        let Groups(groups) = self;
+        groups.get(group_id).map(|controller| controller.value().clone())
+    }
 }
 
 #[derive(Clone)]
@@ -52,6 +75,8 @@ pub struct GroupController {
     pub channel: GroupChannel,
     users: Users,
     privileges: SocketGroupRolePrivileges,
+    filters: FilterCache,
+    role_changes: Arc<Mutex<()>>,
 }
 
 impl GroupController {
@@ -60,10 +85,176 @@ impl GroupController {
             channel: GroupChannel::new(capacity),
             users: Users::new(),
             privileges: privileges,
+            filters: FilterCache::new(),
+            role_changes: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Held from the start of a role or privilege change until it is applied in memory,
+    /// so changes of the group reach memory in the order they were committed
+    pub async fn lock_role_changes(&self) -> MutexGuard<'_, ()> {
+        self.role_changes.lock().await
+    }
+
+    /// Compiled content filters of the group, loaded on first use after every edit
+    pub async fn content_filters<F, Fut, E>(&self, load: F) -> Result<Arc<CompiledFilters>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<CompiledFilters, E>>,
+    {
+        let version = {
+            let guard = self.filters.0.read().await;
+            if let Some(filters) = &guard.filters {
+                return Ok(filters.clone());
+            }
+            guard.version
+        };
+
+        let filters = Arc::new(load().await?);
+
+        // an edit made while loading makes the loaded filters stale
+        let mut guard = self.filters.0.write().await;
+        if guard.version == version {
+            guard.filters = Some(filters.clone());
+        }
+
+        Ok(filters)
+    }
+
+    pub async fn invalidate_filters(&self) {
+        let mut guard = self.filters.0.write().await;
+        guard.version += 1;
+        guard.filters = None;
+    }
+
+    /// Sends the action to every connection of the members picked by `recipients` from their privileges
+    pub async fn send_to<F>(&self, action: &ServerAction, recipients: F)
+    where
+        F: Fn(Uuid, &Privileges) -> bool,
+    {
+        let roles_guard = self.privileges.0.read().await;
+        let users_guard = self.users.0.read().await;
+        for (user_id, user_data) in users_guard.iter() {
+            let Some(role) = roles_guard.get(&user_data.role) else {
+                continue
+            };
+            if recipients(*user_id, &role.privileges) {
+                user_data.connections.send_across_all(action).await;
+            }
+        }
+    }
+
+    /// Replaces privileges of the group roles, members of every changed role get their new privileges once
+    pub async fn set_privileges(&self, privileges: &GroupRolePrivileges) -> Result<(), RoleError> {
+        let mut changed = HashMap::new();
+        let mut roles_guard = self.privileges.0.write().await;
+        for (role, new_privileges) in privileges.0.iter() {
+            let group_role = roles_guard.get_mut(role)
+                .ok_or(RoleError::Unexpected(anyhow!("No role {:?} found in a group", role)))?;
+
+            if group_role.privileges.changes(new_privileges).is_empty() {
+                continue
+            }
+            group_role.privileges = new_privileges.clone();
+            changed.insert(group_role.role(), new_privileges.clone());
+        }
+        drop(roles_guard);
+
+        let users_guard = self.users.0.read().await;
+        for (_, user_data) in users_guard.iter() {
+            if let Some(privileges) = changed.get(&user_data.role) {
+                user_data.connections.send_across_all(&ServerAction::SetPrivileges(privileges.clone())).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the group roles after roles were created, reordered or deleted.
+    ///
+    /// Members of a deleted role get the member role, everyone whose privileges changed gets them again.
+    pub async fn set_roles(&self, roles: GroupRoles) {
+        let mut roles_guard = self.privileges.0.write().await;
+        let mut users_guard = self.users.0.write().await;
+        for (_, user_data) in users_guard.iter_mut() {
+            let Some(role) = roles.get(&user_data.role).or_else(|| roles.get(&Role::Member.into())) else {
+                continue
+            };
+            let changed = match roles_guard.get(&user_data.role) {
+                Some(previous) => !previous.privileges.changes(&role.privileges).is_empty(),
+                None => true,
+            };
+
+            user_data.role = role.role();
+            user_data.rank = role.rank;
+            if changed {
+                user_data.connections.send_across_all(&ServerAction::SetPrivileges(role.privileges.clone())).await;
+            }
+        }
+        *roles_guard = roles;
+    }
+
+    /// Changes the role of a connected member and sends them the privileges of the new role
+    pub async fn set_role(&self, data: &UserRoleChangeData) -> Result<(), RoleError> {
+        let roles_guard = self.privileges.0.read().await;
+        let role = roles_guard.get(&data.value)
+            .ok_or(RoleError::Unexpected(anyhow!("No role {:?} found in the group", data.value)))?;
+
+        let mut users_guard = self.users.0.write().await;
+        let user = users_guard.get_mut(&data.user_id).ok_or(RoleError::UserNotFound)?;
+
+        user.role = role.role();
+        user.rank = role.rank;
+
+        Ok(user.connections.send_across_all(&ServerAction::SetPrivileges(role.privileges.clone())).await)
+    }
+
+    /// Detaches every connection of the user from the group and tells them why
+    pub async fn kick(&self, user_id: Uuid, kick: KickMessage) {
+        self.detach(user_id, &ServerAction::Kick(kick)).await;
+    }
+
+    /// Detaches every connection of the user after they left the group on their own
+    pub async fn leave(&self, user_id: Uuid) {
+        self.detach(user_id, &ServerAction::GroupLeft).await;
+    }
+
+    async fn detach(&self, user_id: Uuid, action: &ServerAction) {
+        let Some(user_data) = self.users.0.write().await.remove(&user_id) else {
+            return;
+        };
+
+        let listeners: Vec<UserChannelListener> = user_data
+            .connections
+            .0
+            .write()
+            .await
+            .drain()
+            .map(|(_, listener)| listener)
+            .collect();
+
+        for listener in listeners {
+            listener.disconnect_with_action(action).await;
         }
     }
 }
 
+#[derive(Clone)]
+struct FilterCache(Arc<RwLock<FilterCacheState>>);
+impl FilterCache {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(FilterCacheState {
+            version: 0,
+            filters: None,
+        })))
+    }
+}
+
+struct FilterCacheState {
+    version: u64,
+    filters: Option<Arc<CompiledFilters>>,
+}
+
 #[derive(Clone)]
 struct Users(Arc<RwLock<HashMap<Uuid, GroupUserData>>>);
 impl Users {
@@ -72,14 +263,17 @@ impl Users {
     }
 }
 struct GroupUserData {
-    role: Role,
+    role: RoleRef,
+    /// Built-in role the member's role counts as
+    rank: Role,
     connections: UserConnections,
 }
 
 impl GroupUserData {
-    fn new(role: Role) -> Self {
+    fn new(role: &GroupRole) -> Self {
         Self {
-            role,
+            role: role.role(),
+            rank: role.rank,
             connections: UserConnections::new(),
         }
     }
@@ -129,42 +323,57 @@ impl UserController {
         }
     }
 
-    pub async fn connect(&mut self, group_id: Uuid, group_controller: GroupController, role: Role) {
-        if let None = self.group_conn {
-            let listener = UserChannelListener::new(
-                self.user_channel.sender.clone(),
-                group_controller.channel.subscribe(),
-            )
-            .await;
-            let listener = group_controller
-                .users
-                .0
-                .write()
-                .await
-                .entry(self.user_id)
-                .or_insert(GroupUserData::new(role))
-                .connections
-                .0
-                .write()
-                .await
-                .insert(self.conn_id.clone(), listener);
-
-            if let Some(prev_listener) = listener {
-                prev_listener.disconnect();
-            }
-
-            self.group_conn = Some(GroupConnection {
-                group_id,
-                controller: group_controller,
-            });
+    /// Subscribes the connection to the group, leaving the previous one first
+    pub async fn connect(&mut self, group_id: Uuid, group_controller: GroupController, role: &GroupRole) {
+        self.disconnect().await;
+
+        let listener = UserChannelListener::new(
+            self.user_channel.sender.clone(),
+            group_controller.channel.subscribe(),
+        )
+        .await;
+        let listener = group_controller
+            .users
+            .0
+            .write()
+            .await
+            .entry(self.user_id)
+            .or_insert(GroupUserData::new(role))
+            .connections
+            .0
+            .write()
+            .await
+            .insert(self.conn_id.clone(), listener);
+
+        if let Some(prev_listener) = listener {
+            prev_listener.disconnect();
         }
+
+        self.group_conn = Some(GroupConnection {
+            group_id,
+            controller: group_controller,
+        });
     }
 
     pub async fn disconnect(&mut self) {
-        if let Some(conn) = &self.group_conn {
-            if let Some(user_data) = conn.controller.users.0.write().await.get(&self.user_id) {
-                user_data.connections.0.write().await.remove(&self.conn_id);
-            }
+        let Some(conn) = self.group_conn.take() else {
+            return;
+        };
+
+        let mut users_guard = conn.controller.users.0.write().await;
+        let Some(user_data) = users_guard.get(&self.user_id) else {
+            // already detached by a kick
+            return;
+        };
+
+        let mut connections_guard = user_data.connections.0.write().await;
+        if let Some(listener) = connections_guard.remove(&self.conn_id) {
+            listener.disconnect();
+        }
+
+        if connections_guard.is_empty() {
+            drop(connections_guard);
+            users_guard.remove(&self.user_id);
         }
     }
 
@@ -185,74 +394,54 @@ impl UserController {
         None
     }
 
-    pub async fn kick(&self, user_id: Uuid) {
-        if let Some(conn) = &self.group_conn {
-            if let Some(connections) = conn.controller.users.0.write().await.remove(&user_id) {
-                let listeners: Vec<UserChannelListener> = connections
-                    .connections
-                    .0
-                    .write()
-                    .await
-                    .drain()
-                    .map(|(_, listener)| listener)
-                    .collect();
-
-                for listener in listeners {
-                    listener
-                        .disconnect_with_action(&ServerAction::Kick(KickMessage {
-                            from: "somone".into(),
-                            reason: "no reason".into(),
-                        }))
-                        .await;
-                }
-            }
-        }
-    }
-
     pub async fn set_privilege(&self, data: &PrivilegeChangeData) -> Result<(), RoleError> {
         let conn = self.group_conn.as_ref()
             .ok_or(RoleError::Unexpected(anyhow!("No group connection found in the user controller")))?;
 
-        let privilege_ref = conn.controller.privileges.0.get(&data.role)
+        let mut roles_guard = conn.controller.privileges.0.write().await;
+        let role = roles_guard.get_mut(&data.role)
             .ok_or(RoleError::Unexpected(anyhow!("No role {:?} found in a group", &data.role)))?;
-
-        let mut privilege_guard = privilege_ref.write().await;
-        privilege_guard.0.replace(data.value);
+        role.privileges.0.replace(data.value);
 
         let users_guard = conn.controller.users.0.read().await;
 
         // send new privileges to every user, whose privileges were changed
         for (_, user_data) in users_guard.iter() {
             if user_data.role == data.role {
-                user_data.connections.send_across_all(&ServerAction::SetPrivileges(privilege_guard.clone())).await;
+                user_data.connections.send_across_all(&ServerAction::SetPrivileges(role.privileges.clone())).await;
             }
         }
 
         Ok(())
     }
 
-    pub async fn single_set_role(&self, data: &UserRoleChangeData) -> Result<(), RoleError> {
+    pub async fn set_privileges(&self, privileges: &GroupRolePrivileges) -> Result<(), RoleError> {
         let conn = self.group_conn.as_ref()
             .ok_or(RoleError::Unexpected(anyhow!("No group connection found in the user controller")))?;
 
-        let mut users_guard = conn.controller.users.0.write().await;
-        let user = users_guard.get_mut(&data.user_id).ok_or(RoleError::UserNotFound)?;
-
-        user.role = data.value;
+        conn.controller.set_privileges(privileges).await
+    }
 
-        let privileges = conn.controller.privileges.get_privileges(data.value).await
-            .ok_or(RoleError::Unexpected(anyhow!("No role {:?} found in the group", data.value)))?;
+    pub async fn single_set_role(&self, data: &UserRoleChangeData) -> Result<(), RoleError> {
+        let conn = self.group_conn.as_ref()
+            .ok_or(RoleError::Unexpected(anyhow!("No group connection found in the user controller")))?;
 
-        Ok(user.connections.send_across_all(&ServerAction::SetPrivileges(privileges)).await)
+        conn.controller.set_role(data).await
     }
 
+    /// Built-in role the member's role counts as
     pub async fn get_role(&self, user_id: Uuid) -> Option<Role> {
         let Some(conn) = &self.group_conn else {
             return None
         };
 
         conn.controller.users.0.read().await.get(&user_id)
-            .and_then(|x| Some(x.role))
+            .and_then(|x| Some(x.rank))
+    }
+
+    async fn get_role_ref(&self, user_id: Uuid) -> Option<RoleRef> {
+        let conn = self.group_conn.as_ref()?;
+        conn.controller.users.0.read().await.get(&user_id).map(|x| x.role)
     }
 
     pub fn get_group_privileges(&self) -> Option<&SocketGroupRolePrivileges> {
@@ -260,15 +449,21 @@ impl UserController {
         Some(&connection.controller.privileges)
     }
 
+    /// Privileges of the member's role
+    pub async fn get_user_privileges(&self, user_id: Uuid) -> Option<Privileges> {
+        let role = self.get_role_ref(user_id).await?;
+        self.get_group_privileges()?.get_privileges(&role).await
+    }
+
     pub async fn get_user_privilege(&self, user_id: Uuid, val: Privilege) -> Option<Privilege> {
-        let role = self.get_role(user_id).await?;
-        self.get_group_privileges()?.get_privilege(role, val).await
+        let role = self.get_role_ref(user_id).await?;
+        self.get_group_privileges()?.get_privilege(&role, val).await
     }
 
     pub async fn verify_with_privilege(&self, user_id: Uuid, min_val: Privilege) -> Result<bool, RoleError> {
-        let role = self.get_role(user_id).await.ok_or(RoleError::Unexpected(anyhow!("No role found for user_id")))?;
+        let role = self.get_role_ref(user_id).await.ok_or(RoleError::Unexpected(anyhow!("No role found for user_id")))?;
         let privileges = self.get_group_privileges().ok_or(RoleError::Unexpected(anyhow!("No socket privileges found")))?;
-        privileges.verify_with_privilege(role, min_val).await
+        privileges.verify_with_privilege(&role, min_val).await
     }
 }
 
@@ -347,6 +542,16 @@ impl UserSender {
         let msg = serde_json::to_string(action).unwrap();
         sender.lock().await.send(Message::Text(msg)).await
     }
+
+    /// Close the connection with a close frame explaining why
+    pub async fn close(&self, code: u16, reason: &str) -> Result<(), axum::Error> {
+        let UserSender(sender) = self;
+        let frame = CloseFrame {
+            code,
+            reason: reason.to_string().into(),
+        };
+        sender.lock().await.send(Message::Close(Some(frame))).await
+    }
     pub async fn listen(&self, broadcast_receiver: GroupReceiver) -> (JoinHandle<()>, UserSender) {
         let GroupReceiver(mut broadcast_receiver) = broadcast_receiver;
 
@@ -424,7 +629,21 @@ pub enum ServerAction {
     GroupInvite,
     Message(GroupUserMessage),
     Kick(KickMessage),
+    GroupLeft,
     SetPrivileges(Privileges),
+    MessageScheduled(ScheduledMessage),
+    ScheduledMessages(Vec<ScheduledMessage>),
+    ScheduledMessageCancelled { id: i32 },
+    PollUpdate(Poll),
+    Error(ActionError),
+    Commands(Vec<CommandInfo>),
+    InvitationCreated { code: String },
+    NicknameChanged(MemberNickname),
+    UserMuted { user_id: Uuid, muted_until: i64 },
+    UserUnmuted { user_id: Uuid },
+    MessageDeleted { id: i32 },
+    ReportCreated(MessageReport),
+    ReportResolved { id: i32, resolution: ReportResolution },
 }
 
 /// Client action send to server
@@ -433,10 +652,25 @@ pub enum ClientAction {
     ChangeGroup { group_id: Uuid },
     SendMessage { content: String },
     GroupInvite { group_id: Uuid },
-    RemoveUser { user_id: Uuid, group_id: Uuid },
+    RemoveUser {
+        user_id: Uuid,
+        group_id: Uuid,
+        #[serde(default)]
+        reason: Option<String>,
+    },
     SingleChangePrivileges { data: PrivilegeChangeData },
     SingleChangeUserRole { data: UserRoleChangeData },
+    ChangePrivileges { data: GroupPrivilegesChangeData },
+    ChangeUserRoles { data: Vec<UserRoleChangeData> },
     RequestMessages { loaded: i64 },
+    ScheduleMessage { content: String, send_at: i64 },
+    RequestScheduledMessages,
+    CancelScheduledMessage { id: i32 },
+    CreatePoll { poll: NewPoll },
+    VotePoll { poll_id: i32, options: Vec<i32> },
+    ClosePoll { poll_id: i32 },
+    RequestPoll { poll_id: i32 },
+    RequestCommands,
     Close,
     Ignore,
 }
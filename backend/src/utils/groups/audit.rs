@@ -0,0 +1,143 @@
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{query, query_as, Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+use super::errors::GroupError;
+use super::models::{AuditAction, AuditFilter, AuditRecord, AuditRecordModel};
+use crate::utils::roles::member_group_role;
+use crate::utils::roles::privileges::{
+    CanBan, CanDeleteMessages, CanEditSettings, CanKick, CanManageInvitations, CanManageNicknames, CanManageRoles,
+    CanMute, Privilege,
+};
+
+pub const AUDIT_LOG_PAGE_SIZE: i64 = 50;
+
+/// Privileges behind the changes the log records, any of them lets a member read it
+const AUDIT_LOG_ACCESS: [Privilege; 8] = [
+    Privilege::CanKick(CanKick::Yes),
+    Privilege::CanBan(CanBan::Yes),
+    Privilege::CanMute(CanMute::Yes),
+    Privilege::CanDeleteMessages(CanDeleteMessages::Yes),
+    Privilege::CanManageInvitations(CanManageInvitations::Yes),
+    Privilege::CanEditSettings(CanEditSettings::Yes),
+    Privilege::CanManageRoles(CanManageRoles::Yes),
+    Privilege::CanManageNicknames(CanManageNicknames::Yes),
+];
+
+/// Change made to a group, recorded in the same transaction as the change itself
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    action: AuditAction,
+    target_user_id: Option<Uuid>,
+    target: Option<String>,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            target_user_id: None,
+            target: None,
+            before: None,
+            after: None,
+        }
+    }
+
+    /// Member the action was taken against
+    pub fn target_user(mut self, user_id: Uuid) -> Self {
+        self.target_user_id = Some(user_id);
+        self
+    }
+
+    /// Setting, role or object the action changed
+    pub fn target(mut self, target: impl ToString) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Value before the change, `None` leaves it empty
+    pub fn before(mut self, value: impl Serialize) -> Self {
+        self.before = to_json(value);
+        self
+    }
+
+    /// Value after the change, `None` leaves it empty
+    pub fn after(mut self, value: impl Serialize) -> Self {
+        self.after = to_json(value);
+        self
+    }
+}
+
+fn to_json(value: impl Serialize) -> Option<Value> {
+    serde_json::to_value(value).ok().filter(|value| !value.is_null())
+}
+
+pub async fn record_audit_entry<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    group_id: &Uuid,
+    actor_id: &Uuid,
+    entry: AuditEntry,
+) -> Result<(), GroupError> {
+    query!(
+        r#"
+            insert into group_audit_log (group_id, actor_id, action, target_user_id, target, before, after)
+            values ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        group_id,
+        actor_id,
+        entry.action as AuditAction,
+        entry.target_user_id,
+        entry.target,
+        entry.before,
+        entry.after
+    )
+    .execute(exe)
+    .await?;
+
+    Ok(())
+}
+
+/// Audit log of the group, newest first, visible to members whose role moderates or manages the group
+pub async fn fetch_audit_log(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    filter: AuditFilter,
+) -> Result<Vec<AuditRecord>, GroupError> {
+    let Some(role) = member_group_role(pool, user_id, group_id).await? else {
+        return Err(GroupError::UserNotInGroup);
+    };
+    if !AUDIT_LOG_ACCESS.iter().any(|privilege| role.privileges.satisfies(*privilege)) {
+        return Err(GroupError::InsufficientPrivileges);
+    }
+
+    let records = query_as!(
+        AuditRecordModel,
+        r#"
+            select a.id, a.actor_id, u.username as "actor?", a.action as "action: AuditAction",
+            a.target_user_id, a.target, a.before, a.after, a.created_at
+            from group_audit_log a
+            left join users u on u.id = a.actor_id
+            where a.group_id = $1
+            and ($2::audit_action is null or a.action = $2)
+            and ($3::uuid is null or a.actor_id = $3)
+            and ($4::uuid is null or a.target_user_id = $4)
+            and a.id < coalesce($5, 2147483647)
+            order by a.id desc
+            limit $6
+        "#,
+        group_id,
+        filter.action as Option<AuditAction>,
+        filter.actor_id,
+        filter.target_user_id,
+        filter.before,
+        AUDIT_LOG_PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records.into_iter().map(AuditRecord::from).collect())
+}
@@ -0,0 +1,270 @@
+use anyhow::Context;
+use serde_json::json;
+use sqlx::{query, query_as, Executor, PgPool, Postgres};
+use time::{Duration, OffsetDateTime};
+use tracing::debug;
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::errors::GroupError;
+use super::models::{AuditAction, GroupBan, GroupBanModel, NewBan};
+use super::{check_if_user_exists, normalize_reason, require_group_privilege};
+use crate::utils::chat::models::{GroupUserMessage, SystemEvent};
+use crate::utils::chat::{create_system_message, get_group_nickname};
+use crate::utils::roles::member_role;
+use crate::utils::roles::models::{Gate, Role, RolePosition, RoleRef};
+use crate::utils::roles::privileges::{CanBan, Privilege};
+
+/// Longest temporary ban, anything longer should be permanent
+pub const MAX_BAN_DURATION: Duration = Duration::days(3650);
+
+const BAN: Privilege = Privilege::CanBan(CanBan::Yes);
+
+/// Bans the user from the group, removing them if they are a member.
+///
+/// The moderator's role has to grant banning and pass the gate against the role of the member.
+/// Returns the ban and the system message recorded when a member was removed.
+pub async fn ban_user(
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: &Uuid,
+    moderator_id: &Uuid,
+    ban: NewBan,
+) -> Result<(GroupBan, Option<GroupUserMessage>), GroupError> {
+    if &ban.user_id == moderator_id {
+        return Err(GroupError::CannotBanSelf);
+    }
+
+    let reason = normalize_reason(ban.reason.as_deref())?;
+
+    let expires_at = match ban.duration.map(Duration::seconds) {
+        Some(duration) if !duration.is_positive() || duration > MAX_BAN_DURATION => {
+            return Err(GroupError::InvalidBanDuration);
+        }
+        duration => duration.map(|duration| OffsetDateTime::now_utc() + duration),
+    };
+
+    let mut transaction = pool.begin().await?;
+
+    let moderator = require_group_privilege(&mut transaction, moderator_id, group_id, BAN).await?;
+
+    if !check_if_user_exists(&mut transaction, &ban.user_id).await? {
+        return Err(GroupError::UserDoesNotExist);
+    }
+
+    let member = query!(
+        r#"
+            select group_users.nickname, group_roles.role_id, group_roles.role_type as "role_type: Role",
+            group_roles.custom, roles.position from group_users
+            join group_roles on group_users.role_id = group_roles.role_id
+            join roles on group_roles.role_id = roles.id
+            where group_users.user_id = $1
+            and group_users.group_id = $2
+            for update of group_users
+        "#,
+        ban.user_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+
+    if let Some(member) = member.as_ref() {
+        let target = RolePosition {
+            role: RoleRef::new(member.role_id, (!member.custom).then_some(member.role_type)),
+            position: member.position,
+        };
+        if !gate.verify(moderator.place(), target, (*moderator_id, ban.user_id)) {
+            return Err(GroupError::InsufficientPrivileges);
+        }
+    }
+
+    // a new ban replaces the previous one, including an expired one
+    query!(
+        r#"
+            update group_bans
+            set lifted_at = now(), lifted_by = $3
+            where group_id = $1 and user_id = $2 and lifted_at is null
+        "#,
+        group_id,
+        ban.user_id,
+        moderator_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let res = query!(
+        r#"
+            insert into group_bans (group_id, user_id, banned_by, reason, expires_at)
+            values ($1, $2, $3, $4, $5)
+            returning id, created_at, (select username from users where id = $2) as "username!"
+        "#,
+        group_id,
+        ban.user_id,
+        moderator_id,
+        reason,
+        expires_at
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let message = match member {
+        Some(member) => {
+            query!(
+                r#"
+                    delete from group_users
+                    where user_id = $1 and group_id = $2
+                "#,
+                ban.user_id,
+                group_id
+            )
+            .execute(&mut transaction)
+            .await?;
+
+            let event = SystemEvent::MemberBanned {
+                user_id: ban.user_id,
+                nickname: member.nickname,
+                banned_by: *moderator_id,
+                moderator: get_group_nickname(&mut transaction, moderator_id, group_id)
+                    .await
+                    .context("Failed to fetch moderator nickname")?,
+                reason: reason.clone(),
+                expires_at: expires_at.map(|expiry| expiry.unix_timestamp()),
+            };
+            let message = create_system_message(&mut transaction, group_id, event)
+                .await
+                .context("Failed to record member ban")?;
+            Some(message)
+        }
+        None => None,
+    };
+
+    let expires_at = expires_at.map(|expiry| expiry.unix_timestamp());
+    let entry = AuditEntry::new(AuditAction::MemberBanned)
+        .target_user(ban.user_id)
+        .after(json!({ "reason": reason, "expires_at": expires_at }));
+    record_audit_entry(&mut transaction, group_id, moderator_id, entry).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {moderator_id} banned user {} from group {group_id}", ban.user_id);
+
+    let ban = GroupBan {
+        id: res.id,
+        user_id: ban.user_id,
+        username: res.username,
+        banned_by: Some(*moderator_id),
+        reason,
+        expires_at,
+        banned_at: res.created_at.unix_timestamp(),
+    };
+
+    Ok((ban, message))
+}
+
+/// Lifts the user's ban in force.
+///
+/// The moderator's role has to grant banning and pass the gate against the role of the moderator who placed the ban,
+/// unless it's their own ban or its moderator is no longer in the group.
+pub async fn unban_user(
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: &Uuid,
+    moderator_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<(), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    let moderator = require_group_privilege(&mut transaction, moderator_id, group_id, BAN).await?;
+
+    let Some(ban) = query!(
+        r#"
+            select id, banned_by from group_bans
+            where group_id = $1 and user_id = $2 and lifted_at is null
+            and (expires_at is null or expires_at > now())
+            for update
+        "#,
+        group_id,
+        user_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::BanNotFound);
+    };
+
+    if let Some(banned_by) = ban.banned_by.filter(|banned_by| banned_by != moderator_id) {
+        if let Some(banner) = member_role(&mut transaction, &banned_by, group_id).await? {
+            if !gate.verify(moderator.place(), banner, (*moderator_id, banned_by)) {
+                return Err(GroupError::InsufficientPrivileges);
+            }
+        }
+    }
+
+    query!(
+        r#"
+            update group_bans
+            set lifted_at = now(), lifted_by = $2
+            where id = $1
+        "#,
+        ban.id,
+        moderator_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::BanLifted).target_user(*user_id);
+    record_audit_entry(&mut transaction, group_id, moderator_id, entry).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {moderator_id} lifted the ban of user {user_id} in group {group_id}");
+
+    Ok(())
+}
+
+pub async fn is_user_banned<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<bool, GroupError> {
+    let res = query!(
+        r#"
+            select exists (
+                select 1 from group_bans
+                where user_id = $1 and group_id = $2 and lifted_at is null
+                and (expires_at is null or expires_at > now())
+            ) as "banned!"
+        "#,
+        user_id,
+        group_id
+    )
+    .fetch_one(exe)
+    .await?;
+
+    Ok(res.banned)
+}
+
+/// Bans currently in force, newest first, visible to members who can ban
+pub async fn fetch_group_bans(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<GroupBan>, GroupError> {
+    require_group_privilege(pool, user_id, group_id, BAN).await?;
+
+    let bans = query_as!(
+        GroupBanModel,
+        r#"
+            select b.id, b.user_id, u.username, b.banned_by, b.reason, b.expires_at, b.created_at
+            from group_bans b
+            join users u on u.id = b.user_id
+            where b.group_id = $1 and b.lifted_at is null
+            and (b.expires_at is null or b.expires_at > now())
+            order by b.id desc
+        "#,
+        group_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(bans.into_iter().map(GroupBan::from).collect())
+}
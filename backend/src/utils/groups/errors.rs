@@ -18,6 +18,46 @@ pub enum GroupError {
     UserAlreadyInGroup,
     #[error("Wrong invitation url")]
     BadInvitation,
+    #[error("Insufficient privileges")]
+    InsufficientPrivileges,
+    #[error("Invalid retention period")]
+    InvalidRetention,
+    #[error("Reason is too long")]
+    ReasonTooLong,
+    #[error("Cannot kick yourself")]
+    CannotKickSelf,
+    #[error("You are banned from this group")]
+    UserBanned,
+    #[error("Ban not found")]
+    BanNotFound,
+    #[error("Cannot ban yourself")]
+    CannotBanSelf,
+    #[error("Invalid ban duration")]
+    InvalidBanDuration,
+    #[error("Cannot mute yourself")]
+    CannotMuteSelf,
+    #[error("Invalid mute duration")]
+    InvalidMuteDuration,
+    #[error("User is not muted")]
+    MuteNotFound,
+    #[error("Invalid filter pattern")]
+    InvalidFilter,
+    #[error("Too many content filters")]
+    TooManyFilters,
+    #[error("Content filter not found")]
+    FilterNotFound,
+    #[error("Message not found")]
+    MessageNotFound,
+    #[error("Cannot report this message")]
+    CannotReportMessage,
+    #[error("Message already reported")]
+    AlreadyReported,
+    #[error("Report not found")]
+    ReportNotFound,
+    #[error("Cannot transfer ownership to yourself")]
+    CannotTransferToSelf,
+    #[error("Ownership transfer not found")]
+    TransferNotFound,
     #[error("Invitation error")]
     InvitationError(#[from] InvitationError),
     #[error("Role error")]
@@ -40,6 +80,26 @@ impl IntoResponse for GroupError {
             GroupError::MissingGroupField => StatusCode::BAD_REQUEST,
             GroupError::UserAlreadyInGroup => StatusCode::BAD_REQUEST,
             GroupError::BadInvitation => StatusCode::BAD_REQUEST,
+            GroupError::InsufficientPrivileges => StatusCode::FORBIDDEN,
+            GroupError::InvalidRetention => StatusCode::BAD_REQUEST,
+            GroupError::ReasonTooLong => StatusCode::BAD_REQUEST,
+            GroupError::CannotKickSelf => StatusCode::BAD_REQUEST,
+            GroupError::UserBanned => StatusCode::FORBIDDEN,
+            GroupError::BanNotFound => StatusCode::NOT_FOUND,
+            GroupError::CannotBanSelf => StatusCode::BAD_REQUEST,
+            GroupError::InvalidBanDuration => StatusCode::BAD_REQUEST,
+            GroupError::CannotMuteSelf => StatusCode::BAD_REQUEST,
+            GroupError::InvalidMuteDuration => StatusCode::BAD_REQUEST,
+            GroupError::MuteNotFound => StatusCode::NOT_FOUND,
+            GroupError::InvalidFilter => StatusCode::BAD_REQUEST,
+            GroupError::TooManyFilters => StatusCode::BAD_REQUEST,
+            GroupError::FilterNotFound => StatusCode::NOT_FOUND,
+            GroupError::MessageNotFound => StatusCode::NOT_FOUND,
+            GroupError::CannotReportMessage => StatusCode::BAD_REQUEST,
+            GroupError::AlreadyReported => StatusCode::CONFLICT,
+            GroupError::ReportNotFound => StatusCode::NOT_FOUND,
+            GroupError::CannotTransferToSelf => StatusCode::BAD_REQUEST,
+            GroupError::TransferNotFound => StatusCode::NOT_FOUND,
             GroupError::InvitationError(e) => return e.into_response(),
             GroupError::RoleError(e) => return e.into_response(),
             GroupError::Unexpected(e) => {
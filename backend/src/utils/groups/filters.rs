@@ -0,0 +1,381 @@
+use regex::{Regex, RegexBuilder};
+use sqlx::{query, query_as, Executor, PgPool, Postgres};
+use tracing::{debug, error};
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::errors::GroupError;
+use super::models::{AuditAction, ContentFilter, FilterAction, FilterKind, NewContentFilter};
+use super::require_group_privilege;
+use crate::utils::roles::privileges::{CanEditSettings, Privilege};
+
+pub const MAX_FILTER_PATTERN_LENGTH: usize = 200;
+pub const MAX_GROUP_FILTERS: i64 = 100;
+/// Upper bound of the compiled size of a single regex, keeps matching cheap
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+const MASK_CHAR: char = '*';
+
+const EDIT_SETTINGS: Privilege = Privilege::CanEditSettings(CanEditSettings::Yes);
+
+/// Rules of a single group compiled once and reused for every message
+#[derive(Debug, Default)]
+pub struct CompiledFilters {
+    rules: Vec<CompiledRule>,
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    id: i32,
+    regex: Regex,
+    action: FilterAction,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FilterVerdict {
+    Blocked { filter_id: i32 },
+    Passed { content: String, flagged_by: Vec<i32> },
+}
+
+impl CompiledFilters {
+    pub fn new(filters: Vec<ContentFilter>) -> Self {
+        let rules = filters
+            .into_iter()
+            .filter_map(|filter| match compile_pattern(filter.kind, &filter.pattern) {
+                Ok(regex) => Some(CompiledRule {
+                    id: filter.id,
+                    regex,
+                    action: filter.action,
+                }),
+                Err(_) => {
+                    error!("Skipping content filter {} with invalid pattern", filter.id);
+                    None
+                }
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Blocking rules win, masks are applied before the flags are checked
+    pub fn apply(&self, content: &str) -> FilterVerdict {
+        let rules_with = |action| self.rules.iter().filter(move |rule| rule.action == action);
+
+        let blocked_by = rules_with(FilterAction::Block).find(|rule| rule.regex.is_match(content));
+        if let Some(rule) = blocked_by {
+            return FilterVerdict::Blocked { filter_id: rule.id };
+        }
+
+        let flagged_by = rules_with(FilterAction::Flag)
+            .filter(|rule| rule.regex.is_match(content))
+            .map(|rule| rule.id)
+            .collect();
+
+        let content = rules_with(FilterAction::Mask).fold(content.to_string(), |content, rule| {
+            rule.regex
+                .replace_all(&content, |caps: &regex::Captures| {
+                    MASK_CHAR.to_string().repeat(caps[0].chars().count())
+                })
+                .into_owned()
+        });
+
+        FilterVerdict::Passed { content, flagged_by }
+    }
+}
+
+fn compile_pattern(kind: FilterKind, pattern: &str) -> Result<Regex, GroupError> {
+    let pattern = match kind {
+        FilterKind::Word => {
+            let word = pattern.trim();
+            let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+            // word boundaries only make sense next to word characters
+            let start = if word.starts_with(is_word_char) { r"\b" } else { "" };
+            let end = if word.ends_with(is_word_char) { r"\b" } else { "" };
+            format!("{start}{}{end}", regex::escape(word))
+        }
+        FilterKind::Regex => pattern.to_string(),
+    };
+
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(kind == FilterKind::Word)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|_| GroupError::InvalidFilter)?;
+
+    // a rule matching nothing at all would flag or mask every message
+    if regex.is_match("") {
+        return Err(GroupError::InvalidFilter);
+    }
+
+    Ok(regex)
+}
+
+fn validate_filter(filter: &NewContentFilter) -> Result<(), GroupError> {
+    let pattern = filter.pattern.trim();
+    if pattern.is_empty() || pattern.chars().count() > MAX_FILTER_PATTERN_LENGTH {
+        return Err(GroupError::InvalidFilter);
+    }
+
+    compile_pattern(filter.kind, pattern).map(|_| ())
+}
+
+/// Compiles every rule of the group, used to fill the group controller cache
+pub async fn load_group_filters<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    group_id: &Uuid,
+) -> Result<CompiledFilters, GroupError> {
+    let filters = query_as!(
+        ContentFilter,
+        r#"
+            select id, kind as "kind: FilterKind", pattern, action as "action: FilterAction"
+            from group_content_filters
+            where group_id = $1
+            order by id
+        "#,
+        group_id
+    )
+    .fetch_all(exe)
+    .await?;
+
+    Ok(CompiledFilters::new(filters))
+}
+
+pub async fn fetch_group_filters(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<ContentFilter>, GroupError> {
+    require_group_privilege(pool, user_id, group_id, EDIT_SETTINGS).await?;
+
+    let filters = query_as!(
+        ContentFilter,
+        r#"
+            select id, kind as "kind: FilterKind", pattern, action as "action: FilterAction"
+            from group_content_filters
+            where group_id = $1
+            order by id
+        "#,
+        group_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(filters)
+}
+
+pub async fn create_group_filter(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    filter: NewContentFilter,
+) -> Result<ContentFilter, GroupError> {
+    validate_filter(&filter)?;
+
+    let mut transaction = pool.begin().await?;
+
+    require_group_privilege(&mut transaction, user_id, group_id, EDIT_SETTINGS).await?;
+
+    let res = query!(
+        r#"
+            select count(*) as "count!" from group_content_filters
+            where group_id = $1
+        "#,
+        group_id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    if res.count >= MAX_GROUP_FILTERS {
+        return Err(GroupError::TooManyFilters);
+    }
+
+    let filter = query_as!(
+        ContentFilter,
+        r#"
+            insert into group_content_filters (group_id, kind, pattern, action, created_by)
+            values ($1, $2, $3, $4, $5)
+            returning id, kind as "kind: FilterKind", pattern, action as "action: FilterAction"
+        "#,
+        group_id,
+        filter.kind as FilterKind,
+        filter.pattern.trim(),
+        filter.action as FilterAction,
+        user_id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::FilterCreated)
+        .target(filter.id)
+        .after(&filter);
+    record_audit_entry(&mut transaction, group_id, user_id, entry).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {user_id} added content filter {} in group {group_id}", filter.id);
+
+    Ok(filter)
+}
+
+pub async fn update_group_filter(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    filter_id: i32,
+    filter: NewContentFilter,
+) -> Result<ContentFilter, GroupError> {
+    validate_filter(&filter)?;
+
+    let mut transaction = pool.begin().await?;
+
+    require_group_privilege(&mut transaction, user_id, group_id, EDIT_SETTINGS).await?;
+
+    let Some(previous) = query_as!(
+        ContentFilter,
+        r#"
+            select id, kind as "kind: FilterKind", pattern, action as "action: FilterAction"
+            from group_content_filters
+            where id = $1 and group_id = $2
+            for update
+        "#,
+        filter_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::FilterNotFound);
+    };
+
+    let filter = query_as!(
+        ContentFilter,
+        r#"
+            update group_content_filters
+            set kind = $2, pattern = $3, action = $4
+            where id = $1
+            returning id, kind as "kind: FilterKind", pattern, action as "action: FilterAction"
+        "#,
+        filter_id,
+        filter.kind as FilterKind,
+        filter.pattern.trim(),
+        filter.action as FilterAction
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::FilterUpdated)
+        .target(filter_id)
+        .before(&previous)
+        .after(&filter);
+    record_audit_entry(&mut transaction, group_id, user_id, entry).await?;
+
+    transaction.commit().await?;
+
+    Ok(filter)
+}
+
+pub async fn delete_group_filter(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    filter_id: i32,
+) -> Result<(), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    require_group_privilege(&mut transaction, user_id, group_id, EDIT_SETTINGS).await?;
+
+    let Some(filter) = query_as!(
+        ContentFilter,
+        r#"
+            delete from group_content_filters
+            where id = $1 and group_id = $2
+            returning id, kind as "kind: FilterKind", pattern, action as "action: FilterAction"
+        "#,
+        filter_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::FilterNotFound);
+    };
+
+    let entry = AuditEntry::new(AuditAction::FilterDeleted)
+        .target(filter_id)
+        .before(&filter);
+    record_audit_entry(&mut transaction, group_id, user_id, entry).await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Marks a sent message for review by the rules it matched
+pub async fn record_message_flags<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    group_id: &Uuid,
+    message_id: i32,
+    filter_ids: &[i32],
+) -> Result<(), GroupError> {
+    query!(
+        r#"
+            insert into message_flags (message_id, group_id, filter_id)
+            select $1, $2, unnest($3::int[])
+        "#,
+        message_id,
+        group_id,
+        filter_ids
+    )
+    .execute(exe)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(id: i32, kind: FilterKind, pattern: &str, action: FilterAction) -> ContentFilter {
+        ContentFilter {
+            id,
+            kind,
+            pattern: pattern.into(),
+            action,
+        }
+    }
+
+    #[test]
+    fn word_filter_masks_whole_words_only() {
+        let filters = CompiledFilters::new(vec![filter(1, FilterKind::Word, "heck", FilterAction::Mask)]);
+        assert_eq!(
+            filters.apply("Heck, what the heck? Checking."),
+            FilterVerdict::Passed {
+                content: "****, what the ****? Checking.".into(),
+                flagged_by: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn block_wins_over_other_actions() {
+        let filters = CompiledFilters::new(vec![
+            filter(1, FilterKind::Regex, r"free \w+", FilterAction::Flag),
+            filter(2, FilterKind::Regex, r"https?://\S+", FilterAction::Block),
+        ]);
+        assert_eq!(
+            filters.apply("free nitro at https://example.com"),
+            FilterVerdict::Blocked { filter_id: 2 }
+        );
+        assert_eq!(
+            filters.apply("free nitro"),
+            FilterVerdict::Passed {
+                content: "free nitro".into(),
+                flagged_by: vec![1]
+            }
+        );
+    }
+
+    #[test]
+    fn empty_matching_regex_is_rejected() {
+        let res = compile_pattern(FilterKind::Regex, "a*");
+        assert!(matches!(res, Err(GroupError::InvalidFilter)));
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Context;
+use serde_json::json;
+use sqlx::{query, query_as, PgConnection, PgPool};
+use tracing::debug;
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::errors::GroupError;
+use super::models::{AuditAction, KickRecord, KickRecordModel};
+use super::{normalize_reason, require_group_privilege};
+use crate::utils::chat::models::{GroupUserMessage, KickMessage, SystemEvent};
+use crate::utils::chat::{create_system_message, get_group_nickname};
+use crate::utils::roles::privileges::{CanKick, Privilege};
+use crate::utils::webhooks::{events::enqueue_group_event, models::GroupEvent};
+
+pub const KICK_HISTORY_PAGE_SIZE: i64 = 50;
+
+/// Removes the user from the group, stores the kick in the moderation history
+/// and returns the system message together with the notice for the kicked user.
+///
+/// The caller checks the kick privilege of the moderator and the kick gate against the member's role.
+pub async fn kick_user_from_group(
+    pool: &PgPool,
+    group_id: &Uuid,
+    user_id: &Uuid,
+    kicked_by: &Uuid,
+    reason: Option<&str>,
+) -> Result<(GroupUserMessage, KickMessage), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    let kick = record_kick(&mut transaction, group_id, user_id, kicked_by, reason).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {kicked_by} kicked user {user_id} from group {group_id}");
+
+    Ok(kick)
+}
+
+/// Kicks the user as a part of a larger transaction, see `kick_user_from_group`
+pub(super) async fn record_kick(
+    conn: &mut PgConnection,
+    group_id: &Uuid,
+    user_id: &Uuid,
+    kicked_by: &Uuid,
+    reason: Option<&str>,
+) -> Result<(GroupUserMessage, KickMessage), GroupError> {
+    if user_id == kicked_by {
+        return Err(GroupError::CannotKickSelf);
+    }
+
+    let reason = normalize_reason(reason)?;
+
+    let nickname = get_group_nickname(&mut *conn, user_id, group_id)
+        .await
+        .map_err(|_| GroupError::UserNotInGroup)?;
+    let moderator = get_group_nickname(&mut *conn, kicked_by, group_id)
+        .await
+        .map_err(|_| GroupError::UserNotInGroup)?;
+
+    query!(
+        r#"
+            delete from group_users
+            where user_id = $1 and group_id = $2
+        "#,
+        user_id,
+        group_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    query!(
+        r#"
+            insert into group_kicks (group_id, user_id, nickname, kicked_by, moderator, reason)
+            values ($1, $2, $3, $4, $5, $6)
+        "#,
+        group_id,
+        user_id,
+        nickname,
+        kicked_by,
+        moderator,
+        reason
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::MemberKicked)
+        .target_user(*user_id)
+        .after(json!({ "reason": reason }));
+    record_audit_entry(&mut *conn, group_id, kicked_by, entry).await?;
+
+    let event = SystemEvent::MemberKicked {
+        user_id: *user_id,
+        nickname,
+        kicked_by: *kicked_by,
+        moderator: moderator.clone(),
+        reason: reason.clone(),
+    };
+    let message = create_system_message(&mut *conn, group_id, event)
+        .await
+        .context("Failed to record member kick")?;
+
+    let event = GroupEvent::MemberKicked { user_id: *user_id, kicked_by: *kicked_by };
+    enqueue_group_event(&mut *conn, group_id, event)
+        .await
+        .context("Failed to enqueue member kicked event")?;
+
+    Ok((
+        message,
+        KickMessage {
+            from: moderator,
+            reason,
+        },
+    ))
+}
+
+/// Kicks in the group, newest first, visible to members who can kick
+pub async fn fetch_kick_history(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    before: Option<i32>,
+) -> Result<Vec<KickRecord>, GroupError> {
+    require_group_privilege(pool, user_id, group_id, Privilege::CanKick(CanKick::Yes)).await?;
+
+    let kicks = query_as!(
+        KickRecordModel,
+        r#"
+            select id, user_id, nickname, kicked_by, moderator, reason, created_at from group_kicks
+            where group_id = $1
+            and id < coalesce($2, 2147483647)
+            order by id desc
+            limit $3
+        "#,
+        group_id,
+        before,
+        KICK_HISTORY_PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(kicks.into_iter().map(KickRecord::from).collect())
+}
@@ -1,6 +1,15 @@
+pub mod audit;
+pub mod bans;
 pub mod errors;
+pub mod filters;
+pub mod kicks;
 pub mod models;
+pub mod mutes;
+pub mod ownership;
+pub mod reports;
+pub mod retention;
 
+use self::bans::is_user_banned;
 use self::models::*;
 use anyhow::Context;
 use axum::Json;
@@ -10,6 +19,17 @@ use sqlx::{query, query_as, Acquire, Executor, PgPool, Postgres};
 use tracing::debug;
 use uuid::Uuid;
 
+use super::chat::models::{GroupUserMessage, SystemEvent};
+use super::chat::nicknames::available_nickname;
+use super::chat::{create_system_message, get_group_nickname};
+use super::roles::member_group_role;
+use super::roles::models::{GroupRole, Role};
+use super::roles::privileges::Privilege;
+use super::webhooks::{events::enqueue_group_event, models::GroupEvent};
+
+/// Longest reason a moderator can give for a kick or a ban
+pub const MAX_REASON_LENGTH: usize = 300;
+
 pub async fn try_add_user_to_group<'c>(
     conn: impl Acquire<'c, Database = Postgres>,
     user_id: &Uuid,
@@ -43,6 +63,11 @@ pub async fn try_add_user_to_group<'c>(
         return Err(GroupError::UserDoesNotExist);
     }
 
+    if is_user_banned(&mut transaction, user_id, group_id).await? {
+        transaction.rollback().await?;
+        return Err(GroupError::UserBanned);
+    }
+
     let username = query!(
         r#"
             select (username) from users
@@ -54,6 +79,9 @@ pub async fn try_add_user_to_group<'c>(
     .await?
     .username;
 
+    // Groups with unique nicknames get a numbered variant of a taken username
+    let nickname = available_nickname(&mut transaction, group_id, user_id, &username).await?;
+
     debug!("Adding user '{username}' to group ");
     query!(
         r#"
@@ -63,15 +91,24 @@ pub async fn try_add_user_to_group<'c>(
                     from group_roles
                     where group_roles.group_id = $2
                     and group_roles.role_type = 'member'
+                    and not group_roles.custom
             ))
         "#,
         user_id,
         group_id,
-        username
+        nickname
     )
     .execute(&mut transaction)
     .await?;
 
+    enqueue_group_event(
+        &mut transaction,
+        group_id,
+        GroupEvent::MemberJoined { user_id: *user_id },
+    )
+    .await
+    .context("Failed to enqueue member joined event")?;
+
     transaction.commit().await?;
 
     Ok(())
@@ -89,7 +126,7 @@ pub async fn create_group(pool: &PgPool, name: &str, user_id: Uuid) -> Result<()
         r#"
             insert into groups (name)
             values ($1)
-            returning *
+            returning id, name
         "#,
         name
     )
@@ -125,6 +162,7 @@ pub async fn create_group(pool: &PgPool, name: &str, user_id: Uuid) -> Result<()
                     from group_roles
                     where group_roles.group_id = $2
                     and group_roles.role_type = 'owner'
+                    and not group_roles.custom
             ))
         "#,
         user_id,
@@ -216,10 +254,10 @@ pub async fn get_group_info(pool: &PgPool, group_id: &Uuid) -> Result<GroupInfo,
 
     let res = query!(
         r#"
-            select g.name,count(user_id) from group_users
+            select g.name, g.retention_days, count(user_id) from group_users
             join groups g on group_users.group_id = g.id
             where group_id = $1
-            group by g.name
+            group by g.name, g.retention_days
         "#,
         group_id
     )
@@ -229,15 +267,27 @@ pub async fn get_group_info(pool: &PgPool, group_id: &Uuid) -> Result<GroupInfo,
     Ok(GroupInfo {
         name: res.name,
         members: res.count.unwrap_or(0),
+        retention_days: res.retention_days,
     })
 }
 
+/// Removes the user from the group on their own request and records it in the group history.
+///
+/// An owner leaving hands the group over to the longest-serving admin, or else member,
+/// the second message records the new owner then.
 pub async fn try_remove_user_from_group(
     pool: &PgPool,
     user_id: Uuid,
     group_id: Uuid,
-) -> Result<(), GroupError> {
-    let _ = query!(
+) -> Result<(GroupUserMessage, Option<GroupUserMessage>), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    let nickname = get_group_nickname(&mut transaction, &user_id, &group_id)
+        .await
+        .map_err(|_| GroupError::UserNotInGroup)?;
+    let role = require_group_role(&mut transaction, &user_id, &group_id, Role::Member).await?;
+
+    query!(
         r#"
             delete from group_users
             where user_id = $1 and group_id = $2
@@ -245,8 +295,82 @@ pub async fn try_remove_user_from_group(
         user_id,
         group_id
     )
-    .execute(pool)
+    .execute(&mut transaction)
     .await?;
 
-    Ok(())
+    let event = SystemEvent::MemberLeft { user_id, nickname: nickname.clone() };
+    let message = create_system_message(&mut transaction, &group_id, event)
+        .await
+        .context("Failed to record member removal")?;
+
+    enqueue_group_event(&mut transaction, &group_id, GroupEvent::MemberLeft { user_id })
+        .await
+        .context("Failed to enqueue member left event")?;
+
+    let succession = match role {
+        Role::Owner => ownership::record_succession(&mut transaction, &group_id, &user_id, nickname).await?,
+        _ => None,
+    };
+
+    transaction.commit().await?;
+
+    Ok((message, succession))
+}
+
+/// Fails unless the user is a group member with at least `min_role`, returns their role
+pub async fn require_group_role<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    min_role: Role,
+) -> Result<Role, GroupError> {
+    let Some(member) = query!(
+        r#"
+            select group_roles.role_type as "role: Role" from group_users
+            join group_roles on group_users.role_id = group_roles.role_id
+            where group_users.user_id = $1
+            and group_users.group_id = $2
+        "#,
+        user_id,
+        group_id
+    )
+    .fetch_optional(exe)
+    .await? else {
+        return Err(GroupError::UserNotInGroup);
+    };
+
+    if member.role < min_role {
+        return Err(GroupError::InsufficientPrivileges);
+    }
+
+    Ok(member.role)
+}
+
+/// Fails unless the user is a group member whose role grants `privilege`, returns their role
+pub async fn require_group_privilege<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    privilege: Privilege,
+) -> Result<GroupRole, GroupError> {
+    let Some(role) = member_group_role(exe, user_id, group_id).await? else {
+        return Err(GroupError::UserNotInGroup);
+    };
+
+    if !role.privileges.satisfies(privilege) {
+        return Err(GroupError::InsufficientPrivileges);
+    }
+
+    Ok(role)
+}
+
+/// Trims the moderation reason, an empty one counts as no reason
+pub fn normalize_reason(reason: Option<&str>) -> Result<Option<String>, GroupError> {
+    let reason = reason.map(str::trim).filter(|reason| !reason.is_empty());
+    match reason {
+        Some(reason) if reason.chars().count() > MAX_REASON_LENGTH => {
+            Err(GroupError::ReasonTooLong)
+        }
+        reason => Ok(reason.map(String::from)),
+    }
 }
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
+use crate::utils::chat::models::{GroupUserMessage, KickMessage};
+
 #[derive(Serialize, Deserialize)]
 pub struct NewGroup {
     pub name: String,
@@ -33,4 +37,381 @@ pub struct NewGroupInvitation {
 pub struct GroupInfo {
     pub name: String,
     pub members: i64,
+    pub retention_days: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub days: Option<i32>,
+}
+
+pub struct KickRecordModel {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub nickname: String,
+    pub kicked_by: Option<Uuid>,
+    pub moderator: String,
+    pub reason: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct KickRecord {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub nickname: String,
+    pub kicked_by: Option<Uuid>,
+    pub moderator: String,
+    pub reason: Option<String>,
+    pub kicked_at: i64,
+}
+
+impl From<KickRecordModel> for KickRecord {
+    fn from(val: KickRecordModel) -> Self {
+        Self {
+            id: val.id,
+            user_id: val.user_id,
+            nickname: val.nickname,
+            kicked_by: val.kicked_by,
+            moderator: val.moderator,
+            reason: val.reason,
+            kicked_at: val.created_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct NewMute {
+    pub user_id: Uuid,
+    /// Mute length in seconds
+    pub duration: i64,
+}
+
+pub struct GroupMuteModel {
+    pub user_id: Uuid,
+    pub nickname: String,
+    pub muted_until: OffsetDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct GroupMute {
+    pub user_id: Uuid,
+    pub nickname: String,
+    pub muted_until: i64,
+}
+
+impl From<GroupMuteModel> for GroupMute {
+    fn from(val: GroupMuteModel) -> Self {
+        Self {
+            user_id: val.user_id,
+            nickname: val.nickname,
+            muted_until: val.muted_until.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NewBan {
+    pub user_id: Uuid,
+    pub reason: Option<String>,
+    /// Ban length in seconds, `None` bans until a moderator lifts it
+    pub duration: Option<i64>,
+}
+
+pub struct GroupBanModel {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub username: String,
+    pub banned_by: Option<Uuid>,
+    pub reason: Option<String>,
+    pub expires_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct GroupBan {
+    pub id: i32,
+    pub user_id: Uuid,
+    pub username: String,
+    pub banned_by: Option<Uuid>,
+    pub reason: Option<String>,
+    pub expires_at: Option<i64>,
+    pub banned_at: i64,
+}
+
+impl From<GroupBanModel> for GroupBan {
+    fn from(val: GroupBanModel) -> Self {
+        Self {
+            id: val.id,
+            user_id: val.user_id,
+            username: val.username,
+            banned_by: val.banned_by,
+            reason: val.reason,
+            expires_at: val.expires_at.map(|expiry| expiry.unix_timestamp()),
+            banned_at: val.created_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct NewOwner {
+    pub user_id: Uuid,
+}
+
+pub struct OwnershipTransferModel {
+    pub from_user_id: Uuid,
+    pub to_user_id: Uuid,
+    pub created_at: OffsetDateTime,
+}
+
+/// Ownership offered to a member, waiting for them to accept it
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct OwnershipTransfer {
+    pub from_user_id: Uuid,
+    pub to_user_id: Uuid,
+    pub offered_at: i64,
+}
+
+impl From<OwnershipTransferModel> for OwnershipTransfer {
+    fn from(val: OwnershipTransferModel) -> Self {
+        Self {
+            from_user_id: val.from_user_id,
+            to_user_id: val.to_user_id,
+            offered_at: val.created_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "filter_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    /// Whole word or phrase, matched case-insensitively
+    Word,
+    Regex,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "filter_action", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Refuse the whole message
+    Block,
+    /// Replace the matching text with asterisks
+    Mask,
+    /// Send the message but mark it for review
+    Flag,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NewContentFilter {
+    pub kind: FilterKind,
+    pub pattern: String,
+    pub action: FilterAction,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ContentFilter {
+    pub id: i32,
+    pub kind: FilterKind,
+    pub pattern: String,
+    pub action: FilterAction,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "report_category", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ReportCategory {
+    Spam,
+    Harassment,
+    Inappropriate,
+    Other,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "report_resolution", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum ReportResolution {
+    Dismissed,
+    MessageDeleted,
+    AuthorMuted,
+    AuthorKicked,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct NewReport {
+    pub message_id: i32,
+    pub category: ReportCategory,
+    pub comment: Option<String>,
+}
+
+/// What the moderator does about a reported message
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ReportAction {
+    Dismiss,
+    DeleteMessage,
+    MuteAuthor {
+        /// Mute length in seconds
+        duration: i64,
+    },
+    KickAuthor {
+        reason: Option<String>,
+    },
+}
+
+impl ReportAction {
+    pub fn resolution(&self) -> ReportResolution {
+        match self {
+            ReportAction::Dismiss => ReportResolution::Dismissed,
+            ReportAction::DeleteMessage => ReportResolution::MessageDeleted,
+            ReportAction::MuteAuthor { .. } => ReportResolution::AuthorMuted,
+            ReportAction::KickAuthor { .. } => ReportResolution::AuthorKicked,
+        }
+    }
+}
+
+pub struct MessageReportModel {
+    pub id: i32,
+    pub message_id: Option<i32>,
+    pub author_id: Option<Uuid>,
+    pub author: String,
+    pub content: String,
+    pub reporter_id: Option<Uuid>,
+    pub category: ReportCategory,
+    pub comment: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub resolution: Option<ReportResolution>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MessageReport {
+    pub id: i32,
+    /// Missing once the message is deleted, `content` keeps what was reported
+    pub message_id: Option<i32>,
+    pub author_id: Option<Uuid>,
+    pub author: String,
+    pub content: String,
+    pub reporter_id: Option<Uuid>,
+    pub category: ReportCategory,
+    pub comment: Option<String>,
+    pub reported_at: i64,
+    pub resolution: Option<ReportResolution>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<i64>,
+}
+
+impl From<MessageReportModel> for MessageReport {
+    fn from(val: MessageReportModel) -> Self {
+        Self {
+            id: val.id,
+            message_id: val.message_id,
+            author_id: val.author_id,
+            author: val.author,
+            content: val.content,
+            reporter_id: val.reporter_id,
+            category: val.category,
+            comment: val.comment,
+            reported_at: val.created_at.unix_timestamp(),
+            resolution: val.resolution,
+            resolved_by: val.resolved_by,
+            resolved_at: val.resolved_at.map(|resolved_at| resolved_at.unix_timestamp()),
+        }
+    }
+}
+
+/// Report closed by a resolution, tells whom to notify
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedReport {
+    pub id: i32,
+    pub reporter_id: Option<Uuid>,
+}
+
+/// What a resolution did besides closing the reports, announced by the caller once it's committed
+#[derive(Debug)]
+pub enum ResolutionEffect {
+    None,
+    MessageDeleted { message_id: i32 },
+    AuthorMuted { user_id: Uuid, muted_until: OffsetDateTime },
+    AuthorKicked { user_id: Uuid, message: Box<GroupUserMessage>, kick: KickMessage },
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    RoleChanged,
+    PrivilegeChanged,
+    MemberKicked,
+    MemberBanned,
+    BanLifted,
+    MemberMuted,
+    MuteLifted,
+    InvitationCreated,
+    RetentionChanged,
+    NicknamePolicyChanged,
+    FilterCreated,
+    FilterUpdated,
+    FilterDeleted,
+    ReportResolved,
+    RoleCreated,
+    RoleUpdated,
+    RoleDeleted,
+    RolesReordered,
+    InvitationRevoked,
+    OwnershipTransferred,
+}
+
+/// Narrows the audit log down, every field is optional
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct AuditFilter {
+    pub action: Option<AuditAction>,
+    pub actor_id: Option<Uuid>,
+    pub target_user_id: Option<Uuid>,
+    /// Id of the oldest entry of the previous page
+    pub before: Option<i32>,
+}
+
+pub struct AuditRecordModel {
+    pub id: i32,
+    pub actor_id: Option<Uuid>,
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    pub target_user_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub id: i32,
+    pub actor_id: Option<Uuid>,
+    /// Username of the actor
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    pub target_user_id: Option<Uuid>,
+    pub target: Option<String>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: i64,
+}
+
+impl From<AuditRecordModel> for AuditRecord {
+    fn from(val: AuditRecordModel) -> Self {
+        Self {
+            id: val.id,
+            actor_id: val.actor_id,
+            actor: val.actor,
+            action: val.action,
+            target_user_id: val.target_user_id,
+            target: val.target,
+            before: val.before,
+            after: val.after,
+            created_at: val.created_at.unix_timestamp(),
+        }
+    }
 }
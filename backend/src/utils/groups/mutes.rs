@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use sqlx::{query, query_as, PgConnection, PgPool};
+use time::{Duration, OffsetDateTime};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::errors::GroupError;
+use super::models::{AuditAction, GroupMute, GroupMuteModel};
+use super::require_group_privilege;
+use crate::utils::chat::socket::{ChatState, ServerAction};
+use crate::utils::roles::member_group_role;
+use crate::utils::roles::models::Gate;
+use crate::utils::roles::privileges::{CanMute, Privilege};
+
+/// Longest mute a moderator can give at once
+pub const MAX_MUTE_DURATION: Duration = Duration::days(365);
+/// Time between two checks for mutes that ended
+pub const MUTE_EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+const MUTE: Privilege = Privilege::CanMute(CanMute::Yes);
+
+/// Mutes the user in the group and returns the time the mute ends.
+///
+/// The moderator's role has to grant muting and pass the gate against the role of the member.
+pub async fn mute_user(
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: &Uuid,
+    user_id: &Uuid,
+    muted_by: &Uuid,
+    duration: Duration,
+) -> Result<OffsetDateTime, GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    let muted_until = apply_mute(&mut transaction, gate, group_id, user_id, muted_by, duration).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {muted_by} muted user {user_id} in group {group_id}");
+
+    Ok(muted_until)
+}
+
+/// Mutes the user as a part of a larger transaction, see `mute_user`
+pub(super) async fn apply_mute(
+    conn: &mut PgConnection,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: &Uuid,
+    user_id: &Uuid,
+    muted_by: &Uuid,
+    duration: Duration,
+) -> Result<OffsetDateTime, GroupError> {
+    if user_id == muted_by {
+        return Err(GroupError::CannotMuteSelf);
+    }
+
+    if !duration.is_positive() || duration > MAX_MUTE_DURATION {
+        return Err(GroupError::InvalidMuteDuration);
+    }
+
+    let moderator = require_group_privilege(&mut *conn, muted_by, group_id, MUTE).await?;
+    let Some(member) = member_group_role(&mut *conn, user_id, group_id).await? else {
+        return Err(GroupError::UserNotInGroup);
+    };
+    if !gate.verify(moderator.place(), member.place(), (*muted_by, *user_id)) {
+        return Err(GroupError::InsufficientPrivileges);
+    }
+
+    let res = query!(
+        r#"
+            insert into group_mutes (group_id, user_id, muted_by, muted_until)
+            values ($1, $2, $3, $4)
+            returning muted_until
+        "#,
+        group_id,
+        user_id,
+        muted_by,
+        OffsetDateTime::now_utc() + duration
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::MemberMuted)
+        .target_user(*user_id)
+        .after(res.muted_until.unix_timestamp());
+    record_audit_entry(&mut *conn, group_id, muted_by, entry).await?;
+
+    Ok(res.muted_until)
+}
+
+/// Ends every active mute of the user in the group.
+///
+/// The moderator's role has to grant muting and pass the gate against the role of the member.
+pub async fn unmute_user(
+    pool: &PgPool,
+    gate: &Gate<(Uuid, Uuid)>,
+    group_id: &Uuid,
+    moderator_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<(), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    let moderator = require_group_privilege(&mut transaction, moderator_id, group_id, MUTE).await?;
+    if let Some(member) = member_group_role(&mut transaction, user_id, group_id).await? {
+        if !gate.verify(moderator.place(), member.place(), (*moderator_id, *user_id)) {
+            return Err(GroupError::InsufficientPrivileges);
+        }
+    }
+
+    // the caller announces the unmute, the expiry worker must not repeat it
+    let res = query!(
+        r#"
+            update group_mutes
+            set muted_until = now(), lifted_at = now(), lifted_by = $3, expiry_announced = true
+            where group_id = $1 and user_id = $2 and muted_until > now()
+        "#,
+        group_id,
+        user_id,
+        moderator_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(GroupError::MuteNotFound);
+    }
+
+    let entry = AuditEntry::new(AuditAction::MuteLifted).target_user(*user_id);
+    record_audit_entry(&mut transaction, group_id, moderator_id, entry).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {moderator_id} unmuted user {user_id} in group {group_id}");
+
+    Ok(())
+}
+
+/// End of the longest mute currently applied to the user
+pub async fn get_active_mute(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Option<OffsetDateTime>, GroupError> {
+    let res = query!(
+        r#"
+            select max(muted_until) as muted_until from group_mutes
+            where user_id = $1 and group_id = $2 and muted_until > now()
+        "#,
+        user_id,
+        group_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(res.muted_until)
+}
+
+/// Members muted right now with the end of their longest mute
+pub async fn fetch_group_mutes(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<GroupMute>, GroupError> {
+    require_group_privilege(pool, user_id, group_id, MUTE).await?;
+
+    let mutes = query_as!(
+        GroupMuteModel,
+        r#"
+            select m.user_id, gu.nickname, max(m.muted_until) as "muted_until!" from group_mutes m
+            join group_users gu on gu.user_id = m.user_id and gu.group_id = m.group_id
+            where m.group_id = $1 and m.muted_until > now()
+            group by m.user_id, gu.nickname
+            order by max(m.muted_until)
+        "#,
+        group_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(mutes.into_iter().map(GroupMute::from).collect())
+}
+
+/// Marks mutes that ran out as announced and returns the members who can talk again
+pub async fn take_ended_mutes(pool: &PgPool) -> Result<Vec<(Uuid, Uuid)>, GroupError> {
+    let ended = query!(
+        r#"
+            with ended as (
+                update group_mutes
+                set expiry_announced = true
+                where not expiry_announced and muted_until <= now()
+                returning group_id, user_id
+            )
+            select distinct e.group_id, e.user_id from ended e
+            where not exists (
+                select 1 from group_mutes m
+                where m.group_id = e.group_id and m.user_id = e.user_id and m.muted_until > now()
+            )
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(ended
+        .into_iter()
+        .map(|mute| (mute.group_id, mute.user_id))
+        .collect())
+}
+
+/// Tells connected group members about mutes that ended on their own
+pub async fn announce_ended_mutes(pool: &PgPool, state: &ChatState) -> Result<usize, GroupError> {
+    let ended = take_ended_mutes(pool).await?;
+
+    for (group_id, user_id) in &ended {
+        if let Some(group_controller) = state.groups.get_loaded(group_id) {
+            group_controller
+                .channel
+                .sender
+                .send(ServerAction::UserUnmuted { user_id: *user_id });
+        }
+    }
+
+    Ok(ended.len())
+}
+
+pub async fn run_mute_expiry_worker(pool: PgPool, state: Arc<ChatState>) {
+    let mut interval = tokio::time::interval(MUTE_EXPIRY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match announce_ended_mutes(&pool, &state).await {
+            Ok(0) => (),
+            Ok(n) => info!("Mute expiry worker announced {n} ended mutes"),
+            Err(e) => error!("Mute expiry worker failed to announce ended mutes: {e:?}"),
+        }
+    }
+}
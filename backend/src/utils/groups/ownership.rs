@@ -0,0 +1,259 @@
+use anyhow::Context;
+use sqlx::{query, query_as, PgConnection, PgPool};
+use tracing::debug;
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::errors::GroupError;
+use super::models::{AuditAction, OwnershipTransfer, OwnershipTransferModel};
+use super::{check_if_group_member, require_group_role};
+use crate::utils::chat::models::{GroupUserMessage, SystemEvent};
+use crate::utils::chat::{create_system_message, get_group_nickname};
+use crate::utils::roles::locked_group_roles;
+use crate::utils::roles::models::Role;
+use crate::utils::webhooks::{events::enqueue_group_event, models::GroupEvent};
+
+/// Offers the ownership of the group to another member, replacing the previous offer.
+///
+/// Nothing changes until the member accepts it.
+pub async fn offer_group_ownership(
+    pool: &PgPool,
+    owner_id: &Uuid,
+    group_id: &Uuid,
+    user_id: &Uuid,
+) -> Result<OwnershipTransfer, GroupError> {
+    if owner_id == user_id {
+        return Err(GroupError::CannotTransferToSelf);
+    }
+
+    let mut transaction = pool.begin().await?;
+
+    require_group_role(&mut transaction, owner_id, group_id, Role::Owner).await?;
+    require_group_role(&mut transaction, user_id, group_id, Role::Member).await?;
+
+    let transfer = query_as!(
+        OwnershipTransferModel,
+        r#"
+            insert into group_ownership_transfers (group_id, from_user_id, to_user_id)
+            values ($1, $2, $3)
+            on conflict (group_id) do update
+            set from_user_id = excluded.from_user_id, to_user_id = excluded.to_user_id, created_at = now()
+            returning from_user_id, to_user_id, created_at
+        "#,
+        group_id,
+        owner_id,
+        user_id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    debug!("User {owner_id} offered the ownership of group {group_id} to user {user_id}");
+
+    Ok(OwnershipTransfer::from(transfer))
+}
+
+/// Pending offer of the group, visible to its members
+pub async fn get_ownership_transfer(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Option<OwnershipTransfer>, GroupError> {
+    if !check_if_group_member(pool, user_id, group_id).await? {
+        return Err(GroupError::UserNotInGroup);
+    }
+
+    let transfer = query_as!(
+        OwnershipTransferModel,
+        r#"
+            select from_user_id, to_user_id, created_at from group_ownership_transfers
+            where group_id = $1
+        "#,
+        group_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(transfer.map(OwnershipTransfer::from))
+}
+
+/// Withdraws the offer as the owner or declines it as the member it was made to
+pub async fn cancel_ownership_transfer(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Result<(), GroupError> {
+    let res = query!(
+        r#"
+            delete from group_ownership_transfers
+            where group_id = $1
+            and $2 in (from_user_id, to_user_id)
+        "#,
+        group_id,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(GroupError::TransferNotFound);
+    }
+
+    Ok(())
+}
+
+/// Makes the user the owner of the group and the previous owner an admin, the offer has to be made to the user.
+///
+/// Returns the accepted offer together with the system message of the change.
+pub async fn accept_group_ownership(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<(OwnershipTransfer, GroupUserMessage), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    // role changes lock the roles first as well
+    let roles = locked_group_roles(&mut transaction, group_id).await?;
+
+    let Some(transfer) = query_as!(
+        OwnershipTransferModel,
+        r#"
+            delete from group_ownership_transfers
+            where group_id = $1 and to_user_id = $2
+            returning from_user_id, to_user_id, created_at
+        "#,
+        group_id,
+        user_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::TransferNotFound);
+    };
+
+    let (Some(owner), Some(admin)) = (roles.get(&Role::Owner.into()), roles.get(&Role::Admin.into())) else {
+        return Err(GroupError::Unexpected(anyhow::anyhow!("Group {group_id} is missing a built-in role")));
+    };
+
+    let res = query!(
+        r#"
+            update group_users
+                set role_id = $3
+                where group_id = $1
+                and user_id = $2
+                and role_id = $4
+        "#,
+        group_id,
+        transfer.from_user_id,
+        admin.id,
+        owner.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+    // leaving members take their offers with them, but the owner's membership is checked anyway
+    if res.rows_affected() == 0 {
+        return Err(GroupError::TransferNotFound);
+    }
+
+    let res = query!(
+        r#"
+            update group_users
+                set role_id = $3
+                where group_id = $1
+                and user_id = $2
+        "#,
+        group_id,
+        user_id,
+        owner.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(GroupError::UserNotInGroup);
+    }
+
+    let previous_owner = get_group_nickname(&mut transaction, &transfer.from_user_id, group_id)
+        .await
+        .context("Failed to fetch previous owner nickname")?;
+    let message = record_ownership_change(&mut transaction, group_id, &transfer.from_user_id, previous_owner, user_id).await?;
+
+    let events = [
+        GroupEvent::RoleChanged { user_id: *user_id, role: Role::Owner.into() },
+        GroupEvent::RoleChanged { user_id: transfer.from_user_id, role: Role::Admin.into() },
+    ];
+    for event in events {
+        enqueue_group_event(&mut transaction, group_id, event)
+            .await
+            .context("Failed to enqueue role changed event")?;
+    }
+
+    transaction.commit().await?;
+
+    debug!("User {user_id} took over group {group_id} from user {}", transfer.from_user_id);
+
+    Ok((OwnershipTransfer::from(transfer), message))
+}
+
+/// Records the hand-over of the group to the longest-serving admin, or else member, once the owner left,
+/// `None` when the group is left empty.
+///
+/// The successor is picked by the database when the owner's membership is deleted,
+/// so groups keep an owner when memberships are deleted outside the application as well.
+pub(super) async fn record_succession(
+    conn: &mut PgConnection,
+    group_id: &Uuid,
+    previous_owner_id: &Uuid,
+    previous_owner: String,
+) -> Result<Option<GroupUserMessage>, GroupError> {
+    let Some(successor) = query!(
+        r#"
+            select group_users.user_id from group_users
+            join group_roles on group_users.role_id = group_roles.role_id
+            where group_users.group_id = $1
+            and group_roles.role_type = 'owner'
+            and not group_roles.custom
+        "#,
+        group_id
+    )
+    .fetch_optional(&mut *conn)
+    .await? else {
+        return Ok(None);
+    };
+
+    let message = record_ownership_change(conn, group_id, previous_owner_id, previous_owner, &successor.user_id).await?;
+
+    let event = GroupEvent::RoleChanged { user_id: successor.user_id, role: Role::Owner.into() };
+    enqueue_group_event(&mut *conn, group_id, event)
+        .await
+        .context("Failed to enqueue role changed event")?;
+
+    debug!("User {} took over group {group_id} after user {previous_owner_id} left", successor.user_id);
+
+    Ok(Some(message))
+}
+
+async fn record_ownership_change(
+    conn: &mut PgConnection,
+    group_id: &Uuid,
+    previous_owner_id: &Uuid,
+    previous_owner: String,
+    user_id: &Uuid,
+) -> Result<GroupUserMessage, GroupError> {
+    let nickname = get_group_nickname(&mut *conn, user_id, group_id)
+        .await
+        .context("Failed to fetch new owner nickname")?;
+
+    let entry = AuditEntry::new(AuditAction::OwnershipTransferred)
+        .target_user(*user_id)
+        .before(previous_owner_id)
+        .after(user_id);
+    record_audit_entry(&mut *conn, group_id, previous_owner_id, entry).await?;
+
+    let event = SystemEvent::OwnershipTransferred {
+        user_id: *user_id,
+        nickname,
+        previous_owner_id: *previous_owner_id,
+        previous_owner,
+    };
+    let message = create_system_message(&mut *conn, group_id, event)
+        .await
+        .context("Failed to record ownership change")?;
+
+    Ok(message)
+}
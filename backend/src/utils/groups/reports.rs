@@ -0,0 +1,263 @@
+use sqlx::{query, query_as, Executor, PgPool, Postgres};
+use time::Duration;
+use tracing::debug;
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::errors::GroupError;
+use super::kicks::record_kick;
+use super::models::{
+    AuditAction, ClosedReport, MessageReport, MessageReportModel, NewReport, ReportAction, ReportCategory,
+    ReportResolution, ResolutionEffect,
+};
+use super::mutes::apply_mute;
+use super::{normalize_reason, require_group_privilege, require_group_role};
+use crate::utils::roles::member_group_role;
+use crate::utils::roles::models::{Gates, GroupRole, Role};
+use crate::utils::roles::privileges::{CanDeleteMessages, CanKick, CanMute, Privilege, Privileges};
+
+pub const REPORT_QUEUE_PAGE_SIZE: i64 = 50;
+
+/// Privileges behind the resolutions of a report, any of them lets a member work through the queue
+const REPORT_MODERATION: [Privilege; 3] = [
+    Privilege::CanDeleteMessages(CanDeleteMessages::Yes),
+    Privilege::CanMute(CanMute::Yes),
+    Privilege::CanKick(CanKick::Yes),
+];
+
+/// Whether a member with the privileges sees and resolves the reports of the group
+pub fn can_moderate_reports(privileges: &Privileges) -> bool {
+    REPORT_MODERATION.iter().any(|privilege| privileges.satisfies(*privilege))
+}
+
+/// Fails unless the user is a group member whose role can resolve reports, returns their role
+async fn require_report_moderator<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<GroupRole, GroupError> {
+    let Some(role) = member_group_role(exe, user_id, group_id).await? else {
+        return Err(GroupError::UserNotInGroup);
+    };
+
+    if !can_moderate_reports(&role.privileges) {
+        return Err(GroupError::InsufficientPrivileges);
+    }
+
+    Ok(role)
+}
+
+/// Files a report of a member's message into the moderation queue of the group.
+///
+/// The author and content are copied, so the report outlives the deletion of the message.
+pub async fn report_message(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    report: NewReport,
+) -> Result<MessageReport, GroupError> {
+    let comment = normalize_reason(report.comment.as_deref())?;
+
+    let mut transaction = pool.begin().await?;
+
+    require_group_role(&mut transaction, user_id, group_id, Role::Member).await?;
+
+    let Some(message) = query!(
+        r#"
+            select m.user_id, m.content, gu.nickname as "nickname?" from messages m
+            left join group_users gu on gu.user_id = m.user_id and gu.group_id = m.group_id
+            where m.id = $1 and m.group_id = $2
+        "#,
+        report.message_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::MessageNotFound);
+    };
+
+    // system and webhook messages have no member behind them to moderate
+    let (Some(author_id), Some(author)) = (message.user_id, message.nickname) else {
+        return Err(GroupError::CannotReportMessage);
+    };
+    if author_id == *user_id {
+        return Err(GroupError::CannotReportMessage);
+    }
+
+    let Some(report) = query_as!(
+        MessageReportModel,
+        r#"
+            insert into message_reports (group_id, message_id, author_id, author, content, reporter_id, category, comment)
+            values ($1, $2, $3, $4, $5, $6, $7, $8)
+            on conflict (message_id, reporter_id) where resolved_at is null do nothing
+            returning id, message_id, author_id, author, content, reporter_id, category as "category: ReportCategory",
+            comment, created_at, resolution as "resolution: ReportResolution", resolved_by, resolved_at
+        "#,
+        group_id,
+        report.message_id,
+        author_id,
+        author,
+        message.content,
+        user_id,
+        report.category as ReportCategory,
+        comment
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::AlreadyReported);
+    };
+
+    transaction.commit().await?;
+
+    debug!("User {user_id} filed report {} in group {group_id}", report.id);
+
+    Ok(MessageReport::from(report))
+}
+
+/// Open or resolved reports of the group, newest first, visible to members who can resolve them
+pub async fn fetch_group_reports(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    resolved: bool,
+    before: Option<i32>,
+) -> Result<Vec<MessageReport>, GroupError> {
+    require_report_moderator(pool, user_id, group_id).await?;
+
+    let reports = query_as!(
+        MessageReportModel,
+        r#"
+            select id, message_id, author_id, author, content, reporter_id, category as "category: ReportCategory",
+            comment, created_at, resolution as "resolution: ReportResolution", resolved_by, resolved_at
+            from message_reports
+            where group_id = $1
+            and (resolved_at is not null) = $2
+            and id < coalesce($3, 2147483647)
+            order by id desc
+            limit $4
+        "#,
+        group_id,
+        resolved,
+        before,
+        REPORT_QUEUE_PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reports.into_iter().map(MessageReport::from).collect())
+}
+
+/// Resolves the report together with every other open report of the same message and returns them
+/// with the effect of the resolution.
+///
+/// The report stays locked while the message is deleted or its author muted or kicked,
+/// which the moderator's role has to allow, so a report is never acted on twice.
+pub async fn close_report(
+    pool: &PgPool,
+    gates: &Gates,
+    moderator_id: &Uuid,
+    group_id: &Uuid,
+    report_id: i32,
+    action: ReportAction,
+) -> Result<(Vec<ClosedReport>, ResolutionEffect), GroupError> {
+    let resolution = action.resolution();
+
+    let mut transaction = pool.begin().await?;
+
+    require_report_moderator(&mut transaction, moderator_id, group_id).await?;
+
+    let Some(report) = query!(
+        r#"
+            select message_id, author_id from message_reports
+            where id = $1 and group_id = $2 and resolved_at is null
+            for update
+        "#,
+        report_id,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::ReportNotFound);
+    };
+
+    let closed = query_as!(
+        ClosedReport,
+        r#"
+            update message_reports
+            set resolution = $3, resolved_by = $4, resolved_at = now()
+            where group_id = $1 and resolved_at is null
+            and (id = $2 or message_id = $5)
+            returning id, reporter_id
+        "#,
+        group_id,
+        report_id,
+        resolution as ReportResolution,
+        moderator_id,
+        report.message_id
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    let effect = match action {
+        ReportAction::Dismiss => ResolutionEffect::None,
+        ReportAction::DeleteMessage => {
+            let privilege = Privilege::CanDeleteMessages(CanDeleteMessages::Yes);
+            require_group_privilege(&mut transaction, moderator_id, group_id, privilege).await?;
+
+            match report.message_id {
+                Some(message_id) => {
+                    query!(
+                        r#"
+                            delete from messages
+                            where id = $1 and group_id = $2
+                        "#,
+                        message_id,
+                        group_id
+                    )
+                    .execute(&mut transaction)
+                    .await?;
+                    ResolutionEffect::MessageDeleted { message_id }
+                }
+                None => ResolutionEffect::None,
+            }
+        }
+        ReportAction::MuteAuthor { duration } => {
+            let user_id = report.author_id.ok_or(GroupError::UserNotInGroup)?;
+            let duration = Duration::seconds(duration);
+            let muted_until =
+                apply_mute(&mut transaction, &gates.mute, group_id, &user_id, moderator_id, duration).await?;
+            ResolutionEffect::AuthorMuted { user_id, muted_until }
+        }
+        ReportAction::KickAuthor { reason } => {
+            let user_id = report.author_id.ok_or(GroupError::UserNotInGroup)?;
+            let privilege = Privilege::CanKick(CanKick::Yes);
+            let moderator = require_group_privilege(&mut transaction, moderator_id, group_id, privilege).await?;
+            let Some(member) = member_group_role(&mut transaction, &user_id, group_id).await? else {
+                return Err(GroupError::UserNotInGroup);
+            };
+            if !gates.kick.verify(moderator.place(), member.place(), (*moderator_id, user_id)) {
+                return Err(GroupError::InsufficientPrivileges);
+            }
+
+            let (message, kick) =
+                record_kick(&mut transaction, group_id, &user_id, moderator_id, reason.as_deref()).await?;
+            ResolutionEffect::AuthorKicked { user_id, message: Box::new(message), kick }
+        }
+    };
+
+    for closed in closed.iter() {
+        let mut entry = AuditEntry::new(AuditAction::ReportResolved)
+            .target(closed.id)
+            .after(resolution);
+        if let Some(author_id) = report.author_id {
+            entry = entry.target_user(author_id);
+        }
+        record_audit_entry(&mut transaction, group_id, moderator_id, entry).await?;
+    }
+
+    transaction.commit().await?;
+
+    debug!("User {moderator_id} resolved report {report_id} in group {group_id} with {resolution:?}");
+
+    Ok((closed, effect))
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use sqlx::{query, Acquire, PgPool, Postgres};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::audit::{record_audit_entry, AuditEntry};
+use super::{errors::GroupError, models::{AuditAction, RetentionPolicy}, require_group_privilege};
+use crate::utils::roles::privileges::{CanEditSettings, Privilege};
+
+/// Longest retention window a group can have (10 years)
+pub const MAX_RETENTION_DAYS: i32 = 3650;
+/// Amount of messages removed by a single delete statement
+pub const RETENTION_BATCH_SIZE: i64 = 500;
+/// Time between two purges of expired messages
+pub const RETENTION_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+pub async fn set_group_retention<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    policy: RetentionPolicy,
+) -> Result<(), GroupError> {
+    if let Some(days) = policy.days {
+        if days <= 0 || days > MAX_RETENTION_DAYS {
+            return Err(GroupError::InvalidRetention);
+        }
+    }
+
+    let mut transaction = conn.begin().await?;
+
+    let Some(group) = query!(
+        r#"
+            select retention_days from groups
+            where id = $1
+            for update
+        "#,
+        group_id
+    )
+    .fetch_optional(&mut transaction)
+    .await? else {
+        return Err(GroupError::GroupDoesNotExist);
+    };
+
+    require_group_privilege(
+        &mut transaction,
+        user_id,
+        group_id,
+        Privilege::CanEditSettings(CanEditSettings::Yes),
+    )
+    .await?;
+
+    query!(
+        r#"
+            update groups
+            set retention_days = $1
+            where id = $2
+        "#,
+        policy.days,
+        group_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::RetentionChanged)
+        .target("retention_days")
+        .before(group.retention_days)
+        .after(policy.days);
+    record_audit_entry(&mut transaction, group_id, user_id, entry).await?;
+
+    transaction.commit().await?;
+
+    debug!(
+        "Group {group_id} retention changed from {:?} to {:?} days",
+        group.retention_days, policy.days
+    );
+
+    Ok(())
+}
+
+/// Deletes messages older than their group retention window, one batch at a time
+pub async fn purge_expired_messages(pool: &PgPool, batch_size: i64) -> Result<u64, GroupError> {
+    let mut purged = 0;
+
+    loop {
+        let res = query!(
+            r#"
+                delete from messages
+                where id in (
+                    select messages.id from messages
+                    join groups on groups.id = messages.group_id
+                    where groups.retention_days is not null
+                    and messages.sent_at < now() - make_interval(days => groups.retention_days)
+                    limit $1
+                )
+            "#,
+            batch_size
+        )
+        .execute(pool)
+        .await?;
+
+        purged += res.rows_affected();
+
+        if (res.rows_affected() as i64) < batch_size {
+            break;
+        }
+    }
+
+    Ok(purged)
+}
+
+pub async fn run_retention_worker(pool: PgPool) {
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match purge_expired_messages(&pool, RETENTION_BATCH_SIZE).await {
+            Ok(0) => (),
+            Ok(n) => info!("Retention worker purged {n} expired messages"),
+            Err(e) => error!("Retention worker failed to purge expired messages: {e:?}"),
+        }
+    }
+}
@@ -4,6 +4,7 @@ pub mod models;
 use anyhow::Context;
 use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::{query, query_as, Acquire, PgPool, Postgres};
 use time::{Duration, OffsetDateTime};
 use tracing::debug;
@@ -11,7 +12,14 @@ use uuid::Uuid;
 
 use self::errors::InvitationError;
 
-use super::groups::{errors::GroupError, models::GroupInfo, try_add_user_to_group};
+use super::chat::models::{GroupUserMessage, SystemEvent};
+use super::chat::{create_system_message, get_group_nickname};
+use super::groups::audit::{record_audit_entry, AuditEntry};
+use super::groups::models::AuditAction;
+use super::groups::{errors::GroupError, models::GroupInfo, require_group_privilege, try_add_user_to_group};
+use super::roles::privileges::{CanManageInvitations, Privilege};
+
+const MANAGE_INVITATIONS: Privilege = Privilege::CanManageInvitations(CanManageInvitations::Yes);
 
 // Frontend payload
 #[derive(Deserialize, Debug)]
@@ -21,6 +29,16 @@ pub struct GroupInvitationCreate {
     usage_index: Option<i32>,
 }
 
+impl GroupInvitationCreate {
+    pub fn new(group_id: Uuid, expiration_index: Option<i32>, usage_index: Option<i32>) -> Self {
+        Self {
+            group_id,
+            expiration_index,
+            usage_index,
+        }
+    }
+}
+
 impl TryFrom<GroupInvitationCreate> for GroupInvitation {
     type Error = InvitationError;
     fn try_from(value: GroupInvitationCreate) -> Result<Self, Self::Error> {
@@ -65,6 +83,9 @@ pub async fn try_create_group_invitation_with_code(
 ) -> Result<String, InvitationError> {
     debug!("{invitation:#?}");
     let invitation = GroupInvitation::try_from(invitation)?;
+
+    let mut transaction = pool.begin().await?;
+
     query!(
         r#"
             insert into group_invitations
@@ -80,12 +101,101 @@ pub async fn try_create_group_invitation_with_code(
         invitation.expiration_date,
         invitation.uses_left
     )
-    .execute(pool)
+    .execute(&mut transaction)
     .await?;
 
+    let entry = AuditEntry::new(AuditAction::InvitationCreated)
+        .target(&invitation.id)
+        .after(json!({
+            "expiration_date": invitation.expiration_date.map(|date| date.unix_timestamp()),
+            "uses_left": invitation.uses_left,
+        }));
+    record_audit_entry(&mut transaction, &invitation.group_id, user_id, entry)
+        .await
+        .context("Failed to record invitation creation")?;
+
+    transaction.commit().await?;
+
     Ok(invitation.id)
 }
 
+/// Invitation of a group as listed to the members managing them
+#[derive(Serialize, Debug)]
+pub struct GroupInvitationInfo {
+    pub code: String,
+    pub created_by: Uuid,
+    pub expiration_date: Option<i64>,
+    pub uses_left: Option<i32>,
+}
+
+/// Invitations of the group, visible to members who can manage invitations
+pub async fn fetch_group_invitations(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<GroupInvitationInfo>, GroupError> {
+    require_group_privilege(pool, user_id, group_id, MANAGE_INVITATIONS).await?;
+
+    let invitations = query!(
+        r#"
+            select id, user_id, expiration_date, uses_left from group_invitations
+            where group_id = $1
+            order by expiration_date nulls last, id
+        "#,
+        group_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(invitations
+        .into_iter()
+        .map(|invitation| GroupInvitationInfo {
+            code: invitation.id,
+            created_by: invitation.user_id,
+            expiration_date: invitation.expiration_date.map(|date| date.unix_timestamp()),
+            uses_left: invitation.uses_left,
+        })
+        .collect())
+}
+
+/// Deletes the invitation, whoever created it, so that nobody else joins with it
+pub async fn revoke_group_invitation(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    code: &str,
+) -> Result<(), GroupError> {
+    let mut transaction = pool.begin().await?;
+
+    require_group_privilege(&mut transaction, user_id, group_id, MANAGE_INVITATIONS).await?;
+
+    let res = query!(
+        r#"
+            delete from group_invitations
+            where id = $1 and group_id = $2
+        "#,
+        code,
+        group_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(InvitationError::InvalidCode)?;
+    }
+
+    let entry = AuditEntry::new(AuditAction::InvitationRevoked).target(code);
+    record_audit_entry(&mut transaction, group_id, user_id, entry)
+        .await
+        .context("Failed to record invitation revocation")?;
+
+    transaction.commit().await?;
+
+    debug!("User {user_id} revoked invitation {code} of group {group_id}");
+
+    Ok(())
+}
+
 pub async fn fetch_group_info_by_code(
     pool: &PgPool,
     code: &str,
@@ -93,7 +203,7 @@ pub async fn fetch_group_info_by_code(
     let mut transaction = pool.begin().await?;
     let res = query!(
         r#"
-            select groups.name, groups.id as group_id, count(*) as members_count from group_invitations
+            select groups.name, groups.id as group_id, groups.retention_days, count(*) as members_count from group_invitations
             join groups on groups.id = group_invitations.group_id
             join group_users on groups.id = group_users.group_id
             where group_invitations.id = $1
@@ -112,6 +222,7 @@ pub async fn fetch_group_info_by_code(
             .members_count
             .context("Members count is None")
             .map_err(InvitationError::Unexpected)?, // to change
+        retention_days: invitation.retention_days,
     })
 }
 
@@ -119,7 +230,7 @@ pub async fn try_join_group_by_code<'c>(
     conn: impl Acquire<'c, Database = Postgres>,
     user_id: &Uuid,
     code: &str,
-) -> Result<(), GroupError> {
+) -> Result<(Uuid, GroupUserMessage), GroupError> {
     let mut transaction = conn.begin().await?;
 
     let Some(invitation) = query_as!(
@@ -175,6 +286,20 @@ pub async fn try_join_group_by_code<'c>(
 
     try_add_user_to_group(&mut transaction, user_id, &invitation.group_id).await?; // ? better error conversion possible
 
+    let nickname = get_group_nickname(&mut transaction, user_id, &invitation.group_id)
+        .await
+        .context("Failed to fetch nickname of the new member")?;
+    let message = create_system_message(
+        &mut transaction,
+        &invitation.group_id,
+        SystemEvent::MemberJoined {
+            user_id: *user_id,
+            nickname,
+        },
+    )
+    .await
+    .context("Failed to record member joining")?;
+
     if let Some(use_number) = invitation.uses_left {
         let _res = query!(
             r"
@@ -190,7 +315,7 @@ pub async fn try_join_group_by_code<'c>(
     }
 
     transaction.commit().await?;
-    return Ok(());
+    return Ok((invitation.group_id, message));
 }
 
 #[derive(Debug)]
@@ -4,3 +4,4 @@ pub mod friends;
 pub mod groups;
 pub mod invitations;
 pub mod roles;
+pub mod webhooks;
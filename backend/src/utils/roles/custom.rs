@@ -0,0 +1,380 @@
+use anyhow::Context;
+use serde_json::json;
+use sqlx::{query, Acquire, PgConnection, Postgres};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::utils::groups::audit::{record_audit_entry, AuditEntry};
+use crate::utils::groups::models::AuditAction;
+
+use super::errors::RoleError;
+use super::models::{CustomRoleUpdate, Gate, GroupRole, GroupRoles, NewCustomRole, Role, RoleOrder, RolePosition, RoleRef};
+use super::{get_group_roles, locked_group_roles, privileged_member_role, MANAGE_ROLES};
+
+/// Custom roles a group can have on top of the built-in ones
+pub const MAX_CUSTOM_ROLES: usize = 20;
+
+fn normalize_role_name(name: &str) -> Result<String, RoleError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > 32 {
+        return Err(RoleError::InvalidRoleName);
+    }
+    Ok(name.to_string())
+}
+
+fn normalize_role_color(color: &str) -> Result<String, RoleError> {
+    let color = color.trim().to_ascii_lowercase();
+    let Some(hex) = color.strip_prefix('#') else {
+        return Err(RoleError::InvalidRoleColor);
+    };
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(RoleError::InvalidRoleColor);
+    }
+    Ok(color)
+}
+
+/// Creates a custom role right above the member role, starting with the privileges of the member role.
+///
+/// The user has to outrank the member role according to the gate, so they outrank the new role as well.
+/// Returns the roles of the group after the change.
+pub async fn create_custom_role<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    data: &NewCustomRole,
+) -> Result<GroupRoles, RoleError> {
+    let name = normalize_role_name(&data.name)?;
+    let color = normalize_role_color(&data.color)?;
+
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, group_id, MANAGE_ROLES).await?;
+
+    let roles = locked_group_roles(&mut transaction, group_id).await?;
+    if roles.0.iter().filter(|role| role.kind.is_none()).count() >= MAX_CUSTOM_ROLES {
+        return Err(RoleError::TooManyRoles);
+    }
+
+    let member = roles.get(&Role::Member.into()).ok_or(RoleError::RoleNotFound)?;
+    if !gate.verify(user_role, member.place(), (*user_id, member.id)) {
+        return Err(RoleError::InsufficientPrivileges);
+    }
+
+    query!(
+        r#"
+            update roles
+                set position = roles.position + 1
+                from group_roles
+                where group_roles.role_id = roles.id
+                and group_roles.group_id = $1
+                and roles.position > $2
+        "#,
+        group_id,
+        member.position,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let role_id = query!(
+        r#"
+            insert into roles (
+                can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+                can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, can_manage_nicknames, name, color, position
+            )
+                select can_invite, can_send_messages, can_export, can_kick, can_ban, can_mute, can_delete_messages,
+                can_pin, can_manage_invitations, can_edit_settings, can_manage_roles, can_manage_nicknames, $2, $3, position + 1
+                from roles
+                where id = $1
+                returning id
+        "#,
+        member.id,
+        name,
+        color,
+    )
+    .fetch_one(&mut transaction)
+    .await?
+    .id;
+
+    // right above the member role is always below the admin role
+    query!(
+        r#"
+            insert into group_roles (group_id, role_id, role_type, custom)
+                values ($1, $2, 'member', true)
+        "#,
+        group_id,
+        role_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::RoleCreated)
+        .target(role_id)
+        .after(json!({ "name": name, "color": color }));
+    record_audit_entry(&mut transaction, group_id, user_id, entry)
+        .await
+        .context("Failed to audit role creation")?;
+
+    let roles = get_group_roles(&mut transaction, group_id).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {user_id} created role {role_id} in group {group_id}");
+
+    Ok(roles)
+}
+
+/// Renames or recolors a custom role, the user has to outrank it according to the gate
+pub async fn update_custom_role<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    role_id: &Uuid,
+    data: &CustomRoleUpdate,
+) -> Result<GroupRoles, RoleError> {
+    let name = data.name.as_deref().map(normalize_role_name).transpose()?;
+    let color = data.color.as_deref().map(normalize_role_color).transpose()?;
+
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, group_id, MANAGE_ROLES).await?;
+
+    let roles = locked_group_roles(&mut transaction, group_id).await?;
+    let role = roles.get(&RoleRef::Id(*role_id)).ok_or(RoleError::RoleNotFound)?;
+    if role.kind.is_some() {
+        return Err(RoleError::BuiltInRole);
+    }
+    if !gate.verify(user_role, role.place(), (*user_id, role.id)) {
+        return Err(RoleError::InsufficientPrivileges);
+    }
+
+    let updated = query!(
+        r#"
+            update roles
+                set name = coalesce($2, name), color = coalesce($3, color)
+                where id = $1
+                returning name, color
+        "#,
+        role_id,
+        name,
+        color,
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::RoleUpdated)
+        .target(role_id)
+        .before(json!({ "name": role.name, "color": role.color }))
+        .after(json!({ "name": updated.name, "color": updated.color }));
+    record_audit_entry(&mut transaction, group_id, user_id, entry)
+        .await
+        .context("Failed to audit role update")?;
+
+    let roles = get_group_roles(&mut transaction, group_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(roles)
+}
+
+/// Deletes a custom role, its members get the member role.
+///
+/// The user has to outrank the role according to the gate. Returns the roles of the group after the change.
+pub async fn delete_custom_role<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    role_id: &Uuid,
+) -> Result<GroupRoles, RoleError> {
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, group_id, MANAGE_ROLES).await?;
+
+    let roles = locked_group_roles(&mut transaction, group_id).await?;
+    let role = roles.get(&RoleRef::Id(*role_id)).ok_or(RoleError::RoleNotFound)?;
+    if role.kind.is_some() {
+        return Err(RoleError::BuiltInRole);
+    }
+    if !gate.verify(user_role, role.place(), (*user_id, role.id)) {
+        return Err(RoleError::InsufficientPrivileges);
+    }
+    let member = roles.get(&Role::Member.into()).ok_or(RoleError::RoleNotFound)?;
+
+    query!(
+        r#"
+            update group_users
+                set role_id = $3
+                where group_id = $1
+                and role_id = $2
+        "#,
+        group_id,
+        role_id,
+        member.id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    query!(
+        r#"
+            delete from group_roles
+                where group_id = $1
+                and role_id = $2
+        "#,
+        group_id,
+        role_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    query!(
+        r#"
+            delete from roles
+                where id = $1
+        "#,
+        role_id,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    query!(
+        r#"
+            update roles
+                set position = roles.position - 1
+                from group_roles
+                where group_roles.role_id = roles.id
+                and group_roles.group_id = $1
+                and roles.position > $2
+        "#,
+        group_id,
+        role.position,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let entry = AuditEntry::new(AuditAction::RoleDeleted)
+        .target(role_id)
+        .before(json!({ "name": role.name, "color": role.color }));
+    record_audit_entry(&mut transaction, group_id, user_id, entry)
+        .await
+        .context("Failed to audit role deletion")?;
+
+    let roles = get_group_roles(&mut transaction, group_id).await?;
+
+    transaction.commit().await?;
+
+    debug!("User {user_id} deleted role {role_id} in group {group_id}");
+
+    Ok(roles)
+}
+
+/// Reorders the roles between the member and the owner role.
+///
+/// The order has to list every one of them once. The user has to outrank every role that moves,
+/// both where it was and where it ends up, and the privileges have to keep the role hierarchy in the new order.
+/// Returns the roles of the group after the change.
+pub async fn reorder_roles<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    data: &RoleOrder,
+) -> Result<GroupRoles, RoleError> {
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, group_id, MANAGE_ROLES).await?;
+
+    let current = locked_group_roles(&mut transaction, group_id).await?;
+    let member = current.get(&Role::Member.into()).ok_or(RoleError::RoleNotFound)?;
+
+    let mut ordered: Vec<&GroupRole> = Vec::new();
+    for role in data.roles.iter() {
+        let role = current.get(role).ok_or(RoleError::RoleNotFound)?;
+        if matches!(role.kind, Some(Role::Member) | Some(Role::Owner)) || ordered.iter().any(|other| other.id == role.id) {
+            return Err(RoleError::RoleChangeRejection);
+        }
+        ordered.push(role);
+    }
+    // the member and the owner role stay where they are
+    if ordered.len() + 2 != current.0.len() {
+        return Err(RoleError::RoleChangeRejection);
+    }
+
+    let mut desired = current.clone();
+    let mut moved = Vec::new();
+    for (index, role) in ordered.iter().enumerate() {
+        let position = member.position + 1 + index as i32;
+        if position == role.position {
+            continue;
+        }
+
+        let info = (*user_id, role.id);
+        let target = RolePosition { role: role.role(), position };
+        if !gate.verify(user_role, role.place(), info) || !gate.verify(user_role, target, info) {
+            return Err(RoleError::InsufficientPrivileges);
+        }
+
+        desired.get_mut(&role.role()).ok_or(RoleError::RoleNotFound)?.position = position;
+        moved.push((role.id, position));
+    }
+
+    if moved.is_empty() {
+        return Ok(current);
+    }
+    desired.0.sort_by_key(|role| role.position);
+    desired.verify_hierarchy()?;
+
+    for (role_id, position) in moved {
+        query!(
+            r#"
+                update roles
+                    set position = $2
+                    where id = $1
+            "#,
+            role_id,
+            position,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    refresh_ranks(&mut transaction, group_id).await?;
+
+    let entry = AuditEntry::new(AuditAction::RolesReordered)
+        .before(current.0.iter().map(|role| role.role()).collect::<Vec<_>>())
+        .after(desired.0.iter().map(|role| role.role()).collect::<Vec<_>>());
+    record_audit_entry(&mut transaction, group_id, user_id, entry)
+        .await
+        .context("Failed to audit role reordering")?;
+
+    let roles = get_group_roles(&mut transaction, group_id).await?;
+
+    transaction.commit().await?;
+
+    Ok(roles)
+}
+
+/// Custom roles above the admin role count as admins, the ones below it as members
+async fn refresh_ranks(conn: &mut PgConnection, group_id: &Uuid) -> Result<(), RoleError> {
+    query!(
+        r#"
+            update group_roles
+                set role_type = case when roles.position > admin.position then 'admin'::user_role else 'member'::user_role end
+                from roles, (
+                    select roles.position from group_roles
+                    join roles on group_roles.role_id = roles.id
+                    where group_roles.group_id = $1
+                    and group_roles.role_type = 'admin'
+                    and not group_roles.custom
+                ) admin
+                where group_roles.role_id = roles.id
+                and group_roles.group_id = $1
+                and group_roles.custom
+        "#,
+        group_id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -15,6 +15,20 @@ pub enum RoleError {
     RoleChangeRejection,
     #[error("Invalid role name")]
     RoleParseError,
+    #[error("Insufficient privileges")]
+    InsufficientPrivileges,
+    #[error("The owner role can only be handed over by an ownership transfer")]
+    OwnershipTransferRequired,
+    #[error("A role can't have higher privileges than the role above it")]
+    HierarchyViolation,
+    #[error("Role name has to be between 1 and 32 characters long")]
+    InvalidRoleName,
+    #[error("Role color has to be a hex color like #3498db")]
+    InvalidRoleColor,
+    #[error("Too many custom roles in the group")]
+    TooManyRoles,
+    #[error("Built-in roles can't be renamed or deleted")]
+    BuiltInRole,
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
 }
@@ -27,6 +41,13 @@ impl IntoResponse for RoleError {
             RoleError::RoleNotFound => StatusCode::BAD_REQUEST,
             RoleError::RoleChangeRejection => StatusCode::BAD_REQUEST,
             RoleError::RoleParseError => StatusCode::BAD_REQUEST,
+            RoleError::InsufficientPrivileges => StatusCode::FORBIDDEN,
+            RoleError::OwnershipTransferRequired => StatusCode::BAD_REQUEST,
+            RoleError::HierarchyViolation => StatusCode::BAD_REQUEST,
+            RoleError::InvalidRoleName => StatusCode::BAD_REQUEST,
+            RoleError::InvalidRoleColor => StatusCode::BAD_REQUEST,
+            RoleError::TooManyRoles => StatusCode::BAD_REQUEST,
+            RoleError::BuiltInRole => StatusCode::BAD_REQUEST,
             RoleError::Unexpected(e) => {
                 tracing::error!("Internal server error: {e:?}");
                 StatusCode::INTERNAL_SERVER_ERROR
@@ -1,71 +1,466 @@
-﻿pub mod errors;
+pub mod custom;
+pub mod errors;
 pub mod models;
 pub mod privileges;
 
-use sqlx::{query, PgPool, Acquire, Postgres};
+use std::collections::HashMap;
+
+use anyhow::Context;
+use sqlx::{query, query_as, Executor, PgConnection, PgPool, Acquire, Postgres};
 use uuid::Uuid;
 
-use self::{errors::RoleError, models::{PrivilegeInterpretationData, GroupRolePrivileges, Role, PrivilegeChangeData, UserRoleChangeData}, privileges::{QueryPrivilege, Privilege, Privileges}};
+use crate::utils::chat::{create_system_message, get_group_nickname, models::{GroupUserMessage, MemberRole, RolePrivilege, SystemEvent}};
+use crate::utils::groups::audit::{record_audit_entry, AuditEntry};
+use crate::utils::groups::models::AuditAction;
+
+use self::{errors::RoleError, models::{Gate, GroupPrivilegesChangeData, GroupRole, GroupRoleModel, GroupRoles, GroupRolePrivileges, Role, RolePosition, RoleRef, PrivilegeChangeData, UserRoleChangeData}, privileges::{CanManageRoles, QueryPrivilege, Privilege, Privileges}};
 
+/// Privilege needed to change roles of members and the roles themselves
+const MANAGE_ROLES: Privilege = Privilege::CanManageRoles(CanManageRoles::Yes);
+
+/// Changes a privilege of the role and records it in the group history.
+///
+/// The user has to outrank the role according to the gate, owner privileges can't be changed.
+/// Roles of the group stay locked until the commit, so concurrent changes are applied one by one,
+/// and the value is corrected to stay between the locked privileges of the roles right below and above.
+/// The applied value is written back to `data`, in-memory privileges should be updated with it only after this succeeds.
 pub async fn single_set_group_role_privileges<'c>(
     conn: impl Acquire<'c, Database = Postgres> + std::marker::Send,
-    data: &PrivilegeChangeData
-) -> Result<(), RoleError> {
-    match data.value {
-        Privilege::CanInvite(x) => x.set_privilege(conn, data).await?,
-        Privilege::CanSendMessages(x) => x.set_privilege(conn, data).await?,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    data: &mut PrivilegeChangeData
+) -> Result<GroupUserMessage, RoleError> {
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, &data.group_id, MANAGE_ROLES).await?;
+
+    let roles = locked_group_roles(&mut transaction, &data.group_id).await?;
+    let role = roles.get(&data.role).ok_or(RoleError::RoleNotFound)?;
+    // editing a role is never editing oneself, so the role id stands in for the target
+    if role.kind == Some(Role::Owner) || !gate.verify(user_role, role.place(), (*user_id, role.id)) {
+        return Err(RoleError::InsufficientPrivileges);
+    }
+
+    // the role is referred to the same way in memory, however the user referred to it
+    data.role = role.role();
+    data.maintain_hierarchy(&roles)?;
+
+    let previous = role.privileges.0.get(&data.value).copied();
+
+    data.value.set_privilege(&mut transaction, data).await?;
+
+    let moderator = get_group_nickname(&mut transaction, user_id, &data.group_id)
+        .await
+        .context("Failed to fetch moderator nickname")?;
+    let event = SystemEvent::PrivilegeChanged {
+        role: data.role,
+        role_name: role.custom_name(),
+        privilege: data.value,
+        changed_by: *user_id,
+        moderator,
     };
+    let message = create_system_message(&mut transaction, &data.group_id, event)
+        .await
+        .context("Failed to record privilege change")?;
+
+    let entry = AuditEntry::new(AuditAction::PrivilegeChanged)
+        .target(data.role)
+        .before(previous)
+        .after(data.value);
+    record_audit_entry(&mut transaction, &data.group_id, user_id, entry)
+        .await
+        .context("Failed to audit privilege change")?;
 
-    Ok(())
+    transaction.commit().await?;
+
+    Ok(message)
 }
 
-pub async fn get_group_role_privileges(pool: &PgPool, group_id: Uuid) -> Result<GroupRolePrivileges, RoleError> {
-    let query_res = query!(
+/// Changes privileges of several roles at once and records them as a single system message.
+///
+/// The desired privileges are merged into the current ones and the result has to keep the role hierarchy.
+/// The user has to outrank every role whose privileges change, owner privileges can't be changed.
+/// Returns privileges of the group roles after the change, the message is `None` when nothing changed.
+pub async fn set_group_role_privileges<'c>(
+    conn: impl Acquire<'c, Database = Postgres> + std::marker::Send,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    data: &GroupPrivilegesChangeData
+) -> Result<(GroupRolePrivileges, Option<GroupUserMessage>), RoleError> {
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, &data.group_id, MANAGE_ROLES).await?;
+
+    let current = locked_group_roles(&mut transaction, &data.group_id).await?;
+    let mut desired = current.clone();
+    desired.merge(&data.privileges)?;
+    desired.verify_hierarchy()?;
+
+    // roles are ordered by position, so lower roles come first
+    let mut changes = Vec::new();
+    for (previous, role) in current.0.iter().zip(desired.0.iter()) {
+        for privilege in previous.privileges.changes(&role.privileges) {
+            if !gate.verify(user_role, previous.place(), (*user_id, previous.id)) {
+                return Err(RoleError::InsufficientPrivileges);
+            }
+            let change = RolePrivilege { role: role.role(), role_name: role.custom_name(), privilege };
+            changes.push((change, previous.privileges.0.get(&privilege).copied()));
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok((desired.privileges(), None));
+    }
+
+    for (change, previous) in changes.iter() {
+        let change_data = PrivilegeChangeData::new(data.group_id, change.role, change.privilege);
+        change.privilege.set_privilege(&mut transaction, &change_data).await?;
+
+        let entry = AuditEntry::new(AuditAction::PrivilegeChanged)
+            .target(change.role)
+            .before(previous)
+            .after(change.privilege);
+        record_audit_entry(&mut transaction, &data.group_id, user_id, entry)
+            .await
+            .context("Failed to audit privilege change")?;
+    }
+
+    let moderator = get_group_nickname(&mut transaction, user_id, &data.group_id)
+        .await
+        .context("Failed to fetch moderator nickname")?;
+    let event = SystemEvent::PrivilegesChanged {
+        changes: changes.into_iter().map(|(change, _)| change).collect(),
+        changed_by: *user_id,
+        moderator,
+    };
+    let message = create_system_message(&mut transaction, &data.group_id, event)
+        .await
+        .context("Failed to record privilege changes")?;
+
+    transaction.commit().await?;
+
+    Ok((desired.privileges(), Some(message)))
+}
+
+/// Role of the group member and its position, `None` when the user is not in the group
+pub(crate) async fn member_role<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Option<RolePosition>, RoleError> {
+    let res = query!(
         r#"
-            select group_roles.role_type as "role_type: Role", roles.can_invite, roles.can_send_messages from
-                group_roles join roles on group_roles.role_id = roles.id
-                where group_roles.group_id = $1
-                and group_roles.role_type in ('member', 'admin')
-                order by group_roles.role_type
+            select group_roles.role_id, group_roles.role_type as "role_type: Role", group_roles.custom, roles.position from group_users
+            join group_roles on group_users.role_id = group_roles.role_id
+            join roles on group_roles.role_id = roles.id
+            where group_users.group_id = $1
+            and group_users.user_id = $2
+        "#,
+        group_id,
+        user_id,
+    )
+    .fetch_optional(exe)
+    .await?;
+
+    Ok(res.map(|res| RolePosition {
+        role: RoleRef::new(res.role_id, (!res.custom).then_some(res.role_type)),
+        position: res.position,
+    }))
+}
+
+/// Role of the group member together with its privileges, `None` when the user is not in the group
+pub async fn member_group_role<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Option<GroupRole>, RoleError> {
+    let res = query_as!(
+        GroupRoleModel,
+        r#"
+            select roles.id, group_roles.role_type as "role_type: Role", group_roles.custom,
+            roles.name, roles.color, roles.position, roles.can_invite, roles.can_send_messages, roles.can_export,
+            roles.can_kick, roles.can_ban, roles.can_mute, roles.can_delete_messages, roles.can_pin,
+            roles.can_manage_invitations, roles.can_edit_settings, roles.can_manage_roles, roles.can_manage_nicknames
+                from group_users
+                join group_roles on group_users.role_id = group_roles.role_id
+                join roles on group_roles.role_id = roles.id
+                where group_users.group_id = $1
+                and group_users.user_id = $2
         "#,
-        group_id
+        group_id,
+        user_id,
     )
-    .fetch_all(pool)
+    .fetch_optional(exe)
     .await?;
 
-    let mut res = GroupRolePrivileges::new();
-    for role_data in query_res {
-        res.0.insert(role_data.role_type, Privileges::try_from(PrivilegeInterpretationData {
-            can_invite: role_data.can_invite,
-            can_send_messages: role_data.can_send_messages,
-        })?);
+    res.map(GroupRole::try_from).transpose()
+}
+
+/// Position of the member's role, as long as the role grants `privilege`
+async fn privileged_member_role<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    privilege: Privilege,
+) -> Result<RolePosition, RoleError> {
+    match member_group_role(exe, user_id, group_id).await? {
+        Some(role) if role.privileges.satisfies(privilege) => Ok(role.place()),
+        _ => Err(RoleError::InsufficientPrivileges),
     }
+}
 
-    Ok(res)
+/// Role of the group member and its position in the hierarchy
+pub async fn get_member_role(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Result<RolePosition, RoleError> {
+    member_role(pool, user_id, group_id).await?.ok_or(RoleError::UserNotFound)
 }
 
-pub async fn single_set_group_user_role<'c>(conn: impl Acquire<'c, Database = Postgres>, data: &UserRoleChangeData) -> Result<(), RoleError> {
+/// Roles of the group locked until the end of the transaction
+pub(crate) async fn locked_group_roles(conn: &mut PgConnection, group_id: &Uuid) -> Result<GroupRoles, RoleError> {
+    // always locked in the same order
+    let roles = query_as!(
+        GroupRoleModel,
+        r#"
+            select roles.id, group_roles.role_type as "role_type: Role", group_roles.custom,
+            roles.name, roles.color, roles.position, roles.can_invite, roles.can_send_messages, roles.can_export,
+            roles.can_kick, roles.can_ban, roles.can_mute, roles.can_delete_messages, roles.can_pin,
+            roles.can_manage_invitations, roles.can_edit_settings, roles.can_manage_roles, roles.can_manage_nicknames
+                from group_roles join roles on group_roles.role_id = roles.id
+                where group_roles.group_id = $1
+                order by roles.position
+                for update of roles
+        "#,
+        group_id,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(GroupRoles(roles.into_iter().map(GroupRole::try_from).collect::<Result<_, _>>()?))
+}
+
+/// Roles of the group from the bottom of the hierarchy to the top
+pub async fn get_group_roles<'c>(exe: impl Executor<'c, Database = Postgres>, group_id: &Uuid) -> Result<GroupRoles, RoleError> {
+    let roles = query_as!(
+        GroupRoleModel,
+        r#"
+            select roles.id, group_roles.role_type as "role_type: Role", group_roles.custom,
+            roles.name, roles.color, roles.position, roles.can_invite, roles.can_send_messages, roles.can_export,
+            roles.can_kick, roles.can_ban, roles.can_mute, roles.can_delete_messages, roles.can_pin,
+            roles.can_manage_invitations, roles.can_edit_settings, roles.can_manage_roles, roles.can_manage_nicknames
+                from group_roles join roles on group_roles.role_id = roles.id
+                where group_roles.group_id = $1
+                order by roles.position
+        "#,
+        group_id,
+    )
+    .fetch_all(exe)
+    .await?;
+
+    Ok(GroupRoles(roles.into_iter().map(GroupRole::try_from).collect::<Result<_, _>>()?))
+}
+
+pub async fn get_group_role_privileges(pool: &PgPool, group_id: Uuid) -> Result<GroupRolePrivileges, RoleError> {
+    Ok(get_group_roles(pool, &group_id).await?.privileges())
+}
+
+/// Changes the role of a group member and records it in the group history.
+///
+/// The user has to outrank both the current and the new role of the member according to the gate.
+/// The owner role is never granted or taken away here, it only moves through an ownership transfer.
+pub async fn single_set_group_user_role<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    data: &UserRoleChangeData
+) -> Result<GroupUserMessage, RoleError> {
     let mut transaction = conn.begin().await?;
-    
-    let _res = query!(
+
+    let user_role = privileged_member_role(&mut transaction, user_id, &data.group_id, MANAGE_ROLES).await?;
+
+    let roles = get_group_roles(&mut transaction, &data.group_id).await?;
+    let role = roles.get(&data.value).ok_or(RoleError::RoleNotFound)?;
+
+    let previous_id = query!(
+        r#"
+            select role_id from group_users
+            where group_id = $1
+            and user_id = $2
+            for update
+        "#,
+        data.group_id,
+        data.user_id,
+    )
+    .fetch_optional(&mut transaction)
+    .await?
+    .ok_or(RoleError::UserNotFound)?
+    .role_id;
+    let previous = roles.get(&RoleRef::Id(previous_id)).ok_or(RoleError::RoleNotFound)?;
+
+    if previous.kind == Some(Role::Owner) || role.kind == Some(Role::Owner) {
+        return Err(RoleError::OwnershipTransferRequired);
+    }
+    let info = (*user_id, data.user_id);
+    if !gate.verify(user_role, previous.place(), info) || !gate.verify(user_role, role.place(), info) {
+        return Err(RoleError::InsufficientPrivileges);
+    }
+
+    query!(
         r#"
             update group_users
-                set role_id = group_roles.role_id
-                from group_roles
-                where group_roles.group_id = $1
-                and group_users.user_id = $2
-                and group_roles.role_type = $3
+                set role_id = $3
+                where group_id = $1
+                and user_id = $2
         "#,
         data.group_id,
         data.user_id,
-        data.value as Role,
+        role.id,
     )
     .execute(&mut transaction)
     .await?;
 
+    let nickname = get_group_nickname(&mut transaction, &data.user_id, &data.group_id)
+        .await
+        .context("Failed to fetch member nickname")?;
+    let moderator = get_group_nickname(&mut transaction, user_id, &data.group_id)
+        .await
+        .context("Failed to fetch moderator nickname")?;
+    let event = SystemEvent::RoleChanged {
+        user_id: data.user_id,
+        nickname,
+        role: role.role(),
+        role_name: role.custom_name(),
+        changed_by: *user_id,
+        moderator,
+    };
+    let message = create_system_message(&mut transaction, &data.group_id, event)
+        .await
+        .context("Failed to record role change")?;
+
+    let entry = AuditEntry::new(AuditAction::RoleChanged)
+        .target_user(data.user_id)
+        .target("role")
+        .before(previous.role())
+        .after(role.role());
+    record_audit_entry(&mut transaction, &data.group_id, user_id, entry)
+        .await
+        .context("Failed to audit role change")?;
+
     transaction.commit().await?;
 
-    Ok(())
+    Ok(message)
+}
+
+/// Changes roles of several members of one group at once and records them as a single system message.
+///
+/// Every change is checked the same way as in `single_set_group_user_role` before any is applied,
+/// a member can appear only once. Returns the changes that were applied, members who already had the role are left out.
+pub async fn set_group_user_roles<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    gate: &Gate<(Uuid, Uuid)>,
+    user_id: &Uuid,
+    data: &[UserRoleChangeData]
+) -> Result<(Vec<UserRoleChangeData>, Option<GroupUserMessage>), RoleError> {
+    let Some(group_id) = data.first().map(|change| change.group_id) else {
+        return Ok((Vec::new(), None));
+    };
+
+    let mut targets: Vec<Uuid> = data.iter().map(|change| change.user_id).collect();
+    targets.sort();
+    targets.dedup();
+    if targets.len() != data.len() || data.iter().any(|change| change.group_id != group_id) {
+        return Err(RoleError::RoleChangeRejection);
+    }
+
+    let mut transaction = conn.begin().await?;
+
+    let user_role = privileged_member_role(&mut transaction, user_id, &group_id, MANAGE_ROLES).await?;
+
+    let roles = get_group_roles(&mut transaction, &group_id).await?;
+
+    // locked in the order of user ids
+    let previous: HashMap<Uuid, Uuid> = query!(
+        r#"
+            select user_id, role_id from group_users
+            where group_id = $1
+            and user_id = any($2)
+            order by user_id
+            for update
+        "#,
+        group_id,
+        &targets,
+    )
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .map(|res| (res.user_id, res.role_id))
+    .collect();
+
+    let mut changes = Vec::new();
+    for change in data {
+        let previous_id = *previous.get(&change.user_id).ok_or(RoleError::UserNotFound)?;
+        let previous = roles.get(&RoleRef::Id(previous_id)).ok_or(RoleError::RoleNotFound)?;
+        let role = roles.get(&change.value).ok_or(RoleError::RoleNotFound)?;
+        if previous.kind == Some(Role::Owner) || role.kind == Some(Role::Owner) {
+            return Err(RoleError::OwnershipTransferRequired);
+        }
+        let info = (*user_id, change.user_id);
+        if !gate.verify(user_role, previous.place(), info) || !gate.verify(user_role, role.place(), info) {
+            return Err(RoleError::InsufficientPrivileges);
+        }
+        if previous.id != role.id {
+            changes.push((UserRoleChangeData::new(group_id, change.user_id, role.role()), previous, role));
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok((Vec::new(), None));
+    }
+
+    let mut members = Vec::new();
+    for (change, previous, role) in changes.iter() {
+        query!(
+            r#"
+                update group_users
+                    set role_id = $3
+                    where group_id = $1
+                    and user_id = $2
+            "#,
+            group_id,
+            change.user_id,
+            role.id,
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        let entry = AuditEntry::new(AuditAction::RoleChanged)
+            .target_user(change.user_id)
+            .target("role")
+            .before(previous.role())
+            .after(change.value);
+        record_audit_entry(&mut transaction, &group_id, user_id, entry)
+            .await
+            .context("Failed to audit role change")?;
+
+        let nickname = get_group_nickname(&mut transaction, &change.user_id, &group_id)
+            .await
+            .context("Failed to fetch member nickname")?;
+        members.push(MemberRole { user_id: change.user_id, nickname, role: change.value, role_name: role.custom_name() });
+    }
+
+    let moderator = get_group_nickname(&mut transaction, user_id, &group_id)
+        .await
+        .context("Failed to fetch moderator nickname")?;
+    let event = SystemEvent::RolesChanged {
+        members,
+        changed_by: *user_id,
+        moderator,
+    };
+    let message = create_system_message(&mut transaction, &group_id, event)
+        .await
+        .context("Failed to record role changes")?;
+
+    transaction.commit().await?;
+
+    Ok((changes.into_iter().map(|(change, _, _)| change).collect(), Some(message)))
 }
 
 pub async fn get_user_role(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Result<Role, RoleError> {
@@ -82,8 +477,18 @@ pub async fn get_user_role(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Re
         user_id,
         group_id,
     )
-    .fetch_one(pool)
-    .await?;
+    .fetch_optional(pool)
+    .await?
+    .ok_or(RoleError::UserNotFound)?;
 
     Ok(res.role)
 }
+
+/// Privileges of the user's role, owners always get the maximal ones
+pub async fn get_user_privileges(pool: &PgPool, user_id: &Uuid, group_id: &Uuid) -> Result<Privileges, RoleError> {
+    let role = member_group_role(pool, user_id, group_id)
+        .await?
+        .ok_or(RoleError::UserNotFound)?;
+
+    Ok(role.privileges)
+}
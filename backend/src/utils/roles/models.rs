@@ -3,12 +3,18 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     sync::Arc,
-    hash::Hash, cmp::Ordering,
+    cmp::Ordering,
 };
+use config::ConfigError;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use super::{errors::RoleError, privileges::{Privileges, Privilege, CanInvite, CanSendMessages}};
+use crate::configuration::{GateSettings, GatesSettings};
+
+use super::{errors::RoleError, privileges::{
+    Privileges, Privilege, CanInvite, CanSendMessages, CanExport, CanKick, CanBan, CanMute, CanDeleteMessages, CanPin,
+    CanManageInvitations, CanEditSettings, CanManageRoles, CanManageNicknames,
+}};
 
 #[derive(
     sqlx::Type, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy,
@@ -21,67 +27,278 @@ pub enum Role {
     Owner,
 }
 
-impl Role {
-    fn increment(self) -> Option<Self> {
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Role::Member => "member",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Built-in role of a group or a custom role created in it
+#[derive(Deserialize, Serialize, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[serde(untagged)]
+pub enum RoleRef {
+    Kind(Role),
+    Id(Uuid),
+}
+
+impl RoleRef {
+    pub(crate) fn new(id: Uuid, kind: Option<Role>) -> Self {
+        match kind {
+            Some(kind) => RoleRef::Kind(kind),
+            None => RoleRef::Id(id),
+        }
+    }
+
+    pub fn kind(&self) -> Option<Role> {
         match self {
-            Role::Member => Some(Role::Admin),
-            Role::Admin => Some(Role::Owner),
-            Role::Owner => None,
+            RoleRef::Kind(kind) => Some(*kind),
+            RoleRef::Id(_) => None,
         }
     }
 
-    fn decrement(self) -> Option<Self> {
+    pub fn id(&self) -> Option<Uuid> {
         match self {
-            Role::Member => None,
-            Role::Admin => Some(Role::Member),
-            Role::Owner => Some(Role::Admin),
+            RoleRef::Kind(_) => None,
+            RoleRef::Id(id) => Some(*id),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
-pub struct GroupRolePrivileges(pub HashMap<Role, Privileges>);
+impl From<Role> for RoleRef {
+    fn from(val: Role) -> Self {
+        RoleRef::Kind(val)
+    }
+}
 
-#[derive(Clone)]
-pub struct SocketGroupRolePrivileges(pub HashMap<Role, Arc<RwLock<Privileges>>>);
+impl From<Uuid> for RoleRef {
+    fn from(val: Uuid) -> Self {
+        RoleRef::Id(val)
+    }
+}
 
-impl SocketGroupRolePrivileges {
-    pub async fn get_privileges(&self, role: Role) -> Option<Privileges> {
-        if role == Role::Owner {
-            Some(Privileges::max())
-        } else {
-            Some(self.0.get(&role)?.read().await.clone())
+impl std::fmt::Display for RoleRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoleRef::Kind(kind) => kind.fmt(f),
+            RoleRef::Id(id) => id.fmt(f),
         }
     }
+}
 
-    pub async fn get_privilege(&self, role: Role, val: Privilege) -> Option<Privilege> {
-        self.0.get(&role)?.read().await.0.get(&val).copied()
+/// Place of a role in the group hierarchy, higher positions outrank lower ones
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct RolePosition {
+    pub role: RoleRef,
+    pub position: i32,
+}
+
+/// Role of a group with its place in the hierarchy.
+///
+/// The member role is always at the bottom and the owner role at the top.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupRole {
+    pub id: Uuid,
+    /// Built-in role, `None` for custom roles
+    pub kind: Option<Role>,
+    pub name: String,
+    pub color: String,
+    pub position: i32,
+    /// Built-in role it counts as, custom roles above the admin role count as admins
+    pub rank: Role,
+    pub privileges: Privileges,
+}
+
+impl GroupRole {
+    pub fn role(&self) -> RoleRef {
+        RoleRef::new(self.id, self.kind)
     }
 
-    pub async fn verify_with_privilege(&self, role: Role, min_val: Privilege) -> Result<bool, RoleError> {
-        let cmp_res = self
-            .get_privilege(role, min_val)
-            .await
-            .ok_or(RoleError::Unexpected(anyhow!("No privilege found")))?
-            .partial_cmp(&min_val);
-        Ok(cmp_res == Some(Ordering::Greater) && cmp_res == Some(Ordering::Equal))
+    pub fn place(&self) -> RolePosition {
+        RolePosition { role: self.role(), position: self.position }
+    }
+
+    /// Name shown in system messages, built-in roles are described by their kind instead
+    pub fn custom_name(&self) -> Option<String> {
+        match self.kind {
+            Some(_) => None,
+            None => Some(self.name.clone()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GroupRoleModel {
+    pub id: Uuid,
+    pub role_type: Role,
+    pub custom: bool,
+    pub name: String,
+    pub color: String,
+    pub position: i32,
+    pub can_invite: bool,
+    pub can_send_messages: i32,
+    pub can_export: bool,
+    pub can_kick: bool,
+    pub can_ban: bool,
+    pub can_mute: bool,
+    pub can_delete_messages: bool,
+    pub can_pin: bool,
+    pub can_manage_invitations: bool,
+    pub can_edit_settings: bool,
+    pub can_manage_roles: bool,
+    pub can_manage_nicknames: bool,
+}
+
+impl TryFrom<GroupRoleModel> for GroupRole {
+    type Error = RoleError;
+
+    fn try_from(val: GroupRoleModel) -> Result<Self, Self::Error> {
+        let privileges = match (val.custom, val.role_type) {
+            (false, Role::Owner) => Privileges::max(),
+            _ => Privileges::try_from(PrivilegeInterpretationData {
+                can_invite: val.can_invite,
+                can_send_messages: val.can_send_messages,
+                can_export: val.can_export,
+                can_kick: val.can_kick,
+                can_ban: val.can_ban,
+                can_mute: val.can_mute,
+                can_delete_messages: val.can_delete_messages,
+                can_pin: val.can_pin,
+                can_manage_invitations: val.can_manage_invitations,
+                can_edit_settings: val.can_edit_settings,
+                can_manage_roles: val.can_manage_roles,
+                can_manage_nicknames: val.can_manage_nicknames,
+            })?,
+        };
+
+        Ok(Self {
+            id: val.id,
+            kind: (!val.custom).then_some(val.role_type),
+            name: val.name,
+            color: val.color,
+            position: val.position,
+            rank: val.role_type,
+            privileges,
+        })
+    }
+}
+
+/// Roles of a group from the bottom of the hierarchy to the top
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GroupRoles(pub Vec<GroupRole>);
+
+impl GroupRoles {
+    pub fn get(&self, role: &RoleRef) -> Option<&GroupRole> {
+        self.0.iter().find(|group_role| match role {
+            RoleRef::Kind(kind) => group_role.kind == Some(*kind),
+            RoleRef::Id(id) => group_role.id == *id,
+        })
+    }
+
+    pub fn get_mut(&mut self, role: &RoleRef) -> Option<&mut GroupRole> {
+        self.0.iter_mut().find(|group_role| match role {
+            RoleRef::Kind(kind) => group_role.kind == Some(*kind),
+            RoleRef::Id(id) => group_role.id == *id,
+        })
+    }
+
+    /// Roles whose privileges can be changed, the owner always has all of them
+    fn changeable(&self) -> impl Iterator<Item = &GroupRole> {
+        self.0.iter().filter(|role| role.kind != Some(Role::Owner))
+    }
+
+    pub fn privileges(&self) -> GroupRolePrivileges {
+        GroupRolePrivileges(
+            self.changeable()
+                .map(|role| (role.role(), role.privileges.clone()))
+                .collect(),
+        )
+    }
+
+    /// Merges the privileges into the ones of the roles, owner privileges can't be changed
+    pub fn merge(&mut self, privileges: &GroupRolePrivileges) -> Result<(), RoleError> {
+        for (role, privileges) in privileges.0.iter() {
+            let target = self.get_mut(role).ok_or(RoleError::RoleNotFound)?;
+            if target.kind == Some(Role::Owner) {
+                return Err(RoleError::InsufficientPrivileges);
+            }
+            for privilege in privileges.0.iter() {
+                target.privileges.0.replace(*privilege);
+            }
+        }
+        Ok(())
+    }
+
+    /// Neighbours of the role whose privileges bound the ones of the role from below and above
+    fn bounds(&self, role: &RoleRef) -> Result<(Option<&GroupRole>, Option<&GroupRole>), RoleError> {
+        let roles: Vec<&GroupRole> = self.changeable().collect();
+        let target = self.get(role).ok_or(RoleError::RoleNotFound)?;
+        let index = roles
+            .iter()
+            .position(|role| role.id == target.id)
+            .ok_or(RoleError::RoleNotFound)?;
+
+        let lower = index.checked_sub(1).map(|index| roles[index]);
+        let higher = roles.get(index + 1).copied();
+        Ok((lower, higher))
+    }
+
+    /// Checks that no role holds a privilege above the one of the role above it
+    pub fn verify_hierarchy(&self) -> Result<(), RoleError> {
+        let roles: Vec<&GroupRole> = self.changeable().collect();
+        for pair in roles.windows(2) {
+            for privilege in pair[0].privileges.0.iter() {
+                let Some(higher) = pair[1].privileges.0.get(privilege) else {
+                    continue
+                };
+                if privilege.partial_cmp(higher) == Some(Ordering::Greater) {
+                    return Err(RoleError::HierarchyViolation);
+                }
+            }
+        }
+        Ok(())
     }
 }
 
+/// Privileges of the roles that can be changed, keyed by the role
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct GroupRolePrivileges(pub HashMap<RoleRef, Privileges>);
+
 impl GroupRolePrivileges {
     pub fn new() -> Self {
         Self(HashMap::new())
     }
 }
 
-impl From<GroupRolePrivileges> for SocketGroupRolePrivileges {
-    fn from(val: GroupRolePrivileges) -> Self {
-        SocketGroupRolePrivileges(
-            val.0
-                .into_iter()
-                .map(|(k, v)| (k, Arc::new(RwLock::new(v))))
-                .collect::<HashMap<_, _>>(),
-        )
+/// Roles of the group shared by connections of the group
+#[derive(Clone)]
+pub struct SocketGroupRolePrivileges(pub Arc<RwLock<GroupRoles>>);
+
+impl SocketGroupRolePrivileges {
+    pub async fn get_privileges(&self, role: &RoleRef) -> Option<Privileges> {
+        Some(self.0.read().await.get(role)?.privileges.clone())
+    }
+
+    pub async fn get_privilege(&self, role: &RoleRef, val: Privilege) -> Option<Privilege> {
+        self.0.read().await.get(role)?.privileges.0.get(&val).copied()
+    }
+
+    pub async fn verify_with_privilege(&self, role: &RoleRef, min_val: Privilege) -> Result<bool, RoleError> {
+        let privileges = self
+            .get_privileges(role)
+            .await
+            .ok_or(RoleError::Unexpected(anyhow!("No privilege found")))?;
+        Ok(privileges.satisfies(min_val))
+    }
+}
+
+impl From<GroupRoles> for SocketGroupRolePrivileges {
+    fn from(val: GroupRoles) -> Self {
+        SocketGroupRolePrivileges(Arc::new(RwLock::new(val)))
     }
 }
 
@@ -105,15 +322,20 @@ impl Privilege {
 
 impl PartialOrd for Privilege {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match self {
-            Privilege::CanInvite(x) => match other {
-                Privilege::CanInvite(y) => x.partial_cmp(y),
-                _ => None,
-            },
-            Privilege::CanSendMessages(x) => match other {
-                Privilege::CanSendMessages(y) => x.partial_cmp(y),
-                _ => None,
-            },
+        match (self, other) {
+            (Privilege::CanInvite(x), Privilege::CanInvite(y)) => x.partial_cmp(y),
+            (Privilege::CanSendMessages(x), Privilege::CanSendMessages(y)) => x.partial_cmp(y),
+            (Privilege::CanExport(x), Privilege::CanExport(y)) => x.partial_cmp(y),
+            (Privilege::CanKick(x), Privilege::CanKick(y)) => x.partial_cmp(y),
+            (Privilege::CanBan(x), Privilege::CanBan(y)) => x.partial_cmp(y),
+            (Privilege::CanMute(x), Privilege::CanMute(y)) => x.partial_cmp(y),
+            (Privilege::CanDeleteMessages(x), Privilege::CanDeleteMessages(y)) => x.partial_cmp(y),
+            (Privilege::CanPin(x), Privilege::CanPin(y)) => x.partial_cmp(y),
+            (Privilege::CanManageInvitations(x), Privilege::CanManageInvitations(y)) => x.partial_cmp(y),
+            (Privilege::CanEditSettings(x), Privilege::CanEditSettings(y)) => x.partial_cmp(y),
+            (Privilege::CanManageRoles(x), Privilege::CanManageRoles(y)) => x.partial_cmp(y),
+            (Privilege::CanManageNicknames(x), Privilege::CanManageNicknames(y)) => x.partial_cmp(y),
+            _ => None,
         }
     }
 }
@@ -121,81 +343,112 @@ impl PartialOrd for Privilege {
 #[derive(Deserialize, Serialize, Debug, PartialEq)]
 pub struct PrivilegeChangeData {
     pub group_id: Uuid,
-    pub role: Role,
+    pub role: RoleRef,
     pub value: Privilege,
 }
 
 impl PrivilegeChangeData {
-    pub fn new(group_id: Uuid, role: Role, value: Privilege) -> Self {
-        Self { group_id, role, value }
+    pub fn new(group_id: Uuid, role: impl Into<RoleRef>, value: Privilege) -> Self {
+        Self { group_id, role: role.into(), value }
     }
 }
 
+/// Desired privileges of the group roles, roles and privileges left out keep their values
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GroupPrivilegesChangeData {
+    pub group_id: Uuid,
+    pub privileges: GroupRolePrivileges,
+}
+
 impl PrivilegeChangeData {
-    pub async fn maintain_hierarchy(
-        &mut self,
-        other: &SocketGroupRolePrivileges,
-    ) -> Result<(), RoleError> {
-        self.maintain_hierarchy_l(other).await?;
-        self.maintain_hierarchy_h(other).await?;
+    /// Corrects the value to stay between the privileges of the roles right below and above in the hierarchy
+    pub fn maintain_hierarchy(&mut self, roles: &GroupRoles) -> Result<(), RoleError> {
+        let (lower, higher) = roles.bounds(&self.role)?;
+        if let Some(lower) = lower {
+            self.raise_to(&lower.privileges)?;
+        }
+        if let Some(higher) = higher {
+            self.lower_to(&higher.privileges)?;
+        }
         Ok(())
     }
 
-    pub async fn maintain_hierarchy_l(
-        &mut self,
-        other: &SocketGroupRolePrivileges,
-    ) -> Result<(), RoleError> {
-        let Some(other_role) = self.role.decrement() else { return Ok(()) };
-
-        let other_privileges_ref = other.0.get(&other_role).ok_or(RoleError::RoleNotFound)?;
-        let other_privileges = other_privileges_ref.read().await;
-        let privilege = other_privileges.0.get(&self.value).ok_or(RoleError::Unexpected(anyhow!("Privilege not found")))?;
-        
+    /// A role never gets less than the role below it
+    fn raise_to(&mut self, other: &Privileges) -> Result<(), RoleError> {
+        let privilege = other.0.get(&self.value).ok_or(RoleError::Unexpected(anyhow!("Privilege not found")))?;
         self.value = self.value.partial_cmp_max(*privilege).ok_or(RoleError::Unexpected(anyhow!("Mismatched privileges")))?;
-
         Ok(())
     }
 
-    pub async fn maintain_hierarchy_h(
-        &mut self,
-        other: &SocketGroupRolePrivileges,
-    ) -> Result<(), RoleError> {
-        let Some(other_role) = self.role.increment() else { return Ok(()) };
-        if other_role == Role::Owner { return Ok(()) }
-
-        let other_privileges_ref = other.0.get(&other_role).ok_or(RoleError::RoleNotFound)?;
-        let other_privileges = other_privileges_ref.read().await;
-        let privilege = other_privileges.0.get(&self.value).ok_or(RoleError::Unexpected(anyhow!("Privilege not found")))?;
-
+    /// A role never gets more than the role above it
+    fn lower_to(&mut self, other: &Privileges) -> Result<(), RoleError> {
+        let privilege = other.0.get(&self.value).ok_or(RoleError::Unexpected(anyhow!("Privilege not found")))?;
         self.value = self.value.partial_cmp_min(*privilege).ok_or(RoleError::Unexpected(anyhow!("Mismatched privileges")))?;
-
         Ok(())
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Role of a group member
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct MemberRoleInfo {
+    pub user_id: Uuid,
+    pub role: RoleRef,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoleUpdate {
+    pub role: RoleRef,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserRoleChangeData {
     pub group_id: Uuid,
     pub user_id: Uuid,
-    pub value: Role,
+    pub value: RoleRef,
 }
 
 impl UserRoleChangeData {
-    pub fn new(group_id: Uuid, user_id: Uuid, value: Role) -> Self {
-        Self { group_id, user_id, value }
+    pub fn new(group_id: Uuid, user_id: Uuid, value: impl Into<RoleRef>) -> Self {
+        Self { group_id, user_id, value: value.into() }
     }
 }
 
+/// Name and color of a new custom role
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewCustomRole {
+    pub name: String,
+    pub color: String,
+}
+
+/// Changes of a custom role, fields left out keep their values
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CustomRoleUpdate {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+/// Roles between the member and the owner role, from the bottom of the hierarchy to the top
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleOrder {
+    pub roles: Vec<RoleRef>,
+}
+
 #[derive(Debug)]
 pub struct PrivilegeInterpretationData {
     pub can_invite: bool,
     pub can_send_messages: i32,
-}
-
-impl PrivilegeInterpretationData {
-    pub fn new(can_invite: bool, can_send_messages: i32) -> Self {
-        Self { can_invite, can_send_messages }
-    }
+    pub can_export: bool,
+    pub can_kick: bool,
+    pub can_ban: bool,
+    pub can_mute: bool,
+    pub can_delete_messages: bool,
+    pub can_pin: bool,
+    pub can_manage_invitations: bool,
+    pub can_edit_settings: bool,
+    pub can_manage_roles: bool,
+    pub can_manage_nicknames: bool,
 }
 
 impl TryFrom<PrivilegeInterpretationData> for Privileges {
@@ -205,56 +458,58 @@ impl TryFrom<PrivilegeInterpretationData> for Privileges {
         let mut res = Privileges::new();
         res.0.insert(Privilege::CanInvite(CanInvite::from(val.can_invite)));
         res.0.insert(Privilege::CanSendMessages(CanSendMessages::try_from(val.can_send_messages)?));
+        res.0.insert(Privilege::CanExport(CanExport::from(val.can_export)));
+        res.0.insert(Privilege::CanKick(CanKick::from(val.can_kick)));
+        res.0.insert(Privilege::CanBan(CanBan::from(val.can_ban)));
+        res.0.insert(Privilege::CanMute(CanMute::from(val.can_mute)));
+        res.0.insert(Privilege::CanDeleteMessages(CanDeleteMessages::from(val.can_delete_messages)));
+        res.0.insert(Privilege::CanPin(CanPin::from(val.can_pin)));
+        res.0.insert(Privilege::CanManageInvitations(CanManageInvitations::from(val.can_manage_invitations)));
+        res.0.insert(Privilege::CanEditSettings(CanEditSettings::from(val.can_edit_settings)));
+        res.0.insert(Privilege::CanManageRoles(CanManageRoles::from(val.can_manage_roles)));
+        res.0.insert(Privilege::CanManageNicknames(CanManageNicknames::from(val.can_manage_nicknames)));
 
         Ok(res)
     }
 }
 
+/// Check of an action a member takes against a role, passed by members whose role is above it
 #[derive(Clone)]
-pub struct Gate<T: Eq + Hash, U> {
-    pub roles: HashMap<Role, i32>,
-    pub requirements: HashMap<T, i32>,
+pub struct Gate<U> {
+    /// Positions added to built-in roles, making them harder to act against
+    pub requirements: HashMap<Role, i32>,
+    /// Decides when the member is at the same position as the role
     pub extra_condition: Option<fn(U) -> bool>,
 }
 
-impl<T: Eq + Hash, U> Gate<T, U> {
-    pub fn build() -> GateBuilder<T, U> {
+impl<U> Gate<U> {
+    pub fn build() -> GateBuilder<U> {
         GateBuilder {
-            roles: HashMap::new(),
             requirements: HashMap::new(),
             extra_condition: None,
         }
     }
 
-    pub fn verify(&self, role: Role, req: T, info: U) -> bool {
-        let Some(amount_1) = self.roles.get(&role) else {
-            return false;
-        };
-        let Some(amount_2) = self.requirements.get(&req) else {
-            return false;
-        };
-        if amount_1 > amount_2 { return true };
-        if amount_1 < amount_2 { return false };
-        let Some(function) = self.extra_condition else {
-            return false;
-        };
-        return function(info);
+    pub fn verify(&self, role: RolePosition, req: RolePosition, info: U) -> bool {
+        let extra = req.role.kind().and_then(|kind| self.requirements.get(&kind)).copied().unwrap_or(0);
+        match role.position.cmp(&(req.position + extra)) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => match self.extra_condition {
+                Some(function) => function(info),
+                None => false,
+            },
+        }
     }
 }
 
-pub struct GateBuilder<T: Eq + Hash, U> {
-    roles: HashMap<Role, i32>,
-    requirements: HashMap<T, i32>,
+pub struct GateBuilder<U> {
+    requirements: HashMap<Role, i32>,
     extra_condition: Option<fn(U) -> bool>,
 }
 
-impl<T: Eq + Hash, U> GateBuilder<T, U> {
-    pub fn role(mut self, role: Role, val: i32) -> Self {
-        self.roles.insert(role, val);
-        self
-    }
-
-    pub fn req(mut self, req: T, val: i32) -> Self {
+impl<U> GateBuilder<U> {
+    pub fn req(mut self, req: Role, val: i32) -> Self {
         self.requirements.insert(req, val);
         self
     }
@@ -264,9 +519,8 @@ impl<T: Eq + Hash, U> GateBuilder<T, U> {
         self
     }
 
-    pub fn finish(self) -> Gate<T, U> {
+    pub fn finish(self) -> Gate<U> {
         Gate {
-            roles: self.roles,
             requirements: self.requirements,
             extra_condition: self.extra_condition,
         }
@@ -276,3 +530,85 @@ impl<T: Eq + Hash, U> GateBuilder<T, U> {
 pub fn is_id_the_same(val: (Uuid, Uuid)) -> bool {
     val.0 == val.1
 }
+
+/// Role checks of the actions members take against each other, built from `GatesSettings`
+#[derive(Clone)]
+pub struct Gates {
+    /// Kicking another member, the requirement is the role of the target
+    pub kick: Gate<(Uuid, Uuid)>,
+    /// Banning a member, the requirement is the role of the target
+    pub ban: Gate<(Uuid, Uuid)>,
+    /// Muting a member, the requirement is the role of the target
+    pub mute: Gate<(Uuid, Uuid)>,
+    /// Changing roles and role privileges, the requirement is the role being changed or granted
+    pub manage_roles: Gate<(Uuid, Uuid)>,
+    /// Resetting nicknames of members, the requirement is the role of the target
+    pub nicknames: Gate<(Uuid, Uuid)>,
+}
+
+impl Gates {
+    pub fn new() -> Self {
+        Self::from_settings(&GatesSettings::default()).expect("Default gate settings are valid")
+    }
+
+    /// Fails when a gate refers to a role that doesn't exist or its requirements contradict the hierarchy
+    pub fn from_settings(settings: &GatesSettings) -> Result<Self, ConfigError> {
+        Ok(Self {
+            kick: Gate::from_settings("kick", &settings.kick)?,
+            ban: Gate::from_settings("ban", &settings.ban)?,
+            mute: Gate::from_settings("mute", &settings.mute)?,
+            manage_roles: Gate::from_settings("manage_roles", &settings.manage_roles)?,
+            nicknames: Gate::from_settings("nicknames", &settings.nicknames)?,
+        })
+    }
+}
+
+impl Gate<(Uuid, Uuid)> {
+    fn from_settings(name: &str, settings: &GateSettings) -> Result<Self, ConfigError> {
+        let mut builder = Gate::build();
+        for (role, val) in gate_requirements(name, settings)? {
+            builder = builder.req(role, val);
+        }
+        if settings.allow_self {
+            builder = builder.condition(is_id_the_same);
+        }
+        Ok(builder.finish())
+    }
+}
+
+/// Requirements of the gate by built-in role, a role without one counts as 0
+fn gate_requirements(name: &str, settings: &GateSettings) -> Result<Vec<(Role, i32)>, ConfigError> {
+    let mut requirements = Vec::new();
+    for (role, val) in settings.requirements.iter() {
+        let role = match role.as_str() {
+            "member" => Role::Member,
+            "admin" => Role::Admin,
+            "owner" => Role::Owner,
+            other => {
+                return Err(ConfigError::Message(format!("Gate `{name}` refers to unknown role `{other}`")));
+            }
+        };
+        if *val < 0 {
+            return Err(ConfigError::Message(format!("Gate `{name}` has a negative requirement for the {role} role")));
+        }
+        requirements.push((role, *val));
+    }
+
+    // a role is never easier to act against than the roles below it
+    let req = |role: Role| requirements.iter().find(|(other, _)| *other == role).map(|(_, val)| *val).unwrap_or(0);
+    for (lower, higher) in [(Role::Member, Role::Admin), (Role::Admin, Role::Owner)] {
+        if req(higher) < req(lower) {
+            return Err(ConfigError::Message(format!(
+                "Gate `{name}` has a lower requirement for the {higher} role than for the {lower} role"
+            )));
+        }
+    }
+
+    Ok(requirements)
+}
+
+impl Default for Gates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
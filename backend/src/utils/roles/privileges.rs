@@ -21,8 +21,41 @@ impl Privileges {
         Self::from([
             Privilege::CanInvite(CanInvite::Yes),
             Privilege::CanSendMessages(CanSendMessages::Yes(0)),
+            Privilege::CanExport(CanExport::Yes),
+            Privilege::CanKick(CanKick::Yes),
+            Privilege::CanBan(CanBan::Yes),
+            Privilege::CanMute(CanMute::Yes),
+            Privilege::CanDeleteMessages(CanDeleteMessages::Yes),
+            Privilege::CanPin(CanPin::Yes),
+            Privilege::CanManageInvitations(CanManageInvitations::Yes),
+            Privilege::CanEditSettings(CanEditSettings::Yes),
+            Privilege::CanManageRoles(CanManageRoles::Yes),
+            Privilege::CanManageNicknames(CanManageNicknames::Yes),
         ])
     }
+
+    /// Privileges of `other` whose values differ from the held ones
+    pub fn changes(&self, other: &Privileges) -> Vec<Privilege> {
+        other
+            .0
+            .iter()
+            .filter(|privilege| {
+                self.0.get(privilege).and_then(|held| held.partial_cmp(privilege)) != Some(Ordering::Equal)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Checks whether the held privilege of the same kind is at least `min_val`
+    pub fn satisfies(&self, min_val: Privilege) -> bool {
+        let Some(val) = self.0.get(&min_val) else {
+            return false;
+        };
+        matches!(
+            val.partial_cmp(&min_val),
+            Some(Ordering::Greater) | Some(Ordering::Equal)
+        )
+    }
 }
 
 impl<const N: usize> From<[Privilege; N]> for Privileges {
@@ -38,6 +71,85 @@ pub enum CanInvite {
     Yes,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanExport {
+    No,
+    Yes,
+}
+
+/// Removing members from the group
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanKick {
+    No,
+    Yes,
+}
+
+/// Banning users from the group and lifting their bans
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanBan {
+    No,
+    Yes,
+}
+
+/// Muting members and lifting their mutes
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanMute {
+    No,
+    Yes,
+}
+
+/// Deleting messages of other members
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanDeleteMessages {
+    No,
+    Yes,
+}
+
+/// Pinning messages
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanPin {
+    No,
+    Yes,
+}
+
+/// Listing and revoking invitations of other members
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanManageInvitations {
+    No,
+    Yes,
+}
+
+/// Changing the retention, nickname policy and content filters of the group
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanEditSettings {
+    No,
+    Yes,
+}
+
+/// Changing roles of members and the roles themselves
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanManageRoles {
+    No,
+    Yes,
+}
+
+/// Resetting nicknames of other members
+#[derive(Serialize, Deserialize, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CanManageNicknames {
+    No,
+    Yes,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
 #[serde(rename_all = "snake_case")]
 pub enum CanSendMessages {
@@ -70,10 +182,19 @@ impl Ord for CanSendMessages {
 
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, Eq)]
 #[serde(rename_all = "snake_case")]
-#[serde(untagged)]
 pub enum Privilege {
     CanInvite(CanInvite),
     CanSendMessages(CanSendMessages),
+    CanExport(CanExport),
+    CanKick(CanKick),
+    CanBan(CanBan),
+    CanMute(CanMute),
+    CanDeleteMessages(CanDeleteMessages),
+    CanPin(CanPin),
+    CanManageInvitations(CanManageInvitations),
+    CanEditSettings(CanEditSettings),
+    CanManageRoles(CanManageRoles),
+    CanManageNicknames(CanManageNicknames),
 }
 
 impl PartialEq for Privilege {
@@ -93,6 +214,30 @@ pub trait QueryPrivilege<'c> {
     async fn set_privilege(&self, conn: impl Acquire<'c, Database = Postgres> + std::marker::Send, data: &PrivilegeChangeData) -> Result<(), RoleError>;
 }
 
+#[async_trait]
+impl<'c> QueryPrivilege<'c> for Privilege {
+    async fn set_privilege(
+        &self,
+        conn: impl Acquire<'c, Database = Postgres> + std::marker::Send,
+        data: &PrivilegeChangeData
+    ) -> Result<(), RoleError> {
+        match self {
+            Privilege::CanInvite(x) => x.set_privilege(conn, data).await,
+            Privilege::CanSendMessages(x) => x.set_privilege(conn, data).await,
+            Privilege::CanExport(x) => x.set_privilege(conn, data).await,
+            Privilege::CanKick(x) => x.set_privilege(conn, data).await,
+            Privilege::CanBan(x) => x.set_privilege(conn, data).await,
+            Privilege::CanMute(x) => x.set_privilege(conn, data).await,
+            Privilege::CanDeleteMessages(x) => x.set_privilege(conn, data).await,
+            Privilege::CanPin(x) => x.set_privilege(conn, data).await,
+            Privilege::CanManageInvitations(x) => x.set_privilege(conn, data).await,
+            Privilege::CanEditSettings(x) => x.set_privilege(conn, data).await,
+            Privilege::CanManageRoles(x) => x.set_privilege(conn, data).await,
+            Privilege::CanManageNicknames(x) => x.set_privilege(conn, data).await,
+        }
+    }
+}
+
 #[async_trait]
 impl<'c> QueryPrivilege<'c> for CanInvite {
     async fn set_privilege(
@@ -112,12 +257,14 @@ impl<'c> QueryPrivilege<'c> for CanInvite {
                 update roles
                     set can_invite = $1
                     from group_roles
-                    where group_roles.group_id = $2
-                    and group_roles.role_type = $3
+                    where group_roles.role_id = roles.id
+                    and group_roles.group_id = $2
+                    and (group_roles.role_id = $3 or (not group_roles.custom and group_roles.role_type = $4))
             "#,
             val,
             data.group_id,
-            data.role as Role,
+            data.role.id(),
+            data.role.kind() as Option<Role>,
         )
         .execute(&mut transaction)
         .await?;
@@ -137,6 +284,52 @@ impl From<bool> for CanInvite {
     }
 }
 
+#[async_trait]
+impl<'c> QueryPrivilege<'c> for CanExport {
+    async fn set_privilege(
+        &self,
+        conn: impl Acquire<'c, Database = Postgres> + std::marker::Send,
+        data: &PrivilegeChangeData
+    ) -> Result<(), RoleError> {
+        let mut transaction = conn.begin().await?;
+
+        let val = match self {
+            CanExport::Yes => true,
+            CanExport::No => false,
+        };
+
+        let _res = query!(
+            r#"
+                update roles
+                    set can_export = $1
+                    from group_roles
+                    where group_roles.role_id = roles.id
+                    and group_roles.group_id = $2
+                    and (group_roles.role_id = $3 or (not group_roles.custom and group_roles.role_type = $4))
+            "#,
+            val,
+            data.group_id,
+            data.role.id(),
+            data.role.kind() as Option<Role>,
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+impl From<bool> for CanExport {
+    fn from(val: bool) -> Self {
+        match val {
+            true => CanExport::Yes,
+            false => CanExport::No,
+        }
+    }
+}
+
 #[async_trait]
 impl<'c> QueryPrivilege<'c> for CanSendMessages {
     async fn set_privilege(
@@ -156,12 +349,14 @@ impl<'c> QueryPrivilege<'c> for CanSendMessages {
                 update roles
                     set can_send_messages = $1
                     from group_roles
-                    where group_roles.group_id = $2
-                    and group_roles.role_type = $3
+                    where group_roles.role_id = roles.id
+                    and group_roles.group_id = $2
+                    and (group_roles.role_id = $3 or (not group_roles.custom and group_roles.role_type = $4))
             "#,
             val,
             data.group_id,
-            data.role as Role,
+            data.role.id(),
+            data.role.kind() as Option<Role>,
         )
         .execute(&mut transaction)
         .await?;
@@ -172,6 +367,72 @@ impl<'c> QueryPrivilege<'c> for CanSendMessages {
     }
 }
 
+/// Stores a yes/no privilege of a role, `column` is always one of the literals of `flag_privilege!`
+async fn set_flag_privilege<'c>(
+    conn: impl Acquire<'c, Database = Postgres> + std::marker::Send,
+    column: &'static str,
+    val: bool,
+    data: &PrivilegeChangeData,
+) -> Result<(), RoleError> {
+    let mut transaction = conn.begin().await?;
+
+    let _res = query(&format!(
+        r#"
+            update roles
+                set {column} = $1
+                from group_roles
+                where group_roles.role_id = roles.id
+                and group_roles.group_id = $2
+                and (group_roles.role_id = $3 or (not group_roles.custom and group_roles.role_type = $4))
+        "#
+    ))
+    .bind(val)
+    .bind(data.group_id)
+    .bind(data.role.id())
+    .bind(data.role.kind())
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Implements storing and reading a yes/no privilege kept in a boolean column of `roles`
+macro_rules! flag_privilege {
+    ($privilege:ident, $column:literal) => {
+        #[async_trait]
+        impl<'c> QueryPrivilege<'c> for $privilege {
+            async fn set_privilege(
+                &self,
+                conn: impl Acquire<'c, Database = Postgres> + std::marker::Send,
+                data: &PrivilegeChangeData
+            ) -> Result<(), RoleError> {
+                set_flag_privilege(conn, $column, *self == $privilege::Yes, data).await
+            }
+        }
+
+        impl From<bool> for $privilege {
+            fn from(val: bool) -> Self {
+                match val {
+                    true => $privilege::Yes,
+                    false => $privilege::No,
+                }
+            }
+        }
+    };
+}
+
+flag_privilege!(CanKick, "can_kick");
+flag_privilege!(CanBan, "can_ban");
+flag_privilege!(CanMute, "can_mute");
+flag_privilege!(CanDeleteMessages, "can_delete_messages");
+flag_privilege!(CanPin, "can_pin");
+flag_privilege!(CanManageInvitations, "can_manage_invitations");
+flag_privilege!(CanEditSettings, "can_edit_settings");
+flag_privilege!(CanManageRoles, "can_manage_roles");
+flag_privilege!(CanManageNicknames, "can_manage_nicknames");
+
 impl TryFrom<i32> for CanSendMessages {
     type Error = RoleError;
 
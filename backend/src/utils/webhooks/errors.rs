@@ -0,0 +1,81 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::utils::groups::errors::GroupError;
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Webhook not found")]
+    WebhookNotFound,
+    #[error("Invalid webhook token")]
+    InvalidToken,
+    #[error("Webhook has been revoked")]
+    WebhookRevoked,
+    #[error("Invalid webhook name")]
+    InvalidName,
+    #[error("Invalid webhook message")]
+    InvalidMessage,
+    #[error("Invalid webhook url")]
+    InvalidUrl,
+    #[error("Webhook url points to a private address")]
+    PrivateUrl,
+    #[error("No events selected")]
+    NoEventsSelected,
+    #[error("Webhook rate limit exceeded")]
+    RateLimited,
+    #[error("Message blocked by the group content filter")]
+    MessageBlocked,
+    #[error("User not in group")]
+    UserNotInGroup,
+    #[error("Insufficient privileges")]
+    InsufficientPrivileges,
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl IntoResponse for WebhookError {
+    fn into_response(self) -> axum::response::Response {
+        let status_code = match &self {
+            WebhookError::WebhookNotFound => StatusCode::NOT_FOUND,
+            WebhookError::InvalidToken => StatusCode::UNAUTHORIZED,
+            WebhookError::WebhookRevoked => StatusCode::GONE,
+            WebhookError::InvalidName => StatusCode::BAD_REQUEST,
+            WebhookError::InvalidMessage => StatusCode::BAD_REQUEST,
+            WebhookError::InvalidUrl => StatusCode::BAD_REQUEST,
+            WebhookError::PrivateUrl => StatusCode::BAD_REQUEST,
+            WebhookError::NoEventsSelected => StatusCode::BAD_REQUEST,
+            WebhookError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            WebhookError::MessageBlocked => StatusCode::BAD_REQUEST,
+            WebhookError::UserNotInGroup => StatusCode::FORBIDDEN,
+            WebhookError::InsufficientPrivileges => StatusCode::FORBIDDEN,
+            WebhookError::Unexpected(e) => {
+                tracing::error!("Internal server error: {e:?}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+
+        let info = match self {
+            WebhookError::Unexpected(_) => "Unexpected server error".into(),
+            _ => self.to_string(),
+        };
+
+        (status_code, Json(json!({ "error_info": info }))).into_response()
+    }
+}
+
+impl From<sqlx::Error> for WebhookError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Unexpected(anyhow::Error::from(e))
+    }
+}
+
+impl From<GroupError> for WebhookError {
+    fn from(e: GroupError) -> Self {
+        match e {
+            GroupError::UserNotInGroup => WebhookError::UserNotInGroup,
+            GroupError::InsufficientPrivileges => WebhookError::InsufficientPrivileges,
+            e => WebhookError::Unexpected(anyhow::Error::from(e)),
+        }
+    }
+}
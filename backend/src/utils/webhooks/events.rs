@@ -0,0 +1,401 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use nanoid::nanoid;
+use reqwest::Url;
+use sha2::Sha256;
+use sqlx::{query, query_as, Executor, PgPool, Postgres};
+use time::OffsetDateTime;
+use tokio::net::lookup_host;
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+use super::errors::WebhookError;
+use super::models::{
+    CreatedEventWebhook, EventDelivery, EventDeliveryModel, EventPayload, EventWebhookInfo,
+    EventWebhookInfoModel, GroupEvent, GroupEventKind, PendingDelivery,
+};
+use crate::configuration::WebhookSettings;
+use crate::modules::external_api::HttpClient;
+use crate::utils::groups::require_group_role;
+use crate::utils::roles::models::Role;
+
+pub const SIGNATURE_HEADER: &str = "x-chad-signature";
+pub const EVENT_HEADER: &str = "x-chad-event";
+pub const DELIVERY_HEADER: &str = "x-chad-delivery";
+/// Attempts after which a delivery is marked as failed
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 8;
+/// Delay before the first retry, doubled with every failed attempt
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(10);
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+/// Time a claimed delivery stays hidden from other workers
+pub const DELIVERY_LEASE: Duration = Duration::from_secs(60);
+pub const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DELIVERY_BATCH_SIZE: i64 = 50;
+pub const DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+pub const DELIVERY_LOG_PAGE_SIZE: i64 = 50;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hex encoded HMAC-SHA256 of the request body, prefixed with the algorithm name
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    (RETRY_BASE_DELAY * 2u32.pow(exponent)).min(MAX_RETRY_DELAY)
+}
+
+/// Whether requests to the address could reach the server itself or the network it runs in
+fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ipv4(ip),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80
+            }
+        },
+    }
+}
+
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        // shared address space of carrier-grade NAT, 100.64.0.0/10
+        || (first == 100 && second & 0xc0 == 64)
+}
+
+/// Fails when the host of the url is or resolves to a private address
+async fn ensure_public_target(url: &Url) -> Result<(), WebhookError> {
+    let port = url.port_or_known_default().ok_or(WebhookError::InvalidUrl)?;
+    // ipv6 hosts keep their brackets in the url
+    let host = url.host_str().ok_or(WebhookError::InvalidUrl)?.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<IpAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![ip],
+        Err(_) => lookup_host((host, port))
+            .await
+            .map_err(|_| WebhookError::InvalidUrl)?
+            .map(|addr| addr.ip())
+            .collect(),
+    };
+
+    if addresses.into_iter().any(is_private_address) {
+        return Err(WebhookError::PrivateUrl);
+    }
+
+    Ok(())
+}
+
+/// Registers an event webhook of the group.
+///
+/// Urls pointing to loopback, link-local or private addresses are refused unless the settings allow them,
+/// the host is resolved only once the owner is verified.
+pub async fn create_event_webhook(
+    pool: &PgPool,
+    settings: &WebhookSettings,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    url: &str,
+    mut events: Vec<GroupEventKind>,
+) -> Result<CreatedEventWebhook, WebhookError> {
+    let url = Url::parse(url.trim()).map_err(|_| WebhookError::InvalidUrl)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(WebhookError::InvalidUrl);
+    }
+
+    events.sort_by_key(|event| *event as u8);
+    events.dedup();
+    if events.is_empty() {
+        return Err(WebhookError::NoEventsSelected);
+    }
+
+    require_group_role(pool, user_id, group_id, Role::Owner).await?;
+
+    if !settings.allow_private_targets {
+        ensure_public_target(&url).await?;
+    }
+
+    let secret = nanoid!(32);
+    let res = query!(
+        r#"
+            insert into event_webhooks (group_id, user_id, url, secret, events)
+            values ($1, $2, $3, $4, $5)
+            returning id
+        "#,
+        group_id,
+        user_id,
+        url.as_str(),
+        secret,
+        &events as &[GroupEventKind]
+    )
+    .fetch_one(pool)
+    .await?;
+
+    debug!("User {user_id} registered event webhook {} in group {group_id}", res.id);
+
+    Ok(CreatedEventWebhook {
+        id: res.id,
+        url: url.to_string(),
+        events,
+        secret,
+    })
+}
+
+pub async fn fetch_event_webhooks(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<EventWebhookInfo>, WebhookError> {
+    require_group_role(pool, user_id, group_id, Role::Owner).await?;
+
+    let webhooks = query_as!(
+        EventWebhookInfoModel,
+        r#"
+            select id, url, events as "events: Vec<GroupEventKind>", created_at from event_webhooks
+            where group_id = $1
+            order by created_at
+        "#,
+        group_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(webhooks.into_iter().map(EventWebhookInfo::from).collect())
+}
+
+pub async fn delete_event_webhook(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    webhook_id: &Uuid,
+) -> Result<(), WebhookError> {
+    require_group_role(pool, user_id, group_id, Role::Owner).await?;
+
+    let res = query!(
+        r#"
+            delete from event_webhooks
+            where id = $1 and group_id = $2
+        "#,
+        webhook_id,
+        group_id
+    )
+    .execute(pool)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(WebhookError::WebhookNotFound);
+    }
+
+    Ok(())
+}
+
+/// Delivery log of a single webhook, newest first
+pub async fn fetch_event_deliveries(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    webhook_id: &Uuid,
+    before: Option<i32>,
+) -> Result<Vec<EventDelivery>, WebhookError> {
+    require_group_role(pool, user_id, group_id, Role::Owner).await?;
+
+    let deliveries = query_as!(
+        EventDeliveryModel,
+        r#"
+            select d.id, d.event as "event: GroupEventKind", d.payload, d.status as "status: _",
+            d.attempts, d.next_attempt_at, d.last_status_code, d.last_error, d.created_at, d.delivered_at
+            from event_deliveries d
+            join event_webhooks w on w.id = d.webhook_id
+            where d.webhook_id = $1
+            and w.group_id = $2
+            and d.id < coalesce($3, 2147483647)
+            order by d.id desc
+            limit $4
+        "#,
+        webhook_id,
+        group_id,
+        before,
+        DELIVERY_LOG_PAGE_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deliveries.into_iter().map(EventDelivery::from).collect())
+}
+
+/// Queues the event for every webhook of the group subscribed to it
+pub async fn enqueue_group_event<'c>(
+    exe: impl Executor<'c, Database = Postgres>,
+    group_id: &Uuid,
+    event: GroupEvent,
+) -> Result<u64, WebhookError> {
+    let payload = EventPayload {
+        group_id: *group_id,
+        occurred_at: OffsetDateTime::now_utc().unix_timestamp(),
+        event: &event,
+    };
+    let payload = serde_json::to_value(payload).context("Failed to serialize group event")?;
+
+    let res = query!(
+        r#"
+            insert into event_deliveries (webhook_id, event, payload)
+            select id, $2, $3 from event_webhooks
+            where group_id = $1 and $2 = any(events)
+        "#,
+        group_id,
+        event.kind() as GroupEventKind,
+        payload
+    )
+    .execute(exe)
+    .await?;
+
+    Ok(res.rows_affected())
+}
+
+/// Claims due deliveries, posts them concurrently and returns the amount of successful ones
+pub async fn deliver_pending_events(
+    pool: &PgPool,
+    client: &HttpClient,
+    batch_size: i64,
+) -> Result<u64, WebhookError> {
+    let pending = query_as!(
+        PendingDelivery,
+        r#"
+            update event_deliveries d
+            set next_attempt_at = now() + make_interval(secs => $2)
+            from event_webhooks w
+            where w.id = d.webhook_id
+            and d.id in (
+                select id from event_deliveries
+                where status = 'pending' and next_attempt_at <= now()
+                order by next_attempt_at
+                limit $1
+                for update skip locked
+            )
+            returning d.id, d.event as "event: GroupEventKind", d.payload, d.attempts, w.url, w.secret
+        "#,
+        batch_size,
+        DELIVERY_LEASE.as_secs_f64()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let results = join_all(
+        pending
+            .into_iter()
+            .map(|delivery| deliver_event(pool, client, delivery)),
+    )
+    .await;
+
+    let mut delivered = 0;
+    for res in results {
+        match res {
+            Ok(true) => delivered += 1,
+            Ok(false) => (),
+            Err(e) => error!("Failed to record event delivery: {e:?}"),
+        }
+    }
+
+    Ok(delivered)
+}
+
+async fn deliver_event(
+    pool: &PgPool,
+    client: &HttpClient,
+    delivery: PendingDelivery,
+) -> Result<bool, WebhookError> {
+    let body = delivery.payload.to_string();
+    let headers = [
+        (SIGNATURE_HEADER, sign_payload(&delivery.secret, body.as_bytes())),
+        (EVENT_HEADER, event_name(delivery.event)),
+        (DELIVERY_HEADER, delivery.id.to_string()),
+    ];
+
+    let (status_code, error) = match client
+        .post_json_with_headers(&delivery.url, &headers, body, DELIVERY_TIMEOUT)
+        .await
+    {
+        Ok(code) if (200..300).contains(&code) => (Some(code as i32), None),
+        Ok(code) => (Some(code as i32), Some(format!("Endpoint responded with {code}"))),
+        Err(e) => (None, Some(e.to_string())),
+    };
+
+    let Some(error) = error else {
+        query!(
+            r#"
+                update event_deliveries
+                set status = 'delivered', attempts = attempts + 1, last_status_code = $2,
+                last_error = null, delivered_at = now()
+                where id = $1
+            "#,
+            delivery.id,
+            status_code
+        )
+        .execute(pool)
+        .await?;
+        return Ok(true);
+    };
+
+    let attempts = delivery.attempts + 1;
+    debug!(
+        "Event delivery {} failed (attempt {attempts}): {error}",
+        delivery.id
+    );
+
+    query!(
+        r#"
+            update event_deliveries
+            set attempts = attempts + 1, last_status_code = $2, last_error = $3,
+            status = case when attempts + 1 >= $4 then 'failed'::delivery_status else 'pending'::delivery_status end,
+            next_attempt_at = now() + make_interval(secs => $5)
+            where id = $1
+        "#,
+        delivery.id,
+        status_code,
+        error,
+        MAX_DELIVERY_ATTEMPTS,
+        retry_delay(attempts).as_secs_f64()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(false)
+}
+
+fn event_name(event: GroupEventKind) -> String {
+    serde_json::to_value(event)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+pub async fn run_event_delivery_worker(pool: PgPool, client: HttpClient) {
+    let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match deliver_pending_events(&pool, &client, DELIVERY_BATCH_SIZE).await {
+            Ok(0) => (),
+            Ok(n) => info!("Event delivery worker delivered {n} events"),
+            Err(e) => error!("Event delivery worker failed to deliver events: {e:?}"),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+pub mod errors;
+pub mod events;
+pub mod models;
+
+use anyhow::Context;
+use nanoid::nanoid;
+use sha2::{Digest, Sha256};
+use sqlx::{query, query_as, PgPool};
+use subtle::ConstantTimeEq;
+use tracing::debug;
+use uuid::Uuid;
+
+use self::errors::WebhookError;
+use self::events::enqueue_group_event;
+use self::models::{CreatedWebhook, GroupEvent, WebhookInfo, WebhookInfoModel};
+use super::chat::models::{GroupUserMessage, MessageKind};
+use super::chat::MAX_MESSAGE_LENGTH;
+use super::groups::filters::{load_group_filters, record_message_flags, FilterVerdict};
+use super::groups::require_group_role;
+use super::roles::models::Role;
+
+pub const MAX_WEBHOOK_NAME_LENGTH: usize = 32;
+/// Amount of messages a single webhook can post within a minute
+pub const WEBHOOK_RATE_LIMIT: i64 = 30;
+
+/// Tokens are long random strings, so a fast hash is enough to keep them out of the database
+fn token_digest(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub async fn create_webhook(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    name: &str,
+) -> Result<CreatedWebhook, WebhookError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_WEBHOOK_NAME_LENGTH {
+        return Err(WebhookError::InvalidName);
+    }
+
+    require_group_role(pool, user_id, group_id, Role::Admin).await?;
+
+    let token = nanoid!(32);
+    let token_hash = token_digest(&token);
+
+    let res = query!(
+        r#"
+            insert into group_webhooks (group_id, user_id, name, token_hash)
+            values ($1, $2, $3, $4)
+            returning id
+        "#,
+        group_id,
+        user_id,
+        name,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await?;
+
+    debug!("User {user_id} created webhook {} in group {group_id}", res.id);
+
+    Ok(CreatedWebhook {
+        id: res.id,
+        name: name.to_string(),
+        token,
+    })
+}
+
+pub async fn fetch_group_webhooks(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+) -> Result<Vec<WebhookInfo>, WebhookError> {
+    require_group_role(pool, user_id, group_id, Role::Admin).await?;
+
+    let webhooks = query_as!(
+        WebhookInfoModel,
+        r#"
+            select id, name, created_at, revoked_at from group_webhooks
+            where group_id = $1
+            order by created_at
+        "#,
+        group_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(webhooks.into_iter().map(WebhookInfo::from).collect())
+}
+
+pub async fn revoke_webhook(
+    pool: &PgPool,
+    user_id: &Uuid,
+    group_id: &Uuid,
+    webhook_id: &Uuid,
+) -> Result<(), WebhookError> {
+    require_group_role(pool, user_id, group_id, Role::Admin).await?;
+
+    let res = query!(
+        r#"
+            update group_webhooks set revoked_at = now()
+            where id = $1 and group_id = $2 and revoked_at is null
+        "#,
+        webhook_id,
+        group_id
+    )
+    .execute(pool)
+    .await?;
+
+    if res.rows_affected() == 0 {
+        return Err(WebhookError::WebhookNotFound);
+    }
+
+    debug!("User {user_id} revoked webhook {webhook_id} in group {group_id}");
+    Ok(())
+}
+
+/// Saves a message posted through the webhook and returns it with the target group
+pub async fn post_webhook_message(
+    pool: &PgPool,
+    webhook_id: &Uuid,
+    token: &str,
+    content: &str,
+) -> Result<(Uuid, GroupUserMessage), WebhookError> {
+    let Some(webhook) = query!(
+        r#"
+            select group_id, name, token_hash, revoked_at from group_webhooks
+            where id = $1
+        "#,
+        webhook_id
+    )
+    .fetch_optional(pool)
+    .await? else {
+        return Err(WebhookError::WebhookNotFound);
+    };
+
+    if webhook.revoked_at.is_some() {
+        return Err(WebhookError::WebhookRevoked);
+    }
+
+    let is_valid: bool = token_digest(token).as_bytes().ct_eq(webhook.token_hash.as_bytes()).into();
+    if !is_valid {
+        return Err(WebhookError::InvalidToken);
+    }
+
+    if content.trim().is_empty() || content.len() > MAX_MESSAGE_LENGTH {
+        return Err(WebhookError::InvalidMessage);
+    }
+
+    let filters = load_group_filters(pool, &webhook.group_id).await?;
+    let (content, flagged_by) = match filters.apply(content) {
+        FilterVerdict::Blocked { filter_id } => {
+            debug!("Webhook {webhook_id} message blocked by content filter {filter_id}");
+            return Err(WebhookError::MessageBlocked);
+        }
+        FilterVerdict::Passed { content, flagged_by } => (content, flagged_by),
+    };
+
+    let mut transaction = pool.begin().await?;
+
+    // the webhook stays locked until the message is saved, so concurrent posts are counted one by one
+    let locked = query!(
+        r#"
+            select id from group_webhooks
+            where id = $1 and revoked_at is null
+            for update
+        "#,
+        webhook_id
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    if locked.is_none() {
+        return Err(WebhookError::WebhookRevoked);
+    }
+
+    let recent = query!(
+        r#"
+            select count(*) as "count!" from messages
+            where webhook_id = $1 and sent_at > now() - interval '1 minute'
+        "#,
+        webhook_id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    if recent.count >= WEBHOOK_RATE_LIMIT {
+        return Err(WebhookError::RateLimited);
+    }
+
+    let res = query!(
+        r#"
+            insert into messages (content, group_id, webhook_id, kind)
+            values ($1, $2, $3, $4)
+            returning id
+        "#,
+        content,
+        webhook.group_id,
+        webhook_id,
+        MessageKind::Webhook as MessageKind
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+    if !flagged_by.is_empty() {
+        record_message_flags(&mut transaction, &webhook.group_id, res.id, &flagged_by).await?;
+    }
+
+    let event = GroupEvent::MessageCreated {
+        user_id: None,
+        webhook_id: Some(*webhook_id),
+        nickname: webhook.name.clone(),
+        content: content.clone(),
+    };
+    enqueue_group_event(&mut transaction, &webhook.group_id, event)
+        .await
+        .context("Failed to enqueue message created event")?;
+
+    transaction.commit().await?;
+
+    Ok((
+        webhook.group_id,
+        GroupUserMessage::webhook(webhook.name, content).with_id(res.id),
+    ))
+}
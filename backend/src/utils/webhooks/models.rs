@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::utils::roles::models::RoleRef;
+
+#[derive(Deserialize, Debug)]
+pub struct NewWebhook {
+    pub name: String,
+}
+
+/// Returned only once - the token is stored hashed
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedWebhook {
+    pub id: Uuid,
+    pub name: String,
+    pub token: String,
+}
+
+#[derive(Debug)]
+pub struct WebhookInfoModel {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: i64,
+    pub revoked_at: Option<i64>,
+}
+
+impl From<WebhookInfoModel> for WebhookInfo {
+    fn from(val: WebhookInfoModel) -> Self {
+        Self {
+            id: val.id,
+            name: val.name,
+            created_at: val.created_at.unix_timestamp(),
+            revoked_at: val.revoked_at.map(|t| t.unix_timestamp()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WebhookPayload {
+    pub content: String,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "group_event", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum GroupEventKind {
+    MessageCreated,
+    MemberJoined,
+    MemberLeft,
+    MemberKicked,
+    RoleChanged,
+}
+
+impl PgHasArrayType for GroupEventKind {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_group_event")
+    }
+}
+
+/// Group state change delivered to the registered event webhooks
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum GroupEvent {
+    MessageCreated {
+        /// Author of the message, `None` for messages posted through an incoming webhook
+        user_id: Option<Uuid>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        webhook_id: Option<Uuid>,
+        nickname: String,
+        content: String,
+    },
+    MemberJoined {
+        user_id: Uuid,
+    },
+    MemberLeft {
+        user_id: Uuid,
+    },
+    MemberKicked {
+        user_id: Uuid,
+        kicked_by: Uuid,
+    },
+    RoleChanged {
+        user_id: Uuid,
+        role: RoleRef,
+    },
+}
+
+impl GroupEvent {
+    pub fn kind(&self) -> GroupEventKind {
+        match self {
+            GroupEvent::MessageCreated { .. } => GroupEventKind::MessageCreated,
+            GroupEvent::MemberJoined { .. } => GroupEventKind::MemberJoined,
+            GroupEvent::MemberLeft { .. } => GroupEventKind::MemberLeft,
+            GroupEvent::MemberKicked { .. } => GroupEventKind::MemberKicked,
+            GroupEvent::RoleChanged { .. } => GroupEventKind::RoleChanged,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct EventPayload<'a> {
+    pub group_id: Uuid,
+    pub occurred_at: i64,
+    #[serde(flatten)]
+    pub event: &'a GroupEvent,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NewEventWebhook {
+    pub url: String,
+    pub events: Vec<GroupEventKind>,
+}
+
+/// Returned only once - the secret is needed to verify payload signatures
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatedEventWebhook {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<GroupEventKind>,
+    pub secret: String,
+}
+
+#[derive(Debug)]
+pub struct EventWebhookInfoModel {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<GroupEventKind>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventWebhookInfo {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<GroupEventKind>,
+    pub created_at: i64,
+}
+
+impl From<EventWebhookInfoModel> for EventWebhookInfo {
+    fn from(val: EventWebhookInfoModel) -> Self {
+        Self {
+            id: val.id,
+            url: val.url,
+            events: val.events,
+            created_at: val.created_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "delivery_status", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug)]
+pub struct PendingDelivery {
+    pub id: i32,
+    pub event: GroupEventKind,
+    pub payload: Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Debug)]
+pub struct EventDeliveryModel {
+    pub id: i32,
+    pub event: GroupEventKind,
+    pub payload: Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: OffsetDateTime,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub delivered_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventDelivery {
+    pub id: i32,
+    pub event: GroupEventKind,
+    pub payload: Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: i64,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: i64,
+    pub delivered_at: Option<i64>,
+}
+
+impl From<EventDeliveryModel> for EventDelivery {
+    fn from(val: EventDeliveryModel) -> Self {
+        Self {
+            id: val.id,
+            event: val.event,
+            payload: val.payload,
+            status: val.status,
+            attempts: val.attempts,
+            next_attempt_at: val.next_attempt_at.unix_timestamp(),
+            last_status_code: val.last_status_code,
+            last_error: val.last_error,
+            created_at: val.created_at.unix_timestamp(),
+            delivered_at: val.delivered_at.map(|t| t.unix_timestamp()),
+        }
+    }
+}
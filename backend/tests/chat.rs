@@ -1,4 +1,11 @@
-﻿use backend::utils::chat::{create_message, errors::ChatError, get_user_email_by_id};
+﻿use backend::utils::chat::nicknames::{
+    find_group_member_by_nickname, reset_group_nickname, set_group_nickname, set_unique_nicknames,
+};
+use backend::utils::chat::{
+    create_message, create_message_with_kind, errors::ChatError, get_group_nickname,
+    get_user_email_by_id, messages::fetch_last_messages_in_range, models::MessageKind,
+};
+use backend::utils::roles::models::Gates;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -61,3 +68,181 @@ async fn create_message_content_is_empty(db: PgPool) {
         _ => panic!("Test result is {:?}", res),
     }
 }
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn set_group_nickname_health_check(db: PgPool) {
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let nickname = set_group_nickname(&db, &user_id, &group_id, "  Marco_Polo ")
+        .await
+        .unwrap();
+    assert_eq!(nickname, "Marco_Polo");
+
+    let res = get_group_nickname(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(res, "Marco_Polo");
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn set_group_nickname_too_long(db: PgPool) {
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let res = set_group_nickname(&db, &user_id, &group_id, &"a".repeat(33)).await;
+
+    match res {
+        Err(ChatError::InvalidNickname) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn set_group_nickname_with_whitespace(db: PgPool) {
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let res = set_group_nickname(&db, &user_id, &group_id, "Marco Polo").await;
+
+    match res {
+        Err(ChatError::InvalidNickname) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_nickname_taken(db: PgPool) {
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    set_unique_nicknames(&db, &owner_id, &group_id, true)
+        .await
+        .unwrap();
+    let res = set_group_nickname(&db, &user_id, &group_id, "polo").await;
+
+    match res {
+        Err(ChatError::NicknameTaken) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_unique_nicknames_with_duplicates(db: PgPool) {
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    set_group_nickname(&db, &user_id, &group_id, "Polo")
+        .await
+        .unwrap();
+    let res = set_unique_nicknames(&db, &owner_id, &group_id, true).await;
+
+    match res {
+        Err(ChatError::NicknamesNotUnique) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn reset_group_nickname_health_check(db: PgPool) {
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    set_group_nickname(&db, &user_id, &group_id, "Marco_Polo")
+        .await
+        .unwrap();
+    let nickname = reset_group_nickname(&db, &Gates::new().nicknames, &admin_id, &group_id, &user_id)
+        .await
+        .unwrap();
+
+    let res = get_group_nickname(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(res, nickname);
+    assert_ne!(res, "Marco_Polo");
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn reset_group_nickname_as_member(db: PgPool) {
+    let member_id = Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let res = reset_group_nickname(&db, &Gates::new().nicknames, &member_id, &group_id, &user_id).await;
+
+    match res {
+        Err(ChatError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn reset_group_nickname_of_owner_as_admin(db: PgPool) {
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let res = reset_group_nickname(&db, &Gates::new().nicknames, &admin_id, &group_id, &owner_id).await;
+
+    match res {
+        Err(ChatError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn get_group_nickname_not_a_member(db: PgPool) {
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("347ac024-f8c9-4450-850f-9d85fb17c957").unwrap();
+
+    let res = get_group_nickname(&db, &user_id, &group_id).await;
+
+    match res {
+        Err(ChatError::MemberNotFound) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn find_group_member_by_nickname_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let res = find_group_member_by_nickname(&db, &group_id, "Polo").await;
+
+    match res {
+        Ok(user_id) if user_id == Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap() => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn find_group_member_by_nickname_ambiguous(db: PgPool) {
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    set_group_nickname(&db, &user_id, &group_id, "Polo")
+        .await
+        .unwrap();
+    let res = find_group_member_by_nickname(&db, &group_id, "Polo").await;
+
+    match res {
+        Err(ChatError::AmbiguousMember) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn create_action_message_health_check(db: PgPool) {
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    create_message_with_kind(&db, &user_id, &group_id, "waves", MessageKind::Action)
+        .await
+        .unwrap();
+
+    let messages = fetch_last_messages_in_range(&db, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].kind, MessageKind::Action);
+    assert_eq!(messages[0].nickname, "Marco");
+}
@@ -1,12 +1,45 @@
 ﻿
-use backend::utils::groups::models::GroupInfo;
+use backend::utils::chat::{create_message, get_group_nickname};
+use backend::utils::chat::messages::fetch_last_messages_in_range;
+use backend::utils::chat::models::{MessageKind, SystemEvent};
+use backend::utils::invitations::{
+    fetch_group_invitations, revoke_group_invitation, try_create_group_invitation_with_code,
+    try_join_group_by_code, GroupInvitationCreate,
+};
+use backend::utils::invitations::errors::InvitationError;
+use backend::utils::roles::models::{Gates, PrivilegeChangeData, Role};
+use backend::utils::roles::privileges::{
+    CanBan, CanDeleteMessages, CanEditSettings, CanKick, CanMute, Privilege,
+};
+use backend::utils::roles::single_set_group_role_privileges;
+use backend::utils::chat::nicknames::{set_group_nickname, set_unique_nicknames};
+use backend::utils::groups::audit::fetch_audit_log;
+use backend::utils::groups::bans::{ban_user, fetch_group_bans, unban_user};
+use backend::utils::groups::filters::{
+    create_group_filter, delete_group_filter, fetch_group_filters, load_group_filters,
+    update_group_filter, FilterVerdict,
+};
+use backend::utils::groups::kicks::{fetch_kick_history, kick_user_from_group};
+use backend::utils::groups::models::{
+    AuditAction, AuditFilter, ClosedReport, FilterAction, FilterKind, GroupInfo, NewBan, NewContentFilter, NewReport,
+    ReportAction, ReportCategory, ReportResolution, ResolutionEffect, RetentionPolicy,
+};
+use backend::utils::groups::mutes::{
+    fetch_group_mutes, get_active_mute, mute_user, take_ended_mutes, unmute_user,
+};
+use backend::utils::groups::ownership::{
+    accept_group_ownership, cancel_ownership_transfer, get_ownership_transfer, offer_group_ownership,
+};
+use backend::utils::groups::reports::{close_report, fetch_group_reports, report_message};
+use backend::utils::groups::retention::{purge_expired_messages, set_group_retention};
 use backend::utils::groups::{check_if_group_exists, get_group_info};
 use backend::utils::groups::{
-    check_if_group_member, create_group, errors::GroupError, query_user_groups,
-    try_add_user_to_group,
+    check_if_group_member, create_group, errors::GroupError, query_user_groups, require_group_role,
+    MAX_REASON_LENGTH, try_add_user_to_group, try_remove_user_from_group,
 };
 use serde_json::Value;
-use sqlx::PgPool;
+use sqlx::{query, PgPool};
+use time::Duration;
 use uuid::Uuid;
 
 #[sqlx::test(fixtures("users", "groups", "roles", "group_users", "group_roles"))]
@@ -25,6 +58,26 @@ async fn add_user_to_group_health_check(db: PgPool) {
     }
 }
 
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users", "group_roles"))]
+async fn add_user_to_group_with_taken_nickname(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let member_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let user_id = Uuid::parse_str("e287ccab-fb33-4314-8d81-bfa9d6e52928").unwrap();
+
+    set_unique_nicknames(&db, &owner_id, &group_id, true)
+        .await
+        .unwrap();
+    set_group_nickname(&db, &member_id, &group_id, "_SomeUser_")
+        .await
+        .unwrap();
+
+    try_add_user_to_group(&db, &user_id, &group_id).await.unwrap();
+
+    let nickname = get_group_nickname(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(nickname, "_SomeUser__2");
+}
+
 #[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
 async fn add_user_to_group_user_is_in_group(db: PgPool) {
     // tries to add Adam to Chadders
@@ -210,6 +263,7 @@ async fn get_group_info_health_check(db: PgPool) {
                 == GroupInfo {
                     members: 4,
                     name: "Chadders".to_string(),
+                    retention_days: None,
                 } =>
         {
             ()
@@ -232,3 +286,1115 @@ async fn get_group_info_group_does_not_exist(db: PgPool) {
         _ => panic!("Test result is {:?}", res),
     }
 }
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_retention_health_check(db: PgPool) {
+    // Adam (owner) keeps Chadders history for 30 days
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let res = set_group_retention(
+        &db,
+        &Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap(),
+        &group_id,
+        RetentionPolicy { days: Some(30) },
+    )
+    .await;
+
+    match res {
+        Ok(_) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let info = get_group_info(&db, &group_id).await.unwrap();
+    assert_eq!(info.retention_days, Some(30));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_retention_not_owner(db: PgPool) {
+    // Hubert (admin) tries to change Chadders retention
+    let res = set_group_retention(
+        &db,
+        &Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap(),
+        &Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        RetentionPolicy { days: Some(30) },
+    )
+    .await;
+
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_retention_invalid_period(db: PgPool) {
+    let res = set_group_retention(
+        &db,
+        &Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap(),
+        &Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        RetentionPolicy { days: Some(0) },
+    )
+    .await;
+
+    match res {
+        Err(GroupError::InvalidRetention) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users", "messages"))]
+async fn purge_expired_messages_health_check(db: PgPool) {
+    // Chadders keep messages for a week, two of them are older
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    query!(
+        r#"
+            update groups set retention_days = 7 where id = $1
+        "#,
+        group_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    query!(
+        r#"
+            update messages set sent_at = now() - interval '8 days'
+            where id in (select id from messages order by id limit 2)
+        "#
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let purged = purge_expired_messages(&db, 1).await.unwrap();
+    assert_eq!(purged, 2);
+
+    let left = query!(
+        r#"
+            select count(*) from messages where group_id = $1
+        "#,
+        group_id
+    )
+    .fetch_one(&db)
+    .await
+    .unwrap();
+    assert_eq!(left.count, Some(5));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn mute_user_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+
+    let muted_until = mute_user(&db, &Gates::new().mute, &group_id, &user_id, &admin_id, Duration::minutes(30))
+        .await
+        .unwrap();
+
+    let res = get_active_mute(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(res, Some(muted_until));
+
+    let mutes = fetch_group_mutes(&db, &admin_id, &group_id).await.unwrap();
+    assert_eq!(mutes.len(), 1);
+    assert_eq!(mutes[0].nickname, "Marco");
+    assert_eq!(mutes[0].muted_until, muted_until.unix_timestamp());
+
+    unmute_user(&db, &Gates::new().mute, &group_id, &admin_id, &user_id).await.unwrap();
+    let res = get_active_mute(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(res, None);
+
+    // lifted mutes are announced by the moderator, not by the expiry worker
+    assert!(take_ended_mutes(&db).await.unwrap().is_empty());
+
+    let res = unmute_user(&db, &Gates::new().mute, &group_id, &admin_id, &user_id).await;
+    match res {
+        Err(GroupError::MuteNotFound) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn get_active_mute_expired(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+
+    query!(
+        r#"
+            insert into group_mutes (group_id, user_id, muted_by, muted_until)
+            values ($1, $2, $3, now() - interval '1 minute')
+        "#,
+        group_id,
+        user_id,
+        admin_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let res = get_active_mute(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(res, None);
+
+    // the end of the mute is announced exactly once
+    assert_eq!(take_ended_mutes(&db).await.unwrap(), vec![(group_id, user_id)]);
+    assert!(take_ended_mutes(&db).await.unwrap().is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn unmute_self_as_admin(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+
+    mute_user(&db, &Gates::new().mute, &group_id, &admin_id, &owner_id, Duration::minutes(30))
+        .await
+        .unwrap();
+
+    let res = unmute_user(&db, &Gates::new().mute, &group_id, &admin_id, &admin_id).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+    assert!(get_active_mute(&db, &admin_id, &group_id).await.unwrap().is_some());
+
+    unmute_user(&db, &Gates::new().mute, &group_id, &owner_id, &admin_id).await.unwrap();
+    assert_eq!(get_active_mute(&db, &admin_id, &group_id).await.unwrap(), None);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn mute_admin_as_admin(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+
+    let res = mute_user(&db, &Gates::new().mute, &group_id, &owner_id, &admin_id, Duration::minutes(30)).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let res = mute_user(&db, &Gates::new().mute, &group_id, &admin_id, &owner_id, Duration::minutes(-1)).await;
+    match res {
+        Err(GroupError::InvalidMuteDuration) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn join_group_by_code_records_system_message(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let user_id = Uuid::parse_str("e287ccab-fb33-4314-8d81-bfa9d6e52928").unwrap();
+
+    let code = try_create_group_invitation_with_code(
+        &db,
+        &owner_id,
+        GroupInvitationCreate::new(group_id, None, None),
+    )
+    .await
+    .unwrap();
+
+    let (joined, message) = try_join_group_by_code(&db, &user_id, &code).await.unwrap();
+    assert_eq!(joined, group_id);
+    assert_eq!(message.content, "_SomeUser_ joined the group");
+
+    let messages = fetch_last_messages_in_range(&db, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].kind, MessageKind::System);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn transfer_group_ownership_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let other_id = Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+
+    let transfer = offer_group_ownership(&db, &owner_id, &group_id, &user_id).await.unwrap();
+    assert_eq!(transfer.to_user_id, user_id);
+
+    // the offer is made to one member only
+    let res = accept_group_ownership(&db, &other_id, &group_id).await;
+    match res {
+        Err(GroupError::TransferNotFound) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+    let role = require_group_role(&db, &owner_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Owner);
+
+    let (transfer, message) = accept_group_ownership(&db, &user_id, &group_id).await.unwrap();
+    assert_eq!(transfer.from_user_id, owner_id);
+    assert_eq!(message.content, "Marco took over the group from Adimac93");
+
+    let role = require_group_role(&db, &user_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Owner);
+    let role = require_group_role(&db, &owner_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Admin);
+
+    assert!(get_ownership_transfer(&db, &user_id, &group_id).await.unwrap().is_none());
+
+    let records = fetch_audit_log(&db, &user_id, &group_id, AuditFilter::default()).await.unwrap();
+    assert_eq!(records[0].action, AuditAction::OwnershipTransferred);
+    assert_eq!(records[0].target_user_id, Some(user_id));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn transfer_group_ownership_requires_owner(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let outsider_id = Uuid::parse_str("e287ccab-fb33-4314-8d81-bfa9d6e52928").unwrap();
+
+    let res = offer_group_ownership(&db, &admin_id, &group_id, &user_id).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let res = offer_group_ownership(&db, &owner_id, &group_id, &owner_id).await;
+    match res {
+        Err(GroupError::CannotTransferToSelf) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let res = offer_group_ownership(&db, &owner_id, &group_id, &outsider_id).await;
+    match res {
+        Err(GroupError::UserNotInGroup) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    // the member declines the offer
+    offer_group_ownership(&db, &owner_id, &group_id, &user_id).await.unwrap();
+    cancel_ownership_transfer(&db, &user_id, &group_id).await.unwrap();
+
+    let res = accept_group_ownership(&db, &user_id, &group_id).await;
+    match res {
+        Err(GroupError::TransferNotFound) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn owner_leaving_passes_ownership(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let marco_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let polo_id = Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+
+    // a pending offer leaves with the owner
+    offer_group_ownership(&db, &owner_id, &group_id, &marco_id).await.unwrap();
+
+    let (_, succession) = try_remove_user_from_group(&db, owner_id, group_id).await.unwrap();
+    assert_eq!(succession.unwrap().content, "HubertK05 took over the group from Adimac93");
+    let role = require_group_role(&db, &admin_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Owner);
+    assert!(get_ownership_transfer(&db, &marco_id, &group_id).await.unwrap().is_none());
+
+    // without admins the longest-serving member takes over
+    query!(
+        r#"
+            update group_users
+                set joined_at = now() - interval '1 day'
+                where user_id = $1 and group_id = $2
+        "#,
+        polo_id,
+        group_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let (_, succession) = try_remove_user_from_group(&db, admin_id, group_id).await.unwrap();
+    assert_eq!(succession.unwrap().content, "Polo took over the group from HubertK05");
+    let role = require_group_role(&db, &polo_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Owner);
+    let role = require_group_role(&db, &marco_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Member);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn owner_removed_outside_application_passes_ownership(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+
+    // memberships deleted straight from the database still leave the group with an owner
+    query!(
+        r#"
+            delete from group_users where user_id = $1 and group_id = $2
+        "#,
+        owner_id,
+        group_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    let role = require_group_role(&db, &admin_id, &group_id, Role::Member).await.unwrap();
+    assert_eq!(role, Role::Owner);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn remove_user_from_group_records_system_message(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let (message, succession) = try_remove_user_from_group(&db, user_id, group_id)
+        .await
+        .unwrap();
+    assert_eq!(message.content, "Marco left the group");
+    assert!(succession.is_none());
+
+    assert!(!check_if_group_member(&db, &user_id, &group_id).await.unwrap());
+
+    let res = try_remove_user_from_group(&db, user_id, group_id).await;
+    match res {
+        Err(GroupError::UserNotInGroup) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn kick_user_from_group_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let (message, kick) = kick_user_from_group(&db, &group_id, &user_id, &admin_id, Some(" spam "))
+        .await
+        .unwrap();
+    assert_eq!(message.content, "HubertK05 removed Marco from the group: spam");
+    assert!(matches!(
+        message.event,
+        Some(SystemEvent::MemberKicked { kicked_by, .. }) if kicked_by == admin_id
+    ));
+    assert_eq!(kick.from, "HubertK05");
+    assert_eq!(kick.reason.as_deref(), Some("spam"));
+
+    assert!(!check_if_group_member(&db, &user_id, &group_id).await.unwrap());
+
+    let history = fetch_kick_history(&db, &admin_id, &group_id, None)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].user_id, user_id);
+    assert_eq!(history[0].nickname, "Marco");
+    assert_eq!(history[0].kicked_by, Some(admin_id));
+    assert_eq!(history[0].moderator, "HubertK05");
+    assert_eq!(history[0].reason.as_deref(), Some("spam"));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn kick_user_from_group_reason_too_long(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let reason = "a".repeat(MAX_REASON_LENGTH + 1);
+    let res = kick_user_from_group(&db, &group_id, &user_id, &admin_id, Some(&reason)).await;
+    match res {
+        Err(GroupError::ReasonTooLong) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    assert!(check_if_group_member(&db, &user_id, &group_id).await.unwrap());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn fetch_kick_history_as_member(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let res = fetch_kick_history(&db, &user_id, &group_id, None).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn ban_user_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let new_ban = NewBan {
+        user_id,
+        reason: Some("spam".into()),
+        duration: Some(60 * 60),
+    };
+    let (ban, message) = ban_user(&db, &Gates::new().ban, &group_id, &admin_id, new_ban).await.unwrap();
+    assert_eq!(ban.username, "Marco");
+    assert_eq!(ban.banned_by, Some(admin_id));
+    assert!(ban.expires_at.is_some());
+    assert_eq!(
+        message.unwrap().content,
+        "HubertK05 banned Marco from the group: spam"
+    );
+    assert!(!check_if_group_member(&db, &user_id, &group_id).await.unwrap());
+
+    let res = try_add_user_to_group(&db, &user_id, &group_id).await;
+    match res {
+        Err(GroupError::UserBanned) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let bans = fetch_group_bans(&db, &admin_id, &group_id).await.unwrap();
+    assert_eq!(bans, vec![ban]);
+
+    unban_user(&db, &Gates::new().ban, &group_id, &admin_id, &user_id).await.unwrap();
+    assert!(fetch_group_bans(&db, &admin_id, &group_id).await.unwrap().is_empty());
+    try_add_user_to_group(&db, &user_id, &group_id).await.unwrap();
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn ban_user_blocks_invitation(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let user_id = Uuid::parse_str("e287ccab-fb33-4314-8d81-bfa9d6e52928").unwrap();
+
+    let new_ban = NewBan {
+        user_id,
+        reason: None,
+        duration: None,
+    };
+    let (_, message) = ban_user(&db, &Gates::new().ban, &group_id, &owner_id, new_ban).await.unwrap();
+    assert!(message.is_none());
+
+    let code = try_create_group_invitation_with_code(
+        &db,
+        &owner_id,
+        GroupInvitationCreate::new(group_id, None, None),
+    )
+    .await
+    .unwrap();
+
+    let res = try_join_group_by_code(&db, &user_id, &code).await;
+    match res {
+        Err(GroupError::UserBanned) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn ban_owner_as_admin(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+
+    let new_ban = NewBan {
+        user_id: owner_id,
+        reason: None,
+        duration: None,
+    };
+    let res = ban_user(&db, &Gates::new().ban, &group_id, &admin_id, new_ban).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+    assert!(check_if_group_member(&db, &owner_id, &group_id).await.unwrap());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn ban_user_requires_ban_privilege(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let mut data = PrivilegeChangeData::new(group_id, Role::Admin, Privilege::CanBan(CanBan::No));
+    single_set_group_role_privileges(&db, &Gates::new().manage_roles, &owner_id, &mut data)
+        .await
+        .unwrap();
+
+    let new_ban = NewBan {
+        user_id,
+        reason: None,
+        duration: None,
+    };
+    let res = ban_user(&db, &Gates::new().ban, &group_id, &admin_id, new_ban).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+    assert!(check_if_group_member(&db, &user_id, &group_id).await.unwrap());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn unban_owner_ban_as_admin(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let new_ban = NewBan {
+        user_id,
+        reason: None,
+        duration: None,
+    };
+    ban_user(&db, &Gates::new().ban, &group_id, &owner_id, new_ban).await.unwrap();
+
+    let res = unban_user(&db, &Gates::new().ban, &group_id, &admin_id, &user_id).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+    assert_eq!(fetch_group_bans(&db, &owner_id, &group_id).await.unwrap().len(), 1);
+
+    unban_user(&db, &Gates::new().ban, &group_id, &owner_id, &user_id).await.unwrap();
+    assert!(fetch_group_bans(&db, &owner_id, &group_id).await.unwrap().is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn expired_ban_allows_rejoining(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("e287ccab-fb33-4314-8d81-bfa9d6e52928").unwrap();
+
+    query!(
+        r#"
+            insert into group_bans (group_id, user_id, expires_at)
+            values ($1, $2, now() - interval '1 minute')
+        "#,
+        group_id,
+        user_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    try_add_user_to_group(&db, &user_id, &group_id).await.unwrap();
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn group_filter_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+
+    let filter = create_group_filter(
+        &db,
+        &owner_id,
+        &group_id,
+        NewContentFilter {
+            kind: FilterKind::Word,
+            pattern: "darn".into(),
+            action: FilterAction::Mask,
+        },
+    )
+    .await
+    .unwrap();
+
+    let filters = load_group_filters(&db, &group_id).await.unwrap();
+    assert_eq!(
+        filters.apply("Darn it"),
+        FilterVerdict::Passed {
+            content: "**** it".into(),
+            flagged_by: vec![]
+        }
+    );
+
+    let filter = update_group_filter(
+        &db,
+        &owner_id,
+        &group_id,
+        filter.id,
+        NewContentFilter {
+            kind: FilterKind::Word,
+            pattern: "darn".into(),
+            action: FilterAction::Block,
+        },
+    )
+    .await
+    .unwrap();
+
+    let filters = load_group_filters(&db, &group_id).await.unwrap();
+    assert_eq!(
+        filters.apply("Darn it"),
+        FilterVerdict::Blocked {
+            filter_id: filter.id
+        }
+    );
+
+    assert_eq!(
+        fetch_group_filters(&db, &owner_id, &group_id).await.unwrap(),
+        vec![filter.clone()]
+    );
+
+    delete_group_filter(&db, &owner_id, &group_id, filter.id)
+        .await
+        .unwrap();
+    assert!(fetch_group_filters(&db, &owner_id, &group_id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_group_filter_as_admin(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+
+    let res = create_group_filter(
+        &db,
+        &admin_id,
+        &group_id,
+        NewContentFilter {
+            kind: FilterKind::Regex,
+            pattern: r"https?://\S+".into(),
+            action: FilterAction::Flag,
+        },
+    )
+    .await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_group_filter_invalid_regex(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+
+    let res = create_group_filter(
+        &db,
+        &owner_id,
+        &group_id,
+        NewContentFilter {
+            kind: FilterKind::Regex,
+            pattern: "(unclosed".into(),
+            action: FilterAction::Block,
+        },
+    )
+    .await;
+    match res {
+        Err(GroupError::InvalidFilter) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn report_message_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let reporter_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let author_id = Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+
+    let message_id = create_message(&db, &author_id, &group_id, "Buy cheap gold")
+        .await
+        .unwrap();
+
+    let new_report = NewReport {
+        message_id,
+        category: ReportCategory::Spam,
+        comment: Some("  Posted it everywhere  ".into()),
+    };
+    let report = report_message(&db, &reporter_id, &group_id, new_report.clone())
+        .await
+        .unwrap();
+    assert_eq!(report.author_id, Some(author_id));
+    assert_eq!(report.author, "Polo");
+    assert_eq!(report.content, "Buy cheap gold");
+    assert_eq!(report.comment.as_deref(), Some("Posted it everywhere"));
+    assert_eq!(report.resolution, None);
+
+    let res = report_message(&db, &reporter_id, &group_id, new_report).await;
+    match res {
+        Err(GroupError::AlreadyReported) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let queue = fetch_group_reports(&db, &admin_id, &group_id, false, None)
+        .await
+        .unwrap();
+    assert_eq!(queue, vec![report]);
+
+    let res = fetch_group_reports(&db, &reporter_id, &group_id, false, None).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn report_own_message(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let message_id = create_message(&db, &user_id, &group_id, "Hello")
+        .await
+        .unwrap();
+
+    let res = report_message(
+        &db,
+        &user_id,
+        &group_id,
+        NewReport {
+            message_id,
+            category: ReportCategory::Other,
+            comment: None,
+        },
+    )
+    .await;
+    match res {
+        Err(GroupError::CannotReportMessage) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn report_queue_follows_moderation_privileges(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let member_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let mut data = PrivilegeChangeData::new(group_id, Role::Member, Privilege::CanKick(CanKick::Yes));
+    single_set_group_role_privileges(&db, &Gates::new().manage_roles, &owner_id, &mut data)
+        .await
+        .unwrap();
+
+    fetch_group_reports(&db, &member_id, &group_id, false, None)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn report_queue_requires_moderation_privilege(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+
+    for privilege in [
+        Privilege::CanKick(CanKick::No),
+        Privilege::CanMute(CanMute::No),
+        Privilege::CanDeleteMessages(CanDeleteMessages::No),
+    ] {
+        let mut data = PrivilegeChangeData::new(group_id, Role::Admin, privilege);
+        single_set_group_role_privileges(&db, &Gates::new().manage_roles, &owner_id, &mut data)
+            .await
+            .unwrap();
+    }
+
+    let res = fetch_group_reports(&db, &admin_id, &group_id, false, None).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn close_report_deletes_message(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let reporter_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let author_id = Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+
+    let message_id = create_message(&db, &author_id, &group_id, "Rude words")
+        .await
+        .unwrap();
+
+    let mut reports = Vec::new();
+    for user_id in [reporter_id, admin_id] {
+        let new_report = NewReport {
+            message_id,
+            category: ReportCategory::Harassment,
+            comment: None,
+        };
+        reports.push(report_message(&db, &user_id, &group_id, new_report).await.unwrap());
+    }
+
+    let (closed, effect) = close_report(
+        &db,
+        &Gates::new(),
+        &owner_id,
+        &group_id,
+        reports[0].id,
+        ReportAction::DeleteMessage,
+    )
+    .await
+    .unwrap();
+    assert!(matches!(effect, ResolutionEffect::MessageDeleted { message_id: id } if id == message_id));
+    assert_eq!(
+        closed.len(),
+        2,
+        "Every open report of the message should be closed"
+    );
+    assert!(closed.contains(&ClosedReport {
+        id: reports[0].id,
+        reporter_id: Some(reporter_id)
+    }));
+
+    let res = query!(
+        r#"
+            select id from messages
+            where id = $1
+        "#,
+        message_id
+    )
+    .fetch_optional(&db)
+    .await
+    .unwrap();
+    assert!(res.is_none());
+
+    let history = fetch_group_reports(&db, &owner_id, &group_id, true, None)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 2);
+    for report in history {
+        assert_eq!(report.message_id, None);
+        assert_eq!(report.content, "Rude words");
+        assert_eq!(report.resolution, Some(ReportResolution::MessageDeleted));
+        assert_eq!(report.resolved_by, Some(owner_id));
+    }
+
+    let res = close_report(
+        &db,
+        &Gates::new(),
+        &owner_id,
+        &group_id,
+        reports[1].id,
+        ReportAction::Dismiss,
+    )
+    .await;
+    match res {
+        Err(GroupError::ReportNotFound) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn close_report_kicks_author(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let reporter_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let author_id = Uuid::parse_str("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+
+    let message_id = create_message(&db, &author_id, &group_id, "Rude words")
+        .await
+        .unwrap();
+    let new_report = NewReport {
+        message_id,
+        category: ReportCategory::Harassment,
+        comment: None,
+    };
+    let report = report_message(&db, &reporter_id, &group_id, new_report).await.unwrap();
+
+    let action = ReportAction::KickAuthor { reason: Some("Rude".into()) };
+    let (closed, effect) = close_report(&db, &Gates::new(), &admin_id, &group_id, report.id, action)
+        .await
+        .unwrap();
+    assert_eq!(closed.len(), 1);
+    match effect {
+        ResolutionEffect::AuthorKicked { user_id, kick, .. } => {
+            assert_eq!(user_id, author_id);
+            assert_eq!(kick.reason.as_deref(), Some("Rude"));
+        }
+        _ => panic!("Effect is {:?}", effect),
+    }
+    assert!(!check_if_group_member(&db, &author_id, &group_id).await.unwrap());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn close_report_refused_kick_keeps_report_open(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let reporter_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let message_id = create_message(&db, &owner_id, &group_id, "Rude words")
+        .await
+        .unwrap();
+    let new_report = NewReport {
+        message_id,
+        category: ReportCategory::Harassment,
+        comment: None,
+    };
+    let report = report_message(&db, &reporter_id, &group_id, new_report).await.unwrap();
+
+    let action = ReportAction::KickAuthor { reason: None };
+    let res = close_report(&db, &Gates::new(), &admin_id, &group_id, report.id, action).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+    assert!(check_if_group_member(&db, &owner_id, &group_id).await.unwrap());
+
+    let queue = fetch_group_reports(&db, &admin_id, &group_id, false, None)
+        .await
+        .unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].resolution, None);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn audit_log_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    set_group_retention(&db, &owner_id, &group_id, RetentionPolicy { days: Some(30) })
+        .await
+        .unwrap();
+    kick_user_from_group(&db, &group_id, &user_id, &admin_id, Some("Spam"))
+        .await
+        .unwrap();
+
+    let log = fetch_audit_log(&db, &admin_id, &group_id, AuditFilter::default())
+        .await
+        .unwrap();
+    assert_eq!(log.len(), 2);
+
+    // newest first
+    assert_eq!(log[0].action, AuditAction::MemberKicked);
+    assert_eq!(log[0].actor_id, Some(admin_id));
+    assert_eq!(log[0].target_user_id, Some(user_id));
+    assert_eq!(log[0].after, Some(serde_json::json!({ "reason": "Spam" })));
+
+    assert_eq!(log[1].action, AuditAction::RetentionChanged);
+    assert_eq!(log[1].actor.as_deref(), Some("Adimac93"));
+    assert_eq!(log[1].before, None);
+    assert_eq!(log[1].after, Some(serde_json::json!(30)));
+
+    let filter = AuditFilter {
+        action: Some(AuditAction::RetentionChanged),
+        ..Default::default()
+    };
+    let log = fetch_audit_log(&db, &owner_id, &group_id, filter).await.unwrap();
+    assert_eq!(log.len(), 1);
+
+    let filter = AuditFilter {
+        before: Some(log[0].id),
+        ..Default::default()
+    };
+    assert!(fetch_audit_log(&db, &owner_id, &group_id, filter)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn audit_log_as_member(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+
+    let res = fetch_audit_log(&db, &user_id, &group_id, AuditFilter::default()).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn settings_editor_reads_filters_and_audit_log(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let user_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let member_role_id = Uuid::parse_str("eb8b3214-f823-49a9-a172-2f312c8f3303").unwrap();
+
+    query!(
+        r#"
+            update roles set can_edit_settings = true
+            where id = $1
+        "#,
+        member_role_id
+    )
+    .execute(&db)
+    .await
+    .unwrap();
+
+    assert!(fetch_group_filters(&db, &user_id, &group_id).await.unwrap().is_empty());
+    fetch_audit_log(&db, &user_id, &group_id, AuditFilter::default())
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn audit_log_is_append_only(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+
+    set_group_retention(&db, &owner_id, &group_id, RetentionPolicy { days: Some(30) })
+        .await
+        .unwrap();
+
+    let res = query!(
+        r#"
+            update group_audit_log set after = null
+        "#
+    )
+    .execute(&db)
+    .await;
+    assert!(res.is_err());
+
+    let res = query!(
+        r#"
+            delete from group_audit_log
+        "#
+    )
+    .execute(&db)
+    .await;
+    assert!(res.is_err());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn admin_with_settings_privilege_sets_retention(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let owner_id = Uuid::parse_str("ba34ff10-4b89-44cb-9b36-31eb57c41556").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+
+    let res = set_group_retention(&db, &admin_id, &group_id, RetentionPolicy { days: Some(7) }).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let privilege = Privilege::CanEditSettings(CanEditSettings::Yes);
+    let mut data = PrivilegeChangeData::new(group_id, Role::Admin, privilege);
+    single_set_group_role_privileges(&db, &Gates::new().manage_roles, &owner_id, &mut data)
+        .await
+        .unwrap();
+
+    set_group_retention(&db, &admin_id, &group_id, RetentionPolicy { days: Some(7) })
+        .await
+        .unwrap();
+    assert_eq!(get_group_info(&db, &group_id).await.unwrap().retention_days, Some(7));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn revoke_group_invitation_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let admin_id = Uuid::parse_str("263541a8-fa1e-4f13-9e5d-5b250a5a71e6").unwrap();
+    let member_id = Uuid::parse_str("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let user_id = Uuid::parse_str("e287ccab-fb33-4314-8d81-bfa9d6e52928").unwrap();
+
+    let code = try_create_group_invitation_with_code(
+        &db,
+        &member_id,
+        GroupInvitationCreate::new(group_id, None, None),
+    )
+    .await
+    .unwrap();
+
+    // members can't manage invitations
+    let res = revoke_group_invitation(&db, &member_id, &group_id, &code).await;
+    match res {
+        Err(GroupError::InsufficientPrivileges) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+
+    let invitations = fetch_group_invitations(&db, &admin_id, &group_id).await.unwrap();
+    assert_eq!(invitations.len(), 1);
+    assert_eq!(invitations[0].created_by, member_id);
+
+    revoke_group_invitation(&db, &admin_id, &group_id, &code).await.unwrap();
+    assert!(fetch_group_invitations(&db, &admin_id, &group_id).await.unwrap().is_empty());
+
+    let res = try_join_group_by_code(&db, &user_id, &code).await;
+    match res {
+        Err(GroupError::InvitationError(InvitationError::InvalidCode)) => (),
+        _ => panic!("Test result is {:?}", res),
+    }
+}
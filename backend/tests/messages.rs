@@ -1,7 +1,21 @@
 use uuid::Uuid;
 mod tools;
-use backend::utils::chat::{messages::fetch_last_messages_in_range, models::GroupUserMessage};
-use sqlx::PgPool;
+use backend::utils::chat::{
+    errors::ChatError,
+    export::export_messages,
+    messages::fetch_last_messages_in_range,
+    models::{ExportFormat, GroupUserMessage, MessageKind},
+    scheduled::{
+        cancel_scheduled_message, deliver_due_messages, fetch_scheduled_messages,
+        schedule_message,
+    },
+    save_filtered_message,
+    socket::ChatState,
+};
+use futures::TryStreamExt;
+use serde_json::Value;
+use sqlx::{query, PgPool};
+use time::OffsetDateTime;
 
 #[sqlx::test(fixtures("users", "groups", "roles", "group_users", "messages"))]
 async fn partial(pool: PgPool) {
@@ -25,3 +39,326 @@ async fn partial(pool: PgPool) {
     assert_eq!(loaded_messages, expected);
     assert_eq!(buffer.len() as i64, expected);
 }
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users", "messages"))]
+async fn export_json(pool: PgPool) {
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let chunks: Vec<String> = export_messages(pool, group_id, "Chadders".into(), ExportFormat::Json)
+        .try_collect()
+        .await
+        .unwrap();
+
+    let json: Value = serde_json::from_str(&chunks.concat()).unwrap();
+    let messages = json["messages"].as_array().unwrap();
+
+    assert_eq!(json["group"], "Chadders");
+    assert_eq!(messages.len(), 7);
+    assert_eq!(messages[0]["author"], "Adimac93");
+    assert_eq!(messages[0]["content"], "Hi");
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users", "messages"))]
+async fn export_csv(pool: PgPool) {
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let chunks: Vec<String> = export_messages(pool, group_id, "Chadders".into(), ExportFormat::Csv)
+        .try_collect()
+        .await
+        .unwrap();
+
+    let csv = chunks.concat();
+    let mut lines = csv.lines();
+
+    assert_eq!(lines.next(), Some("id,author,sent_at,content"));
+    assert_eq!(lines.count(), 7);
+    assert!(csv.contains(r#""Nice, I would love to code in rust too, but I have to maintain JavaScript code base :P.""#));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn schedule_message_health_check(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let send_at = OffsetDateTime::now_utc().unix_timestamp() + 3600;
+
+    let scheduled = schedule_message(&pool, &user_id, &group_id, "Good morning", send_at)
+        .await
+        .unwrap();
+    assert_eq!(scheduled.send_at, send_at);
+
+    let pending = fetch_scheduled_messages(&pool, &user_id, &group_id)
+        .await
+        .unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].content, "Good morning");
+
+    cancel_scheduled_message(&pool, &user_id, scheduled.id)
+        .await
+        .unwrap();
+    let pending = fetch_scheduled_messages(&pool, &user_id, &group_id)
+        .await
+        .unwrap();
+    assert!(pending.is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn schedule_message_in_the_past(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let send_at = OffsetDateTime::now_utc().unix_timestamp() - 60;
+
+    let res = schedule_message(&pool, &user_id, &group_id, "Too late", send_at).await;
+    assert!(matches!(res, Err(ChatError::InvalidScheduleTime)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn cancel_scheduled_message_of_another_user(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let other_user_id = Uuid::try_from("6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let send_at = OffsetDateTime::now_utc().unix_timestamp() + 3600;
+
+    let scheduled = schedule_message(&pool, &user_id, &group_id, "Mine", send_at)
+        .await
+        .unwrap();
+
+    let res = cancel_scheduled_message(&pool, &other_user_id, scheduled.id).await;
+    assert!(matches!(res, Err(ChatError::ScheduledMessageNotFound)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn deliver_due_messages_health_check(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    query!(
+        r#"
+            insert into scheduled_messages (user_id, group_id, content, send_at)
+            values ($1, $2, 'Scheduled hello', now() - interval '1 minute')
+        "#,
+        user_id,
+        group_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let delivered = deliver_due_messages(&pool, &ChatState::new(), 100)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 1);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert!(messages.iter().any(|msg| msg.content == "Scheduled hello"));
+
+    let pending = fetch_scheduled_messages(&pool, &user_id, &group_id)
+        .await
+        .unwrap();
+    assert!(pending.is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn deliver_due_messages_without_send_privilege(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    query!(
+        r#"
+            insert into scheduled_messages (user_id, group_id, content, send_at)
+            values ($1, $2, 'Muted hello', now() - interval '1 minute')
+        "#,
+        user_id,
+        group_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Revoke sending messages from the member role after scheduling
+    query!(
+        r#"
+            update roles set can_send_messages = -1
+            where id = 'eb8b3214-f823-49a9-a172-2f312c8f3303'
+        "#
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let delivered = deliver_due_messages(&pool, &ChatState::new(), 100)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 0);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert!(messages.is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn deliver_due_messages_of_muted_user(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    query!(
+        r#"
+            insert into scheduled_messages (user_id, group_id, content, send_at)
+            values ($1, $2, 'Muted hello', now() - interval '1 minute')
+        "#,
+        user_id,
+        group_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Muted after scheduling
+    let muted_until = query!(
+        r#"
+            insert into group_mutes (group_id, user_id, muted_until)
+            values ($1, $2, now() + interval '10 minutes')
+            returning muted_until
+        "#,
+        group_id,
+        user_id
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap()
+    .muted_until;
+
+    let delivered = deliver_due_messages(&pool, &ChatState::new(), 100)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 0);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert!(messages.is_empty());
+
+    // The message waits for the end of the mute
+    let pending = fetch_scheduled_messages(&pool, &user_id, &group_id)
+        .await
+        .unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].send_at, muted_until.unix_timestamp());
+
+    query!(
+        r#"
+            update group_mutes set muted_until = now() - interval '1 second'
+            where group_id = $1 and user_id = $2
+        "#,
+        group_id,
+        user_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    query!(
+        r#"
+            update scheduled_messages set send_at = now() - interval '1 second'
+            where group_id = $1 and user_id = $2
+        "#,
+        group_id,
+        user_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let delivered = deliver_due_messages(&pool, &ChatState::new(), 100)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 1);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert!(messages.iter().any(|msg| msg.content == "Muted hello"));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn deliver_due_messages_through_content_filters(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    query!(
+        r#"
+            insert into scheduled_messages (user_id, group_id, content, send_at)
+            values ($1, $2, 'Buy spam now', now() - interval '2 minutes'),
+            ($1, $2, 'Darn it', now() - interval '1 minute')
+        "#,
+        user_id,
+        group_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // Rules added after scheduling
+    query!(
+        r#"
+            insert into group_content_filters (group_id, kind, pattern, action)
+            values ($1, 'word', 'spam', 'block'), ($1, 'word', 'darn', 'mask')
+        "#,
+        group_id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let delivered = deliver_due_messages(&pool, &ChatState::new(), 100)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 1);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].content, "**** it");
+
+    let pending = fetch_scheduled_messages(&pool, &user_id, &group_id)
+        .await
+        .unwrap();
+    assert!(pending.is_empty());
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn save_filtered_message_records_flags(pool: PgPool) {
+    let user_id = Uuid::try_from("4bd30a6a-7dfe-46a2-b741-f49612aa85c1").unwrap();
+    let group_id = Uuid::try_from("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+
+    let filter = query!(
+        r#"
+            insert into group_content_filters (group_id, kind, pattern, action)
+            values ($1, 'word', 'spam', 'flag')
+            returning id
+        "#,
+        group_id
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    let message_id = save_filtered_message(&pool, &user_id, &group_id, "Marco", "Buy spam now", MessageKind::Text, &[filter.id])
+        .await
+        .unwrap();
+
+    let flags = query!(
+        r#"
+            select filter_id from message_flags
+            where message_id = $1
+        "#,
+        message_id
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].filter_id, Some(filter.id));
+}
@@ -0,0 +1,218 @@
+use backend::utils::chat::{
+    errors::ChatError,
+    messages::fetch_last_messages_in_range,
+    models::{MessageKind, NewPoll},
+    polls::{close_expired_polls, close_poll, close_poll_as, create_poll, vote_in_poll},
+    socket::ChatState,
+};
+use backend::utils::roles::privileges::{CanDeleteMessages, Privilege, Privileges};
+use sqlx::{query, PgPool};
+use uuid::Uuid;
+
+const GROUP_ID: &str = "b8c9a317-a456-458f-af88-01d99633f8e2";
+const OWNER_ID: &str = "ba34ff10-4b89-44cb-9b36-31eb57c41556";
+const MARCO_ID: &str = "4bd30a6a-7dfe-46a2-b741-f49612aa85c1";
+const POLO_ID: &str = "6666e44f-14ce-4aa5-b5f9-8a4cc5ee5c58";
+
+fn new_poll(multiple_choice: bool, anonymous: bool) -> NewPoll {
+    NewPoll {
+        question: String::from("Rust or JavaScript?"),
+        options: vec![String::from("Rust"), String::from("JavaScript")],
+        multiple_choice,
+        anonymous,
+        closes_at: None,
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn create_poll_health_check(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+
+    let poll = create_poll(&pool, &user_id, &group_id, new_poll(false, false), &[])
+        .await
+        .unwrap();
+    assert_eq!(poll.options.len(), 2);
+    assert!(poll.options.iter().all(|option| option.votes == 0));
+    assert!(!poll.closed);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].kind, MessageKind::Poll);
+    assert_eq!(messages[0].poll_id, Some(poll.id));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn create_poll_flagged_by_filter(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+
+    let filter = query!(
+        r#"
+            insert into group_content_filters (group_id, kind, pattern, action)
+            values ($1, 'word', 'javascript', 'flag')
+            returning id
+        "#,
+        group_id
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+
+    create_poll(&pool, &user_id, &group_id, new_poll(false, false), &[filter.id])
+        .await
+        .unwrap();
+
+    let flags = query!(
+        r#"
+            select message_flags.filter_id, messages.kind as "kind: MessageKind" from message_flags
+            join messages on messages.id = message_flags.message_id
+            where message_flags.group_id = $1
+        "#,
+        group_id
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].filter_id, Some(filter.id));
+    assert_eq!(flags[0].kind, MessageKind::Poll);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn create_poll_with_single_option(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+
+    let mut poll = new_poll(false, false);
+    poll.options.truncate(1);
+
+    let res = create_poll(&pool, &user_id, &group_id, poll, &[]).await;
+    assert!(matches!(res, Err(ChatError::InvalidPoll)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn vote_in_poll_replaces_single_choice(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+
+    let poll = create_poll(&pool, &user_id, &group_id, new_poll(false, false), &[])
+        .await
+        .unwrap();
+    let (rust, js) = (poll.options[0].id, poll.options[1].id);
+
+    vote_in_poll(&pool, &user_id, &group_id, poll.id, vec![rust])
+        .await
+        .unwrap();
+    let poll = vote_in_poll(&pool, &user_id, &group_id, poll.id, vec![js])
+        .await
+        .unwrap();
+
+    assert_eq!(poll.options[0].votes, 0);
+    assert_eq!(poll.options[1].votes, 1);
+    assert_eq!(poll.options[1].voters, Some(vec![String::from("Marco")]));
+
+    let res = vote_in_poll(&pool, &user_id, &group_id, poll.id, vec![rust, js]).await;
+    assert!(matches!(res, Err(ChatError::InvalidVote)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn vote_in_anonymous_poll(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+    let other_user_id = Uuid::try_from(POLO_ID).unwrap();
+
+    let poll = create_poll(&pool, &user_id, &group_id, new_poll(true, true), &[])
+        .await
+        .unwrap();
+    let options: Vec<i32> = poll.options.iter().map(|option| option.id).collect();
+
+    vote_in_poll(&pool, &user_id, &group_id, poll.id, options.clone())
+        .await
+        .unwrap();
+    let poll = vote_in_poll(&pool, &other_user_id, &group_id, poll.id, vec![options[0]])
+        .await
+        .unwrap();
+
+    assert_eq!(poll.options[0].votes, 2);
+    assert_eq!(poll.options[1].votes, 1);
+    assert!(poll.options.iter().all(|option| option.voters.is_none()));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn close_poll_posts_result(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+
+    let poll = create_poll(&pool, &user_id, &group_id, new_poll(false, false), &[])
+        .await
+        .unwrap();
+    let rust = poll.options[0].id;
+    vote_in_poll(&pool, &user_id, &group_id, poll.id, vec![rust])
+        .await
+        .unwrap();
+
+    let (closed, message) = close_poll(&pool, &group_id, poll.id).await.unwrap();
+    assert!(closed.closed);
+    assert_eq!(message.kind, MessageKind::PollResult);
+    assert_eq!(message.content, "Poll \"Rust or JavaScript?\" closed: Rust (1), JavaScript (0)");
+
+    let res = vote_in_poll(&pool, &user_id, &group_id, poll.id, vec![rust]).await;
+    assert!(matches!(res, Err(ChatError::PollClosed)));
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert!(messages
+        .iter()
+        .any(|msg| msg.kind == MessageKind::PollResult && msg.poll_id == Some(poll.id)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn close_poll_of_another_member(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+    let other_user_id = Uuid::try_from(POLO_ID).unwrap();
+    let owner_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let poll = create_poll(&pool, &user_id, &group_id, new_poll(false, false), &[])
+        .await
+        .unwrap();
+
+    let member = Privileges::from([Privilege::CanDeleteMessages(CanDeleteMessages::No)]);
+    let res = close_poll_as(&pool, &other_user_id, &member, &group_id, poll.id).await;
+    assert!(matches!(res, Err(ChatError::InsufficientPrivileges)));
+
+    close_poll_as(&pool, &owner_id, &Privileges::max(), &group_id, poll.id)
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_users"))]
+async fn close_expired_polls_health_check(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MARCO_ID).unwrap();
+
+    let poll = create_poll(&pool, &user_id, &group_id, new_poll(false, false), &[])
+        .await
+        .unwrap();
+
+    query!(
+        r#"
+            update polls set closes_at = now() - interval '1 minute'
+            where id = $1
+        "#,
+        poll.id
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let closed = close_expired_polls(&pool, &ChatState::new()).await.unwrap();
+    assert_eq!(closed, 1);
+
+    let closed = close_expired_polls(&pool, &ChatState::new()).await.unwrap();
+    assert_eq!(closed, 0);
+}
@@ -1,9 +1,23 @@
-﻿use backend::utils::roles::models::{PrivilegeChangeData, UserRoleChangeData, PrivilegeInterpretationData, SocketGroupRolePrivileges};
-use backend::utils::roles::models::{GroupRolePrivileges, Role};
-use backend::utils::roles::privileges::{Privileges, CanInvite, Privilege, CanSendMessages};
+﻿use backend::utils::roles::models::{PrivilegeChangeData, UserRoleChangeData, GroupPrivilegesChangeData};
+use backend::configuration::{GateSettings, GatesSettings};
+use backend::utils::roles::models::{
+    CustomRoleUpdate, Gates, GroupRole, GroupRolePrivileges, GroupRoles, NewCustomRole, Role, RoleOrder, RolePosition, RoleRef,
+};
+use backend::utils::roles::custom::{create_custom_role, delete_custom_role, reorder_roles, update_custom_role};
+use backend::utils::roles::errors::RoleError;
+use backend::utils::roles::privileges::{
+    Privileges, CanInvite, Privilege, CanSendMessages, CanExport, CanKick, CanBan, CanMute, CanDeleteMessages, CanPin,
+    CanManageInvitations, CanEditSettings, CanManageRoles, CanManageNicknames,
+};
 use backend::utils::roles::{
-    get_group_role_privileges, get_user_role, single_set_group_role_privileges, single_set_group_user_role,
+    get_group_role_privileges, get_group_roles, get_member_role, get_user_privileges, get_user_role, single_set_group_role_privileges,
+    single_set_group_user_role,
+    set_group_role_privileges, set_group_user_roles,
 };
+use backend::utils::chat::messages::fetch_last_messages_in_range;
+use backend::utils::groups::audit::fetch_audit_log;
+use backend::utils::groups::models::{AuditAction, AuditFilter};
+use backend::utils::chat::models::{MessageKind, SystemEvent};
 use sqlx::{query, PgPool};
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
@@ -33,13 +47,33 @@ async fn get_group_role_privileges_health_check(db: PgPool) {
             res,
             GroupRolePrivileges (
                 HashMap::from([
-                    (Role::Admin, Privileges (HashSet::from([
+                    (Role::Admin.into(), Privileges (HashSet::from([
                         Privilege::CanInvite(CanInvite::Yes),
                         Privilege::CanSendMessages(CanSendMessages::Yes(2)),
+                        Privilege::CanExport(CanExport::No),
+                        Privilege::CanKick(CanKick::Yes),
+                        Privilege::CanBan(CanBan::Yes),
+                        Privilege::CanMute(CanMute::Yes),
+                        Privilege::CanDeleteMessages(CanDeleteMessages::Yes),
+                        Privilege::CanPin(CanPin::Yes),
+                        Privilege::CanManageInvitations(CanManageInvitations::Yes),
+                        Privilege::CanEditSettings(CanEditSettings::No),
+                        Privilege::CanManageRoles(CanManageRoles::Yes),
+                        Privilege::CanManageNicknames(CanManageNicknames::Yes),
                     ]))),
-                    (Role::Member, Privileges (HashSet::from([
+                    (Role::Member.into(), Privileges (HashSet::from([
                         Privilege::CanInvite(CanInvite::No),
                         Privilege::CanSendMessages(CanSendMessages::Yes(10)),
+                        Privilege::CanExport(CanExport::No),
+                        Privilege::CanKick(CanKick::No),
+                        Privilege::CanBan(CanBan::No),
+                        Privilege::CanMute(CanMute::No),
+                        Privilege::CanDeleteMessages(CanDeleteMessages::No),
+                        Privilege::CanPin(CanPin::No),
+                        Privilege::CanManageInvitations(CanManageInvitations::No),
+                        Privilege::CanEditSettings(CanEditSettings::No),
+                        Privilege::CanManageRoles(CanManageRoles::No),
+                        Privilege::CanManageNicknames(CanManageNicknames::No),
                     ]))),
                 ])
             )
@@ -198,22 +232,31 @@ async fn get_user_role_health_check(db: PgPool) {
 //     assert_eq!(data, GroupUsersRole::from((group_id, [])));
 // }
 
-#[tokio::test]
-async fn maintain_hierarchy_health_check() {
-    let old_privileges = SocketGroupRolePrivileges::from(
-        GroupRolePrivileges (
-            HashMap::from([
-                (Role::Admin, Privileges (HashSet::from([
-                    Privilege::CanInvite(CanInvite::Yes),
-                    Privilege::CanSendMessages(CanSendMessages::Yes(5)),
-                ]))),
-                (Role::Member, Privileges (HashSet::from([
-                    Privilege::CanInvite(CanInvite::No),
-                    Privilege::CanSendMessages(CanSendMessages::Yes(10)),
-                ]))),
-            ])
-        )
-    );
+fn group_role(kind: Option<Role>, position: i32, privileges: Privileges) -> GroupRole {
+    GroupRole {
+        id: Uuid::new_v4(),
+        kind,
+        name: kind.map_or("Moderator".into(), |kind| kind.to_string()),
+        color: "#99aab5".into(),
+        position,
+        rank: kind.unwrap_or(Role::Member),
+        privileges,
+    }
+}
+
+#[test]
+fn maintain_hierarchy_health_check() {
+    let roles = GroupRoles(vec![
+        group_role(Some(Role::Member), 0, Privileges::from([
+            Privilege::CanInvite(CanInvite::No),
+            Privilege::CanSendMessages(CanSendMessages::Yes(10)),
+        ])),
+        group_role(Some(Role::Admin), 1, Privileges::from([
+            Privilege::CanInvite(CanInvite::Yes),
+            Privilege::CanSendMessages(CanSendMessages::Yes(5)),
+        ])),
+        group_role(Some(Role::Owner), 2, Privileges::max()),
+    ]);
 
     let random_group_id = Uuid::new_v4();
     let mut new_privileges = PrivilegeChangeData::new(
@@ -222,23 +265,101 @@ async fn maintain_hierarchy_health_check() {
         Privilege::CanSendMessages(CanSendMessages::Yes(15)),
     );
 
-    new_privileges.maintain_hierarchy(&old_privileges).await.unwrap();
+    new_privileges.maintain_hierarchy(&roles).unwrap();
 
-    assert_eq!(
-        new_privileges,
-        PrivilegeChangeData::new(
-            random_group_id,
-            Role::Admin,
-            Privilege::CanSendMessages(CanSendMessages::Yes(10)),
-        ),
-    );
+    assert_eq!(new_privileges.role, RoleRef::Kind(Role::Admin));
+    assert!(matches!(new_privileges.value, Privilege::CanSendMessages(CanSendMessages::Yes(10))));
+}
+
+#[test]
+fn maintain_hierarchy_between_positions() {
+    let custom = group_role(None, 1, Privileges::from([Privilege::CanSendMessages(CanSendMessages::Yes(10))]));
+    let custom_id = custom.id;
+    let roles = GroupRoles(vec![
+        group_role(Some(Role::Member), 0, Privileges::from([Privilege::CanSendMessages(CanSendMessages::Yes(10))])),
+        custom,
+        group_role(Some(Role::Admin), 2, Privileges::from([Privilege::CanSendMessages(CanSendMessages::Yes(5))])),
+        group_role(Some(Role::Owner), 3, Privileges::max()),
+    ]);
+
+    // can't be faster than the admin role right above it
+    let mut data = PrivilegeChangeData::new(Uuid::new_v4(), custom_id, Privilege::CanSendMessages(CanSendMessages::Yes(2)));
+    data.maintain_hierarchy(&roles).unwrap();
+    assert!(matches!(data.value, Privilege::CanSendMessages(CanSendMessages::Yes(5))));
+
+    // nor slower than the member role right below it
+    let mut data = PrivilegeChangeData::new(Uuid::new_v4(), custom_id, Privilege::CanSendMessages(CanSendMessages::Yes(20)));
+    data.maintain_hierarchy(&roles).unwrap();
+    assert!(matches!(data.value, Privilege::CanSendMessages(CanSendMessages::Yes(10))));
+
+    // the admin role is now bounded by the custom role instead of the member role
+    let mut data = PrivilegeChangeData::new(Uuid::new_v4(), Role::Admin, Privilege::CanSendMessages(CanSendMessages::Yes(20)));
+    data.maintain_hierarchy(&roles).unwrap();
+    assert!(matches!(data.value, Privilege::CanSendMessages(CanSendMessages::Yes(10))));
+}
+
+#[test]
+fn maintain_hierarchy_moderation_privileges() {
+    let roles = GroupRoles(vec![
+        group_role(Some(Role::Member), 0, Privileges::from([Privilege::CanKick(CanKick::No)])),
+        group_role(Some(Role::Admin), 1, Privileges::from([Privilege::CanKick(CanKick::No)])),
+        group_role(Some(Role::Owner), 2, Privileges::max()),
+    ]);
+
+    // members get no more than the admins above them
+    let mut data = PrivilegeChangeData::new(Uuid::new_v4(), Role::Member, Privilege::CanKick(CanKick::Yes));
+    data.maintain_hierarchy(&roles).unwrap();
+    assert!(matches!(data.value, Privilege::CanKick(CanKick::No)));
+
+    // admins get no more than the owner, who can always kick
+    let mut data = PrivilegeChangeData::new(Uuid::new_v4(), Role::Admin, Privilege::CanKick(CanKick::Yes));
+    data.maintain_hierarchy(&roles).unwrap();
+    assert!(matches!(data.value, Privilege::CanKick(CanKick::Yes)));
 }
 
-#[sqlx::test(fixtures("users", "groups", "roles", "group_roles"))]
+#[test]
+fn gates_from_settings_health_check() {
+    let gates = Gates::from_settings(&GatesSettings::default()).unwrap();
+    let owner = RolePosition { role: Role::Owner.into(), position: 2 };
+    let user_id = Uuid::new_v4();
+
+    // nobody kicks the owner, not even the owner
+    assert!(!gates.kick.verify(owner, owner, (user_id, user_id)));
+
+    let settings = GatesSettings {
+        ban: GateSettings {
+            requirements: HashMap::from([("admin".into(), 1), ("owner".into(), 1)]),
+            allow_self: false,
+        },
+        ..Default::default()
+    };
+    let gates = Gates::from_settings(&settings).unwrap();
+    let admin = RolePosition { role: Role::Admin.into(), position: 1 };
+    assert!(!gates.ban.verify(owner, admin, (user_id, Uuid::new_v4())));
+}
+
+#[test]
+fn gates_from_settings_rejects_invalid_settings() {
+    let invalid = [
+        HashMap::from([("moderator".into(), 1)]),
+        HashMap::from([("admin".into(), -1)]),
+        HashMap::from([("member".into(), 2), ("admin".into(), 1)]),
+        HashMap::from([("admin".into(), 2), ("owner".into(), 1)]),
+    ];
+    for requirements in invalid {
+        let settings = GatesSettings {
+            mute: GateSettings { requirements, allow_self: false },
+            ..Default::default()
+        };
+        assert!(Gates::from_settings(&settings).is_err());
+    }
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
 async fn single_set_group_role_privileges_health_check(db: PgPool) {
-    let data = PrivilegeChangeData {
+    let mut data = PrivilegeChangeData {
         group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
-        role: Role::Member,
+        role: Role::Member.into(),
         value: Privilege::CanInvite(CanInvite::No),
     };
 
@@ -260,30 +381,41 @@ async fn single_set_group_role_privileges_health_check(db: PgPool) {
     // ]));
 
     // data.maintain_hierarchy(&old_privileges).await.unwrap();
-    single_set_group_role_privileges(&db, &data).await.unwrap();
+    single_set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &mut data).await.unwrap();
 
-    let query_res = query!(
-        r#"
-            select roles.can_invite, roles.can_send_messages
-                from group_roles join roles on group_roles.role_id = roles.id
-                where group_roles.group_id = $1
-                and group_roles.role_type = $2
-        "#,
-        data.group_id,
-        data.role as Role,
+    let roles = get_group_roles(&db, &data.group_id).await.unwrap();
+    let res = &roles.get(&Role::Member.into()).unwrap().privileges;
+    assert!(!res.satisfies(Privilege::CanInvite(CanInvite::Yes)));
+    assert!(res.satisfies(Privilege::CanSendMessages(CanSendMessages::Yes(10))));
+    assert!(!res.satisfies(Privilege::CanExport(CanExport::Yes)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn get_user_privileges_owner_can_export(db: PgPool) {
+    // Adam - Chadders owner
+    let res = get_user_privileges(
+        &db,
+        &Uuid::parse_str(ADIMAC_ID).unwrap(),
+        &Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
     )
-    .fetch_one(&db)
     .await
-    .unwrap();
+    .expect("Query failed");
 
-    let res = Privileges::try_from(PrivilegeInterpretationData::new(query_res.can_invite, query_res.can_send_messages)).unwrap();
-    assert_eq!(
-        res,
-        Privileges::from([
-            Privilege::CanInvite(CanInvite::No),
-            Privilege::CanSendMessages(CanSendMessages::Yes(10)),
-        ])
+    assert!(res.satisfies(Privilege::CanExport(CanExport::Yes)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn get_user_privileges_member_cannot_export(db: PgPool) {
+    // Marco - Chadders member
+    let res = get_user_privileges(
+        &db,
+        &Uuid::parse_str(MARCO_ID).unwrap(),
+        &Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
     )
+    .await
+    .expect("Query failed");
+
+    assert!(!res.satisfies(Privilege::CanExport(CanExport::Yes)));
 }
 
 // #[sqlx::test(fixtures("users", "groups", "roles", "group_roles"))]
@@ -313,7 +445,7 @@ async fn single_set_group_role_privileges_health_check(db: PgPool) {
 //     ]));
 
 //     data.maintain_hierarchy(&old_privileges).await.unwrap();
-//     single_set_group_role_privileges(&db, &data).await.unwrap();
+//     single_set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &mut data).await.unwrap();
 
 //     let query_res = query!(
 //         r#"
@@ -346,10 +478,10 @@ async fn single_set_group_user_role_health_check (db: PgPool) {
     let data = UserRoleChangeData {
         group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
         user_id: Uuid::parse_str(MARCO_ID).unwrap(),
-        value: Role::Admin,
+        value: Role::Admin.into(),
     };
 
-    single_set_group_user_role(&db, &data).await.unwrap();
+    single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await.unwrap();
 
     let query_res = query!(
         r#"
@@ -370,3 +502,419 @@ async fn single_set_group_user_role_health_check (db: PgPool) {
         (Uuid::parse_str(MARCO_ID).unwrap(), Role::Admin),
     )
 }
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_user_role_records_system_message(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let data = UserRoleChangeData {
+        group_id,
+        user_id: Uuid::parse_str(MARCO_ID).unwrap(),
+        value: Role::Admin.into(),
+    };
+
+    let message = single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await.unwrap();
+    assert_eq!(message.content, "Adimac93 changed the role of Marco to admin");
+
+    let messages = fetch_last_messages_in_range(&db, &group_id, 10, 0).await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].kind, MessageKind::System);
+    assert!(matches!(
+        &messages[0].event,
+        Some(SystemEvent::RoleChanged { role: RoleRef::Kind(Role::Admin), nickname, .. }) if nickname == "Marco"
+    ));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_user_role_is_audited(db: PgPool) {
+    let data = UserRoleChangeData {
+        group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        user_id: Uuid::parse_str(MARCO_ID).unwrap(),
+        value: Role::Admin.into(),
+    };
+
+    single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await.unwrap();
+
+    let filter = AuditFilter {
+        target_user_id: Some(data.user_id),
+        ..Default::default()
+    };
+    let log = fetch_audit_log(&db, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data.group_id, filter)
+        .await
+        .unwrap();
+
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].action, AuditAction::RoleChanged);
+    assert_eq!(log[0].before, Some(serde_json::json!("member")));
+    assert_eq!(log[0].after, Some(serde_json::json!("admin")));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_user_role_admin_cannot_grant_admin(db: PgPool) {
+    let data = UserRoleChangeData {
+        group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        user_id: Uuid::parse_str(MARCO_ID).unwrap(),
+        value: Role::Admin.into(),
+    };
+
+    let res = single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(HUBERT_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+    assert_eq!(get_user_role(&db, &data.user_id, &data.group_id).await.unwrap(), Role::Member);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_user_role_cannot_grant_owner(db: PgPool) {
+    // an admin promoting themselves to the owner
+    let data = UserRoleChangeData {
+        group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        user_id: Uuid::parse_str(HUBERT_ID).unwrap(),
+        value: Role::Owner.into(),
+    };
+
+    let res = single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(HUBERT_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::OwnershipTransferRequired)));
+
+    // the owner can't hand it over either
+    let res = single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::OwnershipTransferRequired)));
+    assert_eq!(get_user_role(&db, &data.user_id, &data.group_id).await.unwrap(), Role::Admin);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_user_role_cannot_demote_owner(db: PgPool) {
+    let data = UserRoleChangeData {
+        group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        user_id: Uuid::parse_str(ADIMAC_ID).unwrap(),
+        value: Role::Member.into(),
+    };
+
+    let res = single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(HUBERT_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::OwnershipTransferRequired)));
+    assert_eq!(get_user_role(&db, &data.user_id, &data.group_id).await.unwrap(), Role::Owner);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_role_privileges_requires_higher_role(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let gate = Gates::new().manage_roles;
+
+    // a member can't change anything
+    let mut data = PrivilegeChangeData::new(group_id, Role::Member, Privilege::CanInvite(CanInvite::Yes));
+    let res = single_set_group_role_privileges(&db, &gate, &Uuid::parse_str(MARCO_ID).unwrap(), &mut data).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+
+    // an admin can't change the privileges of their own role
+    let mut data = PrivilegeChangeData::new(group_id, Role::Admin, Privilege::CanInvite(CanInvite::No));
+    let res = single_set_group_role_privileges(&db, &gate, &Uuid::parse_str(HUBERT_ID).unwrap(), &mut data).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+
+    // but can change the member ones
+    let mut data = PrivilegeChangeData::new(group_id, Role::Member, Privilege::CanInvite(CanInvite::No));
+    single_set_group_role_privileges(&db, &gate, &Uuid::parse_str(HUBERT_ID).unwrap(), &mut data).await.unwrap();
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_role_privileges_own_role_with_allow_self(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let settings = GatesSettings {
+        manage_roles: GateSettings { requirements: HashMap::new(), allow_self: true },
+        ..Default::default()
+    };
+    let gate = Gates::from_settings(&settings).unwrap().manage_roles;
+
+    // allowing self only concerns members acting on themselves, not on the role they hold
+    let mut data = PrivilegeChangeData::new(group_id, Role::Admin, Privilege::CanEditSettings(CanEditSettings::Yes));
+    let res = single_set_group_role_privileges(&db, &gate, &Uuid::parse_str(HUBERT_ID).unwrap(), &mut data).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_role_privileges_keeps_other_groups(db: PgPool) {
+    let mut data = PrivilegeChangeData::new(
+        Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        Role::Member,
+        Privilege::CanSendMessages(CanSendMessages::No),
+    );
+    single_set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &mut data).await.unwrap();
+
+    // Giga-chadders
+    let res = get_group_role_privileges(&db, Uuid::parse_str("347ac024-f8c9-4450-850f-9d85fb17c957").unwrap())
+        .await
+        .unwrap();
+    assert!(res.0[&RoleRef::Kind(Role::Member)].satisfies(Privilege::CanSendMessages(CanSendMessages::Yes(15))));
+    assert!(res.0[&RoleRef::Kind(Role::Admin)].satisfies(Privilege::CanSendMessages(CanSendMessages::Yes(3))));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn single_set_group_role_privileges_maintains_hierarchy(db: PgPool) {
+    // Chadders admins wait 2 seconds between messages, members can't be faster
+    let mut data = PrivilegeChangeData::new(
+        Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        Role::Member,
+        Privilege::CanSendMessages(CanSendMessages::Yes(0)),
+    );
+    single_set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &mut data).await.unwrap();
+    assert!(matches!(data.value, Privilege::CanSendMessages(CanSendMessages::Yes(2))));
+
+    let res = get_group_role_privileges(&db, data.group_id).await.unwrap();
+    assert!(matches!(
+        res.0[&RoleRef::Kind(Role::Member)].0.get(&data.value),
+        Some(Privilege::CanSendMessages(CanSendMessages::Yes(2)))
+    ));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_role_privileges_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let data = GroupPrivilegesChangeData {
+        group_id,
+        privileges: GroupRolePrivileges(HashMap::from([
+            (Role::Admin.into(), Privileges::from([
+                Privilege::CanInvite(CanInvite::No),
+                Privilege::CanSendMessages(CanSendMessages::Yes(1)),
+            ])),
+            (Role::Member.into(), Privileges::from([
+                Privilege::CanInvite(CanInvite::No),
+            ])),
+        ])),
+    };
+
+    let (privileges, message) = set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data)
+        .await
+        .unwrap();
+    assert_eq!(privileges, get_group_role_privileges(&db, group_id).await.unwrap());
+    assert!(matches!(
+        message.unwrap().event,
+        Some(SystemEvent::PrivilegesChanged { changes, .. }) if changes.len() == 3
+    ));
+
+    let res = get_group_role_privileges(&db, group_id).await.unwrap();
+    assert!(!res.0[&RoleRef::Kind(Role::Admin)].satisfies(Privilege::CanInvite(CanInvite::Yes)));
+    assert!(res.0[&RoleRef::Kind(Role::Admin)].satisfies(Privilege::CanSendMessages(CanSendMessages::Yes(1))));
+    assert!(!res.0[&RoleRef::Kind(Role::Member)].satisfies(Privilege::CanInvite(CanInvite::Yes)));
+    // left out privileges keep their values
+    assert!(res.0[&RoleRef::Kind(Role::Member)].satisfies(Privilege::CanSendMessages(CanSendMessages::Yes(10))));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_role_privileges_rejects_broken_hierarchy(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    // members would send messages faster than admins
+    let data = GroupPrivilegesChangeData {
+        group_id,
+        privileges: GroupRolePrivileges(HashMap::from([
+            (Role::Admin.into(), Privileges::from([Privilege::CanInvite(CanInvite::No)])),
+            (Role::Member.into(), Privileges::from([Privilege::CanSendMessages(CanSendMessages::Yes(1))])),
+        ])),
+    };
+
+    let res = set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::HierarchyViolation)));
+
+    // nothing was applied
+    let res = get_group_role_privileges(&db, group_id).await.unwrap();
+    assert!(res.0[&RoleRef::Kind(Role::Admin)].satisfies(Privilege::CanInvite(CanInvite::Yes)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_role_privileges_admin_cannot_change_admins(db: PgPool) {
+    let data = GroupPrivilegesChangeData {
+        group_id: Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap(),
+        privileges: GroupRolePrivileges(HashMap::from([
+            (Role::Admin.into(), Privileges::from([Privilege::CanSendMessages(CanSendMessages::Yes(0))])),
+            (Role::Member.into(), Privileges::from([Privilege::CanInvite(CanInvite::No)])),
+        ])),
+    };
+
+    let res = set_group_role_privileges(&db, &Gates::new().manage_roles, &Uuid::parse_str(HUBERT_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_user_roles_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let data = vec![
+        UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), Role::Admin),
+        UserRoleChangeData::new(group_id, Uuid::parse_str(HUBERT_ID).unwrap(), Role::Member),
+        UserRoleChangeData::new(group_id, Uuid::parse_str(POLO_ID).unwrap(), Role::Member),
+    ];
+
+    let (changes, message) = set_group_user_roles(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data)
+        .await
+        .unwrap();
+
+    // Polo already was a member
+    assert_eq!(changes.len(), 2);
+    assert_eq!(message.unwrap().content, "Adimac93 changed roles: Marco to admin, HubertK05 to member");
+    assert_eq!(get_user_role(&db, &Uuid::parse_str(MARCO_ID).unwrap(), &group_id).await.unwrap(), Role::Admin);
+    assert_eq!(get_user_role(&db, &Uuid::parse_str(HUBERT_ID).unwrap(), &group_id).await.unwrap(), Role::Member);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn set_group_user_roles_is_atomic(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let data = vec![
+        UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), Role::Admin),
+        UserRoleChangeData::new(group_id, Uuid::parse_str(HUBERT_ID).unwrap(), Role::Owner),
+    ];
+
+    let res = set_group_user_roles(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::OwnershipTransferRequired)));
+    assert_eq!(get_user_role(&db, &Uuid::parse_str(MARCO_ID).unwrap(), &group_id).await.unwrap(), Role::Member);
+
+    // a member can appear only once
+    let data = vec![
+        UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), Role::Admin),
+        UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), Role::Member),
+    ];
+    let res = set_group_user_roles(&db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &data).await;
+    assert!(matches!(res, Err(RoleError::RoleChangeRejection)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn get_user_role_not_in_group(db: PgPool) {
+    // Adam is not in Giga-chadders
+    let res = get_user_role(
+        &db,
+        &Uuid::parse_str(ADIMAC_ID).unwrap(),
+        &Uuid::parse_str("347ac024-f8c9-4450-850f-9d85fb17c957").unwrap(),
+    )
+    .await;
+
+    assert!(matches!(res, Err(RoleError::UserNotFound)));
+}
+
+async fn create_moderator_role(db: &PgPool, group_id: Uuid) -> Uuid {
+    let role = NewCustomRole { name: "Moderator".into(), color: "#1ABC9C".into() };
+    let roles = create_custom_role(db, &Gates::new().manage_roles, &Uuid::parse_str(ADIMAC_ID).unwrap(), &group_id, &role)
+        .await
+        .unwrap();
+    roles.0.iter().find(|role| role.kind.is_none()).unwrap().id
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_custom_role_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let role_id = create_moderator_role(&db, group_id).await;
+
+    let roles = get_group_roles(&db, &group_id).await.unwrap();
+    let order: Vec<(Option<Role>, i32)> = roles.0.iter().map(|role| (role.kind, role.position)).collect();
+    assert_eq!(order, vec![(Some(Role::Member), 0), (None, 1), (Some(Role::Admin), 2), (Some(Role::Owner), 3)]);
+
+    // starts with the privileges of the member role
+    let role = roles.get(&RoleRef::Id(role_id)).unwrap();
+    assert_eq!(role.name, "Moderator");
+    assert_eq!(role.color, "#1abc9c");
+    assert_eq!(role.rank, Role::Member);
+    assert!(matches!(
+        role.privileges.0.get(&Privilege::CanSendMessages(CanSendMessages::No)),
+        Some(Privilege::CanSendMessages(CanSendMessages::Yes(10)))
+    ));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_custom_role_rejects_invalid_data(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let gate = Gates::new().manage_roles;
+    let adimac_id = Uuid::parse_str(ADIMAC_ID).unwrap();
+
+    let role = NewCustomRole { name: "  ".into(), color: "#1abc9c".into() };
+    let res = create_custom_role(&db, &gate, &adimac_id, &group_id, &role).await;
+    assert!(matches!(res, Err(RoleError::InvalidRoleName)));
+
+    let role = NewCustomRole { name: "Moderator".into(), color: "teal".into() };
+    let res = create_custom_role(&db, &gate, &adimac_id, &group_id, &role).await;
+    assert!(matches!(res, Err(RoleError::InvalidRoleColor)));
+
+    // members don't outrank the place of the new role
+    let role = NewCustomRole { name: "Moderator".into(), color: "#1abc9c".into() };
+    let res = create_custom_role(&db, &gate, &Uuid::parse_str(MARCO_ID).unwrap(), &group_id, &role).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+
+    let res = update_custom_role(&db, &gate, &adimac_id, &group_id, &Uuid::parse_str("f3c322e4-c1b0-41d4-a47e-afbb217d931a").unwrap(), &CustomRoleUpdate::default()).await;
+    assert!(matches!(res, Err(RoleError::BuiltInRole)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn custom_role_can_be_assigned(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let role_id = create_moderator_role(&db, group_id).await;
+
+    // admins outrank the new role
+    let data = UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), role_id);
+    let message = single_set_group_user_role(&db, &Gates::new().manage_roles, &Uuid::parse_str(HUBERT_ID).unwrap(), &data)
+        .await
+        .unwrap();
+    assert_eq!(message.content, "HubertK05 changed the role of Marco to Moderator");
+
+    let member = get_member_role(&db, &data.user_id, &group_id).await.unwrap();
+    assert_eq!(member.role, RoleRef::Id(role_id));
+    assert_eq!(member.position, 1);
+    assert_eq!(get_user_role(&db, &data.user_id, &group_id).await.unwrap(), Role::Member);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn reorder_roles_health_check(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let gate = Gates::new().manage_roles;
+    let adimac_id = Uuid::parse_str(ADIMAC_ID).unwrap();
+    let role_id = create_moderator_role(&db, group_id).await;
+    let data = UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), role_id);
+    single_set_group_user_role(&db, &gate, &adimac_id, &data).await.unwrap();
+
+    let order = RoleOrder { roles: vec![Role::Admin.into(), role_id.into()] };
+
+    // an admin can't move their own role
+    let res = reorder_roles(&db, &gate, &Uuid::parse_str(HUBERT_ID).unwrap(), &group_id, &order).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+
+    // moderators would send messages slower and moderate less than admins below them
+    let res = reorder_roles(&db, &gate, &adimac_id, &group_id, &order).await;
+    assert!(matches!(res, Err(RoleError::HierarchyViolation)));
+
+    let privileges = GroupPrivilegesChangeData {
+        group_id,
+        privileges: GroupRolePrivileges(HashMap::from([
+            (role_id.into(), Privileges::from([
+                Privilege::CanSendMessages(CanSendMessages::Yes(2)),
+                Privilege::CanKick(CanKick::Yes),
+                Privilege::CanBan(CanBan::Yes),
+                Privilege::CanMute(CanMute::Yes),
+                Privilege::CanDeleteMessages(CanDeleteMessages::Yes),
+                Privilege::CanPin(CanPin::Yes),
+                Privilege::CanManageInvitations(CanManageInvitations::Yes),
+                Privilege::CanManageRoles(CanManageRoles::Yes),
+                Privilege::CanManageNicknames(CanManageNicknames::Yes),
+            ])),
+        ])),
+    };
+    set_group_role_privileges(&db, &gate, &adimac_id, &privileges).await.unwrap();
+
+    let roles = reorder_roles(&db, &gate, &adimac_id, &group_id, &order).await.unwrap();
+    let moderator = roles.get(&RoleRef::Id(role_id)).unwrap();
+    assert_eq!(moderator.position, 2);
+    assert_eq!(moderator.rank, Role::Admin);
+
+    // Marco counts as an admin now
+    assert_eq!(get_user_role(&db, &data.user_id, &group_id).await.unwrap(), Role::Admin);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn delete_custom_role_moves_members(db: PgPool) {
+    let group_id = Uuid::parse_str("b8c9a317-a456-458f-af88-01d99633f8e2").unwrap();
+    let gate = Gates::new().manage_roles;
+    let adimac_id = Uuid::parse_str(ADIMAC_ID).unwrap();
+    let role_id = create_moderator_role(&db, group_id).await;
+    let data = UserRoleChangeData::new(group_id, Uuid::parse_str(MARCO_ID).unwrap(), role_id);
+    single_set_group_user_role(&db, &gate, &adimac_id, &data).await.unwrap();
+
+    // members don't outrank it
+    let res = delete_custom_role(&db, &gate, &Uuid::parse_str(POLO_ID).unwrap(), &group_id, &role_id).await;
+    assert!(matches!(res, Err(RoleError::InsufficientPrivileges)));
+
+    let roles = delete_custom_role(&db, &gate, &adimac_id, &group_id, &role_id).await.unwrap();
+    let order: Vec<(Option<Role>, i32)> = roles.0.iter().map(|role| (role.kind, role.position)).collect();
+    assert_eq!(order, vec![(Some(Role::Member), 0), (Some(Role::Admin), 1), (Some(Role::Owner), 2)]);
+
+    let member = get_member_role(&db, &data.user_id, &group_id).await.unwrap();
+    assert_eq!(member.role, RoleRef::Kind(Role::Member));
+}
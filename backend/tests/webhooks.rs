@@ -0,0 +1,455 @@
+use axum::{http::HeaderMap, http::StatusCode, routing::post, Router};
+use backend::configuration::WebhookSettings;
+use backend::modules::external_api::HttpClient;
+use backend::utils::chat::{messages::fetch_last_messages_in_range, models::MessageKind};
+use backend::utils::groups::{try_add_user_to_group, try_remove_user_from_group};
+use backend::utils::webhooks::events::{
+    create_event_webhook, deliver_pending_events, fetch_event_deliveries, retry_delay,
+    sign_payload, MAX_RETRY_DELAY, SIGNATURE_HEADER,
+};
+use backend::utils::webhooks::models::{DeliveryStatus, GroupEventKind};
+use backend::utils::webhooks::{
+    create_webhook, errors::WebhookError, fetch_group_webhooks, post_webhook_message,
+    revoke_webhook, WEBHOOK_RATE_LIMIT,
+};
+use serde_json::Value;
+use sqlx::{query, PgPool};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uuid::Uuid;
+
+const GROUP_ID: &str = "b8c9a317-a456-458f-af88-01d99633f8e2";
+const OWNER_ID: &str = "ba34ff10-4b89-44cb-9b36-31eb57c41556";
+const ADMIN_ID: &str = "263541a8-fa1e-4f13-9e5d-5b250a5a71e6";
+const MEMBER_ID: &str = "4bd30a6a-7dfe-46a2-b741-f49612aa85c1";
+const NEW_USER_ID: &str = "e287ccab-fb33-4314-8d81-bfa9d6e52928";
+/// Event webhooks of the tests post to a listener on the loopback interface
+const LOCAL_TARGETS: WebhookSettings = WebhookSettings { allow_private_targets: true };
+
+type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+/// Local HTTP listener recording every request and answering with `status`
+fn spawn_listener(status: StatusCode) -> (String, Received) {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+
+    let app = Router::new().route(
+        "/events",
+        post({
+            let received = received.clone();
+            move |headers: HeaderMap, body: String| async move {
+                received.lock().unwrap().push((headers, body));
+                status
+            }
+        }),
+    );
+
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service())
+            .await
+            .unwrap()
+    });
+
+    (format!("http://{addr}/events"), received)
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn post_webhook_message_health_check(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(ADMIN_ID).unwrap();
+
+    let webhook = create_webhook(&pool, &user_id, &group_id, "CI")
+        .await
+        .unwrap();
+
+    let (target, message) =
+        post_webhook_message(&pool, &webhook.id, &webhook.token, "Build #42 failed")
+            .await
+            .unwrap();
+    assert_eq!(target, group_id);
+    assert_eq!(message.nickname, "CI");
+    assert_eq!(message.kind, MessageKind::Webhook);
+
+    let messages = fetch_last_messages_in_range(&pool, &group_id, 10, 0)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].nickname, "CI");
+    assert_eq!(messages[0].content, "Build #42 failed");
+    assert_eq!(message.id, messages[0].id);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_webhook_as_member(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(MEMBER_ID).unwrap();
+
+    let res = create_webhook(&pool, &user_id, &group_id, "CI").await;
+    assert!(matches!(res, Err(WebhookError::InsufficientPrivileges)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn post_webhook_message_invalid_token(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let webhook = create_webhook(&pool, &user_id, &group_id, "Monitoring")
+        .await
+        .unwrap();
+
+    let res = post_webhook_message(&pool, &webhook.id, "not-a-token", "Disk is full").await;
+    assert!(matches!(res, Err(WebhookError::InvalidToken)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn revoke_webhook_health_check(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let webhook = create_webhook(&pool, &user_id, &group_id, "Monitoring")
+        .await
+        .unwrap();
+    revoke_webhook(&pool, &user_id, &group_id, &webhook.id)
+        .await
+        .unwrap();
+
+    let webhooks = fetch_group_webhooks(&pool, &user_id, &group_id)
+        .await
+        .unwrap();
+    assert_eq!(webhooks.len(), 1);
+    assert!(webhooks[0].revoked_at.is_some());
+
+    let res = post_webhook_message(&pool, &webhook.id, &webhook.token, "Disk is full").await;
+    assert!(matches!(res, Err(WebhookError::WebhookRevoked)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn post_webhook_message_through_content_filters(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let webhook = create_webhook(&pool, &user_id, &group_id, "CI")
+        .await
+        .unwrap();
+
+    let filters = query!(
+        r#"
+            insert into group_content_filters (group_id, kind, pattern, action)
+            values ($1, 'word', 'flaky', 'flag'), ($1, 'word', 'darn', 'block')
+            returning id
+        "#,
+        group_id
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+
+    let res = post_webhook_message(&pool, &webhook.id, &webhook.token, "Darn, build #42 failed").await;
+    assert!(matches!(res, Err(WebhookError::MessageBlocked)));
+
+    let (_, message) = post_webhook_message(&pool, &webhook.id, &webhook.token, "Flaky test in build #43")
+        .await
+        .unwrap();
+
+    let flags = query!(
+        r#"
+            select filter_id, message_id from message_flags
+            where group_id = $1
+        "#,
+        group_id
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(flags.len(), 1);
+    assert_eq!(flags[0].filter_id, Some(filters[0].id));
+    assert_eq!(Some(flags[0].message_id), message.id);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn post_webhook_message_rate_limited(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let webhook = create_webhook(&pool, &user_id, &group_id, "Monitoring")
+        .await
+        .unwrap();
+
+    query!(
+        r#"
+            insert into messages (content, group_id, webhook_id, kind)
+            select 'Ping', $1, $2, 'webhook' from generate_series(1, $3)
+        "#,
+        group_id,
+        webhook.id,
+        WEBHOOK_RATE_LIMIT as i32
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let res = post_webhook_message(&pool, &webhook.id, &webhook.token, "Ping").await;
+    assert!(matches!(res, Err(WebhookError::RateLimited)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn post_webhook_message_rate_limited_concurrently(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let webhook = create_webhook(&pool, &user_id, &group_id, "Monitoring")
+        .await
+        .unwrap();
+
+    query!(
+        r#"
+            insert into messages (content, group_id, webhook_id, kind)
+            select 'Ping', $1, $2, 'webhook' from generate_series(2, $3)
+        "#,
+        group_id,
+        webhook.id,
+        WEBHOOK_RATE_LIMIT as i32
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // one message is left within the limit, only one of the racing posts gets it
+    let posts = (0..5).map(|_| post_webhook_message(&pool, &webhook.id, &webhook.token, "Ping"));
+    let results = futures::future::join_all(posts).await;
+    assert_eq!(results.iter().filter(|res| res.is_ok()).count(), 1);
+    assert!(results
+        .iter()
+        .filter_map(|res| res.as_ref().err())
+        .all(|err| matches!(err, WebhookError::RateLimited)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn event_webhook_signed_delivery(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let owner_id = Uuid::try_from(OWNER_ID).unwrap();
+    let new_user_id = Uuid::try_from(NEW_USER_ID).unwrap();
+    let (url, received) = spawn_listener(StatusCode::OK);
+
+    let webhook = create_event_webhook(
+        &pool,
+        &LOCAL_TARGETS,
+        &owner_id,
+        &group_id,
+        &url,
+        vec![GroupEventKind::MemberJoined],
+    )
+    .await
+    .unwrap();
+
+    try_add_user_to_group(&pool, &new_user_id, &group_id)
+        .await
+        .unwrap();
+
+    let delivered = deliver_pending_events(&pool, &HttpClient::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 1);
+
+    let received = received.lock().unwrap().clone();
+    assert_eq!(received.len(), 1);
+    let (headers, body) = &received[0];
+    assert_eq!(
+        headers[SIGNATURE_HEADER].to_str().unwrap(),
+        sign_payload(&webhook.secret, body.as_bytes())
+    );
+
+    let payload: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(payload["event"], "member_joined");
+    assert_eq!(payload["data"]["user_id"], NEW_USER_ID);
+
+    let log = fetch_event_deliveries(&pool, &owner_id, &group_id, &webhook.id, None)
+        .await
+        .unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].status, DeliveryStatus::Delivered);
+    assert_eq!(log[0].last_status_code, Some(200));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn event_webhook_member_left_delivery(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let owner_id = Uuid::try_from(OWNER_ID).unwrap();
+    let member_id = Uuid::try_from(MEMBER_ID).unwrap();
+    let (url, received) = spawn_listener(StatusCode::OK);
+
+    create_event_webhook(
+        &pool,
+        &LOCAL_TARGETS,
+        &owner_id,
+        &group_id,
+        &url,
+        vec![GroupEventKind::MemberLeft],
+    )
+    .await
+    .unwrap();
+
+    try_remove_user_from_group(&pool, member_id, group_id)
+        .await
+        .unwrap();
+
+    let delivered = deliver_pending_events(&pool, &HttpClient::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 1);
+
+    let received = received.lock().unwrap().clone();
+    let payload: Value = serde_json::from_str(&received[0].1).unwrap();
+    assert_eq!(payload["event"], "member_left");
+    assert_eq!(payload["data"]["user_id"], MEMBER_ID);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn event_webhook_webhook_message_delivery(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let owner_id = Uuid::try_from(OWNER_ID).unwrap();
+    let (url, received) = spawn_listener(StatusCode::OK);
+
+    create_event_webhook(
+        &pool,
+        &LOCAL_TARGETS,
+        &owner_id,
+        &group_id,
+        &url,
+        vec![GroupEventKind::MessageCreated],
+    )
+    .await
+    .unwrap();
+
+    let webhook = create_webhook(&pool, &owner_id, &group_id, "CI")
+        .await
+        .unwrap();
+    post_webhook_message(&pool, &webhook.id, &webhook.token, "Build #42 passed")
+        .await
+        .unwrap();
+
+    let delivered = deliver_pending_events(&pool, &HttpClient::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 1);
+
+    let received = received.lock().unwrap().clone();
+    let payload: Value = serde_json::from_str(&received[0].1).unwrap();
+    assert_eq!(payload["event"], "message_created");
+    assert_eq!(payload["data"]["user_id"], Value::Null);
+    assert_eq!(payload["data"]["webhook_id"], webhook.id.to_string());
+    assert_eq!(payload["data"]["content"], "Build #42 passed");
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn event_webhook_failed_delivery_is_retried(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let owner_id = Uuid::try_from(OWNER_ID).unwrap();
+    let new_user_id = Uuid::try_from(NEW_USER_ID).unwrap();
+    let (url, _received) = spawn_listener(StatusCode::INTERNAL_SERVER_ERROR);
+
+    let webhook = create_event_webhook(
+        &pool,
+        &LOCAL_TARGETS,
+        &owner_id,
+        &group_id,
+        &url,
+        vec![GroupEventKind::MemberJoined],
+    )
+    .await
+    .unwrap();
+
+    try_add_user_to_group(&pool, &new_user_id, &group_id)
+        .await
+        .unwrap();
+
+    let delivered = deliver_pending_events(&pool, &HttpClient::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 0);
+
+    let log = fetch_event_deliveries(&pool, &owner_id, &group_id, &webhook.id, None)
+        .await
+        .unwrap();
+    assert_eq!(log[0].status, DeliveryStatus::Pending);
+    assert_eq!(log[0].attempts, 1);
+    assert_eq!(log[0].last_status_code, Some(500));
+
+    // Not due again until the backoff passes
+    let delivered = deliver_pending_events(&pool, &HttpClient::new(), 10)
+        .await
+        .unwrap();
+    assert_eq!(delivered, 0);
+    let log = fetch_event_deliveries(&pool, &owner_id, &group_id, &webhook.id, None)
+        .await
+        .unwrap();
+    assert_eq!(log[0].attempts, 1);
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_event_webhook_as_admin(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let user_id = Uuid::try_from(ADMIN_ID).unwrap();
+
+    let res = create_event_webhook(
+        &pool,
+        &WebhookSettings::default(),
+        &user_id,
+        &group_id,
+        "http://localhost/events",
+        vec![GroupEventKind::MessageCreated],
+    )
+    .await;
+    assert!(matches!(res, Err(WebhookError::InsufficientPrivileges)));
+}
+
+#[sqlx::test(fixtures("users", "groups", "roles", "group_roles", "group_users"))]
+async fn create_event_webhook_private_target(pool: PgPool) {
+    let group_id = Uuid::try_from(GROUP_ID).unwrap();
+    let owner_id = Uuid::try_from(OWNER_ID).unwrap();
+
+    let urls = [
+        "http://localhost:8080/events",
+        "http://127.0.0.1/events",
+        "http://10.0.0.1/events",
+        "http://169.254.169.254/latest/meta-data",
+        "http://[::1]/events",
+        "http://[::ffff:192.168.0.1]/events",
+        "http://[fd00::1]/events",
+    ];
+    for url in urls {
+        let res = create_event_webhook(
+            &pool,
+            &WebhookSettings::default(),
+            &owner_id,
+            &group_id,
+            url,
+            vec![GroupEventKind::MessageCreated],
+        )
+        .await;
+        assert!(matches!(res, Err(WebhookError::PrivateUrl)), "{url} was accepted");
+    }
+
+    create_event_webhook(
+        &pool,
+        &WebhookSettings::default(),
+        &owner_id,
+        &group_id,
+        "https://93.184.216.34/events",
+        vec![GroupEventKind::MessageCreated],
+    )
+    .await
+    .unwrap();
+}
+
+#[test]
+fn retry_delay_backs_off_exponentially() {
+    assert_eq!(retry_delay(1), Duration::from_secs(10));
+    assert_eq!(retry_delay(2), Duration::from_secs(20));
+    assert_eq!(retry_delay(4), Duration::from_secs(80));
+    assert_eq!(retry_delay(20), MAX_RETRY_DELAY);
+}